@@ -0,0 +1,107 @@
+use crate::vecmath::{TransformationMatrix, Vec2d};
+
+// Looks at a part of the world and maps it onto the screen. It trails the
+// lander and zooms in when the ground comes close.
+
+// full zoom at this altitude and below, none above FAR_FROM_GROUND
+const NEAR_GROUND: f32 = 60.0;
+const FAR_FROM_GROUND: f32 = 250.0;
+const MAX_ZOOM: f32 = 2.0;
+// how quickly the camera catches up, per second
+const FOLLOW_RATE: f32 = 4.0;
+
+pub struct Camera {
+    // the world point in the middle of the screen
+    centre: Vec2d,
+    zoom: f32,
+    screen: Vec2d,
+}
+
+impl Camera {
+    pub fn new(screen_width: u32, screen_height: u32, centre: Vec2d) -> Self {
+        Camera {
+            centre,
+            zoom: 1.0,
+            screen: Vec2d::new(screen_width as f32, screen_height as f32),
+        }
+    }
+
+    // World to screen coordinates
+    pub fn transform(&self) -> TransformationMatrix {
+        TransformationMatrix::translation_v(self.screen / 2.0)
+            * TransformationMatrix::scale(self.zoom, self.zoom)
+            * TransformationMatrix::translation(-self.centre.x, -self.centre.y)
+    }
+
+    pub fn to_screen(&self, p: Vec2d) -> Vec2d {
+        self.transform().transform(&p)
+    }
+
+    // Eases towards `target`, `world` is the size of the world, which the
+    // camera doesn't look past
+    pub fn follow(&mut self, target: Vec2d, altitude: Option<f32>, dt: f32, world: Vec2d) {
+        let closeness = altitude.map_or(0.0, |a| ((FAR_FROM_GROUND - a) / (FAR_FROM_GROUND - NEAR_GROUND)).clamp(0.0, 1.0));
+        let ease = 1.0 - (-FOLLOW_RATE * dt).exp();
+        self.zoom += (1.0 + (MAX_ZOOM - 1.0) * closeness - self.zoom) * ease;
+        self.centre = self.centre + (target - self.centre) * ease;
+        self.keep_inside(world);
+    }
+
+    fn keep_inside(&mut self, world: Vec2d) {
+        let half = self.screen / (2.0 * self.zoom);
+        self.centre.x = if world.x <= 2.0 * half.x {
+            world.x / 2.0
+        } else {
+            self.centre.x.clamp(half.x, world.x - half.x)
+        };
+        // the sky goes on forever, the ground doesn't
+        self.centre.y = self.centre.y.min(world.y - half.y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORLD: Vec2d = Vec2d::new(3200.0, 600.0);
+
+    #[test]
+    fn world_points_land_on_the_screen() {
+        let mut camera = Camera::new(800, 600, Vec2d::new(1000.0, 300.0));
+        let centre = camera.to_screen(Vec2d::new(1000.0, 300.0));
+        assert_eq!((centre.x, centre.y), (400.0, 300.0));
+        let right = camera.to_screen(Vec2d::new(1100.0, 250.0));
+        assert_eq!((right.x, right.y), (500.0, 250.0));
+
+        camera.zoom = 2.0;
+        let right = camera.to_screen(Vec2d::new(1100.0, 250.0));
+        assert_eq!((right.x, right.y), (600.0, 200.0));
+    }
+
+    #[test]
+    fn follows_and_zooms_in_near_the_ground() {
+        let mut camera = Camera::new(800, 600, Vec2d::new(1000.0, 300.0));
+        for _ in 0..120 {
+            camera.follow(Vec2d::new(1600.0, 400.0), Some(500.0), 1.0 / 30.0, WORLD);
+        }
+        assert!((camera.centre.x - 1600.0).abs() < 1.0);
+        assert!((camera.zoom - 1.0).abs() < 0.01);
+
+        for _ in 0..120 {
+            camera.follow(Vec2d::new(1600.0, 400.0), Some(20.0), 1.0 / 30.0, WORLD);
+        }
+        assert!((camera.zoom - MAX_ZOOM).abs() < 0.01);
+    }
+
+    #[test]
+    fn stays_inside_the_world() {
+        let mut camera = Camera::new(800, 600, Vec2d::new(0.0, 900.0));
+        camera.follow(Vec2d::new(-500.0, 900.0), None, 1.0, WORLD);
+        let left = camera.to_screen(Vec2d::new(0.0, 600.0));
+        assert_eq!((left.x, left.y), (0.0, 600.0));
+
+        // a world narrower than the screen is centred
+        camera.follow(Vec2d::new(0.0, 0.0), None, 1.0, Vec2d::new(400.0, 600.0));
+        assert_eq!(camera.to_screen(Vec2d::new(200.0, 0.0)).x, 400.0);
+    }
+}
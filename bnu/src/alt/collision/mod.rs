@@ -1,90 +1,296 @@
-use crate::graphics;
 use crate::vecmath::*;
-use sdl2::rect::Rect;
-use sdl2::rect::Point;
-//use std::cmp::{min, max};
-
-fn is_on_segment(a: Vec2d, b: Vec2d, sample: Vec2d) -> bool
-{
-    let total = (a - b).len();
-    let dA = (a-sample).len();
-    let dB = (b-sample).len();
-    return ((dA + dB) - total).abs() < 0.001
+
+// Collision tests for the lander: convex polygons against each other and
+// against the terrain line, using the separating axis theorem. A hit comes
+// with a contact: the direction to push the body out, how deep it is in and
+// the points where it touches.
+
+// lengths and cross products below this count as zero
+const EPSILON: f32 = 1e-4;
+// vertices this close to the deepest one are touching too
+const CONTACT_SLOP: f32 = 0.5;
+
+#[derive(Clone, Debug)]
+pub struct Contact {
+    // unit vector from the other shape towards the body, e.g. out of the ground
+    pub normal: Vec2d,
+    pub depth: f32,
+    pub points: Vec<Vec2d>,
+}
+
+fn dot(a: Vec2d, b: Vec2d) -> f32 {
+    a.x * b.x + a.y * b.y
 }
 
-pub fn detect_collision(bbox: Vec<Vec2d>, points: &Vec<Vec2d>)
-    -> Option<Vec<(Vec2d, Vec2d)>>
-{
-    // create vector of bounding box lines
-    let mut bb_lines: Vec<(Vec2d, Vec2d)> = Vec::new();
-    for idx in 1..bbox.len() {
-        bb_lines.push((bbox[idx-1], bbox[idx]));
+fn cross(a: Vec2d, b: Vec2d) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+// Smallest and largest position of the points along `axis`
+fn project(points: &[Vec2d], axis: Vec2d) -> (f32, f32) {
+    points
+        .iter()
+        .map(|p| dot(*p, axis))
+        .fold((f32::MAX, f32::MIN), |(lo, hi), d| (lo.min(d), hi.max(d)))
+}
+
+// The unit normals of every edge, closing the polygon. Degenerate edges
+// have no normal, a segment has just one.
+fn edge_normals(points: &[Vec2d]) -> Vec<Vec2d> {
+    let edges = if points.len() == 2 { 1 } else { points.len() };
+    (0..edges)
+        .filter_map(|i| {
+            let edge = points[(i + 1) % points.len()] - points[i];
+            (edge.len() > EPSILON).then(|| Vec2d::new(-edge.y, edge.x) / edge.len())
+        })
+        .collect()
+}
+
+// Separating axis test of two convex shapes, polygons or segments. The
+// contact points are the vertices of `body` that reach into `other`.
+pub fn polygon_polygon(body: &[Vec2d], other: &[Vec2d]) -> Option<Contact> {
+    if body.is_empty() || other.is_empty() {
+        return None;
     }
-    bb_lines.push((bbox[bbox.len()-1], bbox[0]));
+    let mut best: Option<(Vec2d, f32)> = None;
+    for axis in edge_normals(body).into_iter().chain(edge_normals(other)) {
+        let (a_lo, a_hi) = project(body, axis);
+        let (b_lo, b_hi) = project(other, axis);
+        if a_hi < b_lo || b_hi < a_lo {
+            return None;
+        }
+        // the shorter way out, which also works for a segment with no inside
+        let (push, overlap) = if b_hi - a_lo < a_hi - b_lo {
+            (axis, b_hi - a_lo)
+        } else {
+            (axis * -1.0, a_hi - b_lo)
+        };
+        if best.is_none_or(|(_, depth)| overlap < depth) {
+            best = Some((push, overlap));
+        }
+    }
+    let (normal, depth) = best?;
 
-    let mut collisions: Vec<(Vec2d, Vec2d)> = Vec::new();
+    // how far every vertex of the body is behind the surface of `other`
+    let (_, surface) = project(other, normal);
+    let inside: Vec<(Vec2d, f32)> = body.iter().map(|p| (*p, surface - dot(*p, normal))).collect();
+    let deepest = inside.iter().map(|(_, d)| *d).fold(f32::MIN, f32::max);
+    let points = inside
+        .into_iter()
+        .filter(|(_, d)| *d >= deepest - CONTACT_SLOP)
+        .map(|(p, _)| p)
+        .collect();
+    Some(Contact { normal, depth, points })
+}
 
-    for idx in 1..points.len() {
-        for (a,b) in bb_lines.iter() {
-            if let Some(collision_point) = get_line_intersection(*a, *b, points[idx - 1], points[idx]) {
-                if !is_on_segment(*a, *b, collision_point) {continue;}
-                if !is_on_segment(points[idx - 1], points[idx], collision_point) {continue;}               
-                collisions.push((points[idx - 1], points[idx]));
-            }
+pub fn segment_polygon(a: Vec2d, b: Vec2d, body: &[Vec2d]) -> Option<Contact> {
+    polygon_polygon(body, &[a, b])
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vec2d,
+    pub max: Vec2d,
+}
+
+impl Aabb {
+    pub fn around(points: &[Vec2d]) -> Aabb {
+        let (min_x, max_x) = project(points, Vec2d::new(1.0, 0.0));
+        let (min_y, max_y) = project(points, Vec2d::new(0.0, 1.0));
+        Aabb {
+            min: Vec2d::new(min_x, min_y),
+            max: Vec2d::new(max_x, max_y),
         }
     }
 
-    if collisions.len() > 0 {
-        return Some(collisions);
-    } else {
-        return None;
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x && other.min.x <= self.max.x && self.min.y <= other.max.y && other.min.y <= self.max.y
     }
 }
 
-fn min(a: f32, b: f32) -> f32
-{
-    if a > b {
-        b
-    } else {
-        a
-    }
+// width of a broad phase cell
+const CELL_WIDTH: f32 = 64.0;
+// a swept body is tested at least every this far along its way, well below
+// the size of the lander so it can't skip over a spike
+const SWEEP_STEP: f32 = 2.0;
+// halvings to narrow down the moment of impact between two sweep steps
+const IMPACT_REFINEMENTS: u32 = 12;
+
+// Broad phase over the terrain: every column of the world knows the
+// segments that cross it, with their boxes, so a body is only tested
+// against the few segments below it
+pub struct TerrainGrid {
+    left: f32,
+    segments: Vec<(Vec2d, Vec2d, Aabb)>,
+    cells: Vec<Vec<usize>>,
 }
 
-fn max(a: f32, b: f32) -> f32
-{
-    if a > b {
-        a
-    } else {
-        b
+impl TerrainGrid {
+    // `points` is the terrain line, sorted by x
+    pub fn new(points: &[Vec2d]) -> TerrainGrid {
+        let left = points.first().map_or(0.0, |p| p.x);
+        let mut grid = TerrainGrid {
+            left,
+            segments: Vec::new(),
+            cells: Vec::new(),
+        };
+        for pair in points.windows(2) {
+            let (first, last) = (grid.cell(pair[0].x), grid.cell(pair[1].x));
+            if grid.cells.len() <= last {
+                grid.cells.resize(last + 1, Vec::new());
+            }
+            for cell in grid.cells[first..=last].iter_mut() {
+                cell.push(grid.segments.len());
+            }
+            grid.segments.push((pair[0], pair[1], Aabb::around(pair)));
+        }
+        grid
+    }
+
+    fn cell(&self, x: f32) -> usize {
+        ((x - self.left) / CELL_WIDTH).max(0.0) as usize
     }
-}
 
-fn get_line_intersection(p1: Vec2d, p2: Vec2d, p3: Vec2d, p4: Vec2d) -> Option<Vec2d>
-{
-    let s1_x = p2.x - p1.x;
-    let s2_x = p4.x - p3.x;
-    let s1_y = p2.y - p1.y;
-    let s2_y = p4.y - p3.y;
+    // Segments whose box overlaps `area`, each once and in order
+    pub fn candidates(&self, area: &Aabb) -> Vec<(Vec2d, Vec2d)> {
+        if self.cells.is_empty() {
+            return Vec::new();
+        }
+        let last = self.cell(area.max.x).min(self.cells.len() - 1);
+        let first = self.cell(area.min.x).min(last);
+        let mut indices: Vec<usize> = self.cells[first..=last].iter().flatten().copied().collect();
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+            .into_iter()
+            .map(|i| self.segments[i])
+            .filter(|(_, _, bounds)| bounds.overlaps(area))
+            .map(|(a, b, _)| (a, b))
+            .collect()
+    }
 
-    let s = (-s1_y * (p1.x - p2.x) + s1_x * (p1.y - p3.y)) / (-s2_x * s1_y + s1_x * s2_y);
-    let t = ( s2_x * (p1.y - p3.y) - s2_y * (p1.x - p3.x)) / (-s2_x * s1_y + s1_x * s2_y);
+    // The body against every terrain segment it may touch. The normal and
+    // depth are the deepest contact's, the points those of all contacts.
+    pub fn collide(&self, body: &[Vec2d]) -> Option<Contact> {
+        let mut result: Option<Contact> = None;
+        for (a, b) in self.candidates(&Aabb::around(body)) {
+            let Some(contact) = segment_polygon(a, b, body) else {
+                continue;
+            };
+            result = Some(match result {
+                None => contact,
+                Some(mut merged) => {
+                    merged.points.extend(contact.points.iter().copied());
+                    if contact.depth > merged.depth {
+                        merged.normal = contact.normal;
+                        merged.depth = contact.depth;
+                    }
+                    merged
+                }
+            });
+        }
+        result
+    }
 
-    if (s >= 0.0 && s <= 1.0 && t >= 0.0 && t <= 1.0)
-    {
-        // Collision detected
-        return Some(Vec2d::new(p1.x + (t * s1_x), p1.y + (t * s1_y)));
+    // The first moment a moving body touches the terrain, as the fraction of
+    // the move done, with the contact there. `body_at` places the body along
+    // its way from 0 to 1, `travel` is how far its furthest point goes.
+    pub fn time_of_impact(&self, body_at: impl Fn(f32) -> Vec<Vec2d>, travel: f32) -> Option<(f32, Contact)> {
+        let steps = (travel / SWEEP_STEP).ceil().max(1.0) as u32;
+        let mut free = 0.0;
+        for i in 0..=steps {
+            let t = i as f32 / steps as f32;
+            let Some(mut contact) = self.collide(&body_at(t)) else {
+                free = t;
+                continue;
+            };
+            if i == 0 {
+                return Some((0.0, contact));
+            }
+            // it is clear at `free` and touching at `hit`
+            let mut hit = t;
+            for _ in 0..IMPACT_REFINEMENTS {
+                let mid = (free + hit) / 2.0;
+                match self.collide(&body_at(mid)) {
+                    Some(closer) => {
+                        hit = mid;
+                        contact = closer;
+                    }
+                    None => free = mid,
+                }
+            }
+            return Some((hit, contact));
+        }
+        None
+    }
+
+    // Whether the segment from a to b crosses or touches the terrain
+    pub fn segment_hits(&self, a: Vec2d, b: Vec2d) -> bool {
+        self.candidates(&Aabb::around(&[a, b]))
+            .into_iter()
+            .any(|(c, d)| get_line_intersection(a, b, c, d).is_some())
     }
+}
 
-    return None
+fn distance_to_segment(p: Vec2d, a: Vec2d, b: Vec2d) -> f32 {
+    let ab = b - a;
+    let t = (dot(p - a, ab) / dot(ab, ab)).clamp(0.0, 1.0);
+    (a + ab * t - p).len()
 }
 
+// Where the segments p1-p2 and p3-p4 meet. Parallel segments only meet if
+// they lie on the same line and overlap, then it is the first shared point.
+fn get_line_intersection(p1: Vec2d, p2: Vec2d, p3: Vec2d, p4: Vec2d) -> Option<Vec2d> {
+    let s1 = p2 - p1;
+    let s2 = p4 - p3;
+    let denom = cross(s1, s2);
+    let scale = s1.len() * s2.len();
+
+    // a segment that is a point meets the other one where it lies on it
+    match (s1.len() <= EPSILON, s2.len() <= EPSILON) {
+        (true, true) => return ((p3 - p1).len() <= EPSILON).then_some(p1),
+        (true, false) => return (distance_to_segment(p1, p3, p4) <= EPSILON).then_some(p1),
+        (false, true) => return (distance_to_segment(p3, p1, p2) <= EPSILON).then_some(p3),
+        (false, false) => {}
+    }
+
+    if denom.abs() <= EPSILON * scale {
+        // parallel, they meet only on the same line
+        if cross(p3 - p1, s1).abs() > EPSILON * s1.len() {
+            return None;
+        }
+        let length = dot(s1, s1);
+        let t3 = dot(p3 - p1, s1) / length;
+        let t4 = dot(p4 - p1, s1) / length;
+        let start = t3.min(t4).max(0.0);
+        let end = t3.max(t4).min(1.0);
+        return (start <= end).then(|| p1 + s1 * start);
+    }
+
+    let s = cross(s1, p1 - p3) / denom;
+    let t = cross(s2, p1 - p3) / denom;
+    if (0.0..=1.0).contains(&s) && (0.0..=1.0).contains(&t) {
+        // Collision detected
+        return Some(p1 + s1 * t);
+    }
+    None
+}
 
 #[cfg(test)]
 mod tests {
 
-    use crate::collision;
+    use crate::alt::collision::*;
     use crate::vecmath::Vec2d;
 
+    fn square(x: f32, y: f32, size: f32) -> Vec<Vec2d> {
+        vec![
+            Vec2d::new(x, y),
+            Vec2d::new(x + size, y),
+            Vec2d::new(x + size, y + size),
+            Vec2d::new(x, y + size),
+        ]
+    }
+
     #[test]
     fn test_line_intersection()
     {
@@ -93,7 +299,7 @@ mod tests {
         let p3 = Vec2d::new(5.0, -5.0);
         let p4 = Vec2d::new(5.0, 5.0);
 
-        if let Some(result) = collision::get_line_intersection(p1, p2, p3, p4)
+        if let Some(result) = get_line_intersection(p1, p2, p3, p4)
         {
             assert_eq!(result.x, 5.0);
             assert_eq!(result.y, 0.0);
@@ -101,4 +307,86 @@ mod tests {
             assert!(false);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parallel_and_collinear_lines() {
+        let (a, b) = (Vec2d::new(0.0, 0.0), Vec2d::new(10.0, 0.0));
+        assert!(get_line_intersection(a, b, Vec2d::new(0.0, 1.0), Vec2d::new(10.0, 1.0)).is_none());
+        let shared = get_line_intersection(a, b, Vec2d::new(15.0, 0.0), Vec2d::new(5.0, 0.0)).unwrap();
+        assert_eq!((shared.x, shared.y), (5.0, 0.0));
+        assert!(get_line_intersection(a, b, Vec2d::new(11.0, 0.0), Vec2d::new(20.0, 0.0)).is_none());
+        // a point on the line, and one beside it
+        assert!(get_line_intersection(a, b, Vec2d::new(3.0, 0.0), Vec2d::new(3.0, 0.0)).is_some());
+        assert!(get_line_intersection(a, b, Vec2d::new(3.0, 1.0), Vec2d::new(3.0, 1.0)).is_none());
+        let on = get_line_intersection(Vec2d::new(3.0, 0.0), Vec2d::new(3.0, 0.0), a, b).unwrap();
+        assert_eq!((on.x, on.y), (3.0, 0.0));
+        // two points meet only where they are the same
+        let (p, q) = (Vec2d::new(3.0, 0.0), Vec2d::new(3.5, 0.0));
+        assert!(get_line_intersection(p, p, q, q).is_none());
+        assert!(get_line_intersection(p, p, p, p).is_some());
+    }
+
+    #[test]
+    fn polygons_overlap_along_the_shallowest_axis() {
+        let body = square(0.0, 0.0, 10.0);
+        assert!(polygon_polygon(&body, &square(11.0, 0.0, 10.0)).is_none());
+
+        let contact = polygon_polygon(&body, &square(8.0, 1.0, 10.0)).unwrap();
+        assert!((contact.depth - 2.0).abs() < 0.001);
+        assert_eq!((contact.normal.x, contact.normal.y), (-1.0, 0.0));
+        // the right edge of the body is inside
+        assert_eq!(contact.points.len(), 2);
+        assert!(contact.points.iter().all(|p| p.x == 10.0));
+    }
+
+    #[test]
+    fn a_box_sunk_into_flat_ground() {
+        // y grows downwards, the ground is at 100
+        let body = square(0.0, 93.0, 10.0);
+        let contact = segment_polygon(Vec2d::new(-50.0, 100.0), Vec2d::new(50.0, 100.0), &body).unwrap();
+        assert!((contact.depth - 3.0).abs() < 0.001);
+        assert!((contact.normal.y + 1.0).abs() < 0.001);
+        let feet: Vec<f32> = contact.points.iter().map(|p| p.y).collect();
+        assert_eq!(feet, [103.0, 103.0]);
+    }
+
+    #[test]
+    fn a_fast_box_does_not_pass_through_a_spike() {
+        // flat ground at 100 with a spike one unit wide up to 20
+        let terrain = [
+            Vec2d::new(0.0, 100.0),
+            Vec2d::new(300.0, 100.0),
+            Vec2d::new(300.5, 20.0),
+            Vec2d::new(301.0, 100.0),
+            Vec2d::new(600.0, 100.0),
+        ];
+        let grid = TerrainGrid::new(&terrain);
+        // from well left to well right of the spike in one frame
+        let body_at = |t: f32| square(100.0 + 400.0 * t, 40.0, 10.0);
+        assert!(grid.collide(&body_at(0.0)).is_none());
+        assert!(grid.collide(&body_at(1.0)).is_none());
+
+        let (t, contact) = grid.time_of_impact(body_at, 400.0).unwrap();
+        // the right edge reaches the spike at x 300
+        let x = 110.0 + 400.0 * t;
+        assert!(x > 299.5 && x < 300.6);
+        assert!(contact.normal.x < 0.0);
+        assert!(contact.points.iter().all(|p| (p.x - x).abs() < 0.01));
+
+        assert!(grid.time_of_impact(|t| square(100.0 + 400.0 * t, 0.0, 10.0), 400.0).is_none());
+    }
+
+    #[test]
+    fn the_grid_only_offers_segments_nearby() {
+        let terrain: Vec<Vec2d> = (0..=100).map(|i| Vec2d::new(i as f32 * 10.0, 100.0 + (i % 2) as f32)).collect();
+        let grid = TerrainGrid::new(&terrain);
+        let near = grid.candidates(&Aabb::around(&square(500.0, 90.0, 10.0)));
+        assert!(!near.is_empty() && near.len() <= 3);
+        assert!(grid.candidates(&Aabb::around(&square(500.0, 0.0, 10.0))).is_empty());
+
+        assert!(grid.collide(&square(500.0, 95.0, 10.0)).is_some());
+        assert!(grid.collide(&square(500.0, 80.0, 10.0)).is_none());
+        assert!(grid.segment_hits(Vec2d::new(5.0, 90.0), Vec2d::new(5.0, 110.0)));
+        assert!(!grid.segment_hits(Vec2d::new(5.0, 0.0), Vec2d::new(5.0, 50.0)));
+    }
+}
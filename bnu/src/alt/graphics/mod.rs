@@ -37,11 +37,12 @@ pub const LanderBoundBoxOrigin: Vec2d = Vec2d::new(-3.0, 4.0);
 pub const LanderWidth: u32 = 6;
 pub const LanderHeight: u32 = 7;
 
+// spanned from its top left corner
 pub const BBox: [Vec2d; 4] = [
-    Vec2d::new(-3.0, 4.0),
-    Vec2d::new(3.0, 4.0),
-    Vec2d::new(3.0, -3.0),
-    Vec2d::new(-3.0, -3.0),
+    LanderBoundBoxOrigin,
+    Vec2d::new(LanderBoundBoxOrigin.x + LanderWidth as f32, LanderBoundBoxOrigin.y),
+    Vec2d::new(LanderBoundBoxOrigin.x + LanderWidth as f32, LanderBoundBoxOrigin.y - LanderHeight as f32),
+    Vec2d::new(LanderBoundBoxOrigin.x, LanderBoundBoxOrigin.y - LanderHeight as f32),
 ];
 
 
@@ -49,18 +50,6 @@ pub const LeftLeg: [Vec2d; 2] = [Vec2d::new(-2.0, 0.0), Vec2d::new(-3.0, -3.0)];
 
 pub const RightLeg: [Vec2d; 2] = [Vec2d::new(2.0, 0.0), Vec2d::new(3.0, -3.0)];
 
-pub const FlameA: [Vec2d; 3] = [
-    Vec2d::new(-1.0, -3.0),
-    Vec2d::new(1.0, -3.0),
-    Vec2d::new(0.0, -6.0),
-];
-
-pub const FlameB: [Vec2d; 3] = [
-    Vec2d::new(-1.0, -3.0),
-    Vec2d::new(1.0, -3.0),
-    Vec2d::new(0.0, -4.0),
-];
-
 pub fn renderGameOver(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) {
     draw::draw_text_centered(
         canvas,
@@ -7,6 +7,15 @@ use sdl2::video::Window;
 
 pub struct Hud {
     position: Vec2d,
+    // above the ground right below, None outside of the map
+    altitude: Option<f32>,
+    // dy/dx of the ground right below
+    slope: Option<f32>,
+    // radians between the legs and the ground right below, 0 when they would
+    // both touch at once
+    lean: Option<f32>,
+    // multiplier of the nearest pad and how far away it is, negative is left
+    pad: Option<(u32, f32)>,
     direction: Vec2d,
     fuel: f32,
     deaths: u32,
@@ -16,21 +25,16 @@ impl Hud {
     pub fn new() -> Self {
         Self {
             position: Vec2d::new(0.0, 0.0),
+            altitude: None,
+            slope: None,
+            lean: None,
+            pad: None,
             direction: Vec2d::new(0.0, 0.0),
             fuel: 0.0,
             deaths: 0,
         }
     }
 
-    pub fn from(position: Vec2d, direction: Vec2d, fuel: f32, deaths: u32) -> Self {
-        Self {
-            position,
-            direction,
-            fuel,
-            deaths,
-        }
-    }
-
     pub fn update(&mut self, position: Vec2d, direction: Vec2d, fuel: f32, deaths: u32) {
         self.position = position;
         self.direction = direction;
@@ -38,24 +42,28 @@ impl Hud {
         self.deaths = deaths;
     }
 
-    pub fn updatePosition(&mut self, position: Vec2d) {
-        self.position = position;
-    }
-
-    pub fn updateDirection(&mut self, direction: Vec2d) {
-        self.direction = direction;
-    }
-
-    pub fn updateFuel(&mut self, fuel: f32) {
-        self.fuel = fuel;
-    }
-
-    pub fn updateDeaths(&mut self, deaths: u32) {
-        self.deaths = deaths;
+    pub fn update_ground(&mut self, altitude: Option<f32>, slope: Option<f32>, lean: Option<f32>, pad: Option<(u32, f32)>) {
+        self.altitude = altitude;
+        self.slope = slope;
+        self.lean = lean;
+        self.pad = pad;
     }
 
     pub fn render(&self, canvas: &mut Canvas<Window>) {
-        let hud_position = format!("Position: x = {}, y = {}", self.position.x, self.position.y);
+        let hud_position = match (self.altitude, self.slope) {
+            // +y is down, a positive slope falls to the right
+            (Some(altitude), Some(slope)) => match slope.atan().to_degrees() {
+                degrees if degrees.abs() < 1.0 => format!("Altitude: {:.0}, ground flat", altitude),
+                degrees => format!(
+                    "Altitude: {:.0}, ground {:.0} deg down to the {}",
+                    altitude,
+                    degrees.abs(),
+                    if degrees > 0.0 { "right" } else { "left" }
+                ),
+            },
+            (Some(altitude), None) => format!("Altitude: {:.0}", altitude),
+            _ => format!("Position: x = {}, y = {}", self.position.x, self.position.y),
+        };
         let hud_direction = format!(
             "Direction: x = {}, y = {}",
             self.direction.x, self.direction.y
@@ -95,5 +103,17 @@ impl Hud {
             Color::RGB(0, 255, 0),
         )
         .unwrap();
+        if let Some(lean) = self.lean {
+            let hud_lean = format!("Lean to the ground: {:.0} deg", lean.to_degrees().abs());
+            draw::draw_text(canvas, &hud_lean, 10, Point::new(0, 40), Color::RGB(0, 255, 0)).unwrap();
+        }
+        if let Some((multiplier, distance)) = self.pad {
+            let side = if distance < 0.0 { "left" } else { "right" };
+            let hud_pad = match distance.abs() {
+                d if d < 1.0 => format!("Pad x{}: below", multiplier),
+                d => format!("Pad x{}: {:.0} to the {}", multiplier, d, side),
+            };
+            draw::draw_text(canvas, &hud_pad, 10, Point::new(0, 50), Color::RGB(0, 255, 0)).unwrap();
+        }
     }
 }
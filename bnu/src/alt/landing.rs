@@ -0,0 +1,137 @@
+use crate::vecmath::Vec2d;
+
+// How the lander came down, measured the moment it touched the ground
+
+// speeds in pixels per second, +y is down
+const SOFT_SPEED: f32 = 5.0;
+const CRASH_SPEED: f32 = 10.0;
+const SOFT_DRIFT: f32 = 3.0;
+const CRASH_DRIFT: f32 = 8.0;
+// radians away from upright
+const SOFT_TILT: f32 = std::f32::consts::PI / 36.0;
+const MAX_TILT: f32 = std::f32::consts::PI / 12.0;
+
+pub struct Touchdown {
+    pub velocity: Vec2d,
+    pub tilt: f32,
+    // left and right leg on the ground
+    pub legs: [bool; 2],
+    // the multiplier of the pad it came down on, None if it missed
+    pub pad: Option<u32>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Verdict {
+    SoftLanding,
+    HardLanding,
+    Crash,
+    // down in one piece, but not on a pad
+    MissedPad,
+}
+
+impl Verdict {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Verdict::SoftLanding => "Soft landing",
+            Verdict::HardLanding => "Hard landing",
+            Verdict::Crash => "Crashed",
+            Verdict::MissedPad => "Missed the pad",
+        }
+    }
+
+    pub fn is_win(&self) -> bool {
+        matches!(self, Verdict::SoftLanding | Verdict::HardLanding)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Landing {
+    pub verdict: Verdict,
+    pub score: u32,
+}
+
+pub fn evaluate(t: &Touchdown) -> Landing {
+    let sink = t.velocity.y.max(0.0);
+    let drift = t.velocity.x.abs();
+    let tilt = t.tilt.abs();
+
+    let crashed = sink > CRASH_SPEED || drift > CRASH_DRIFT || tilt > MAX_TILT || !(t.legs[0] && t.legs[1]);
+    let verdict = if crashed {
+        Verdict::Crash
+    } else if t.pad.is_none() {
+        Verdict::MissedPad
+    } else if sink > SOFT_SPEED || drift > SOFT_DRIFT || tilt > SOFT_TILT {
+        Verdict::HardLanding
+    } else {
+        Verdict::SoftLanding
+    };
+
+    // up to 100 for how gently it came down, a bonus for where, all times
+    // the multiplier of the pad
+    let margin = |value: f32, limit: f32| (1.0 - value / limit).clamp(0.0, 1.0);
+    let gentleness = (margin(sink, CRASH_SPEED) + margin(drift, CRASH_DRIFT) + margin(tilt, MAX_TILT)) / 3.0;
+    let bonus = match verdict {
+        Verdict::SoftLanding => 100,
+        Verdict::HardLanding => 50,
+        Verdict::MissedPad => 0,
+        Verdict::Crash => return Landing { verdict, score: 0 },
+    };
+    Landing {
+        verdict,
+        score: ((gentleness * 100.0).round() as u32 + bonus) * t.pad.unwrap_or(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touchdown(sink: f32, drift: f32, tilt: f32) -> Touchdown {
+        Touchdown {
+            velocity: Vec2d::new(drift, sink),
+            tilt,
+            legs: [true, true],
+            pad: Some(1),
+        }
+    }
+
+    #[test]
+    fn speed_and_tilt_decide_how_hard_it_was() {
+        let soft = evaluate(&touchdown(1.0, 0.5, 0.0));
+        assert_eq!(soft.verdict, Verdict::SoftLanding);
+        assert_eq!(evaluate(&touchdown(7.0, 0.0, 0.0)).verdict, Verdict::HardLanding);
+        assert_eq!(evaluate(&touchdown(1.0, 5.0, 0.0)).verdict, Verdict::HardLanding);
+        assert_eq!(evaluate(&touchdown(1.0, 0.0, 0.2)).verdict, Verdict::HardLanding);
+
+        let hard = evaluate(&touchdown(9.0, 0.0, 0.0));
+        assert!(soft.score > hard.score);
+        assert_eq!(evaluate(&touchdown(12.0, 0.0, 0.0)), Landing { verdict: Verdict::Crash, score: 0 });
+        assert_eq!(evaluate(&touchdown(1.0, 0.0, 0.5)).verdict, Verdict::Crash);
+        // going up again doesn't count as falling
+        assert_eq!(evaluate(&touchdown(-3.0, 0.0, 0.0)).verdict, Verdict::SoftLanding);
+    }
+
+    #[test]
+    fn both_legs_on_a_pad() {
+        let one_leg = Touchdown {
+            legs: [true, false],
+            ..touchdown(1.0, 0.0, 0.0)
+        };
+        assert_eq!(evaluate(&one_leg).verdict, Verdict::Crash);
+
+        let beside = Touchdown {
+            pad: None,
+            ..touchdown(1.0, 0.0, 0.0)
+        };
+        let missed = evaluate(&beside);
+        assert_eq!(missed.verdict, Verdict::MissedPad);
+        assert!(!missed.verdict.is_win());
+        assert!(missed.score < evaluate(&touchdown(9.0, 0.0, 0.0)).score);
+
+        let narrow = Touchdown {
+            pad: Some(4),
+            ..touchdown(1.0, 0.5, 0.0)
+        };
+        assert_eq!(evaluate(&narrow).score, 4 * evaluate(&touchdown(1.0, 0.5, 0.0)).score);
+    }
+}
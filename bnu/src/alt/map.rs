@@ -1,130 +1,173 @@
 use crate::vecmath::Vec2d;
-use rand::distributions::Uniform;
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+// A flat stretch of ground to land on. Narrow pads pay more.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pad {
+    pub from: f32,
+    pub to: f32,
+    pub y: f32,
+    pub multiplier: u32,
+}
+
+impl Pad {
+    pub fn width(&self) -> f32 {
+        self.to - self.from
+    }
+
+    pub fn centre(&self) -> f32 {
+        (self.from + self.to) / 2.0
+    }
+
+    // Feet `stance` apart around x stand on the pad, each may be up to
+    // `reach` past its edge
+    pub fn holds(&self, x: f32, stance: f32, reach: f32) -> bool {
+        (x - self.centre()).abs() * 2.0 <= self.width() - stance + 2.0 * reach
+    }
+
+    pub fn contains(&self, x: f32) -> bool {
+        x >= self.from && x <= self.to
+    }
+
+    // How far to fly from x to get over the pad, negative is left
+    pub fn offset(&self, x: f32) -> f32 {
+        if self.contains(x) {
+            0.0
+        } else if x < self.from {
+            self.from - x
+        } else {
+            self.to - x
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct PointList {
-    window_height: Option<f32>,
+    world_height: Option<f32>,
     values: Vec<Vec2d>,
+    // sorted by x, never overlapping
+    pads: Vec<Pad>,
 }
 
-const X_MAX_DELTA: f32 = 10.0;
-const Y_MAX_DELTA: f32 = 200.0;
-const Y_DELTA_DIVIDOR: f32 = 1.70;
-const X_START_POINTS: usize = 5;
-const X_LANDING_AREA_SIZE: f32 = 40.0;
+// width, relative to the widest pad, and score multiplier of the pads
+const PAD_KINDS: [(f32, u32); 3] = [(1.0, 1), (0.75, 2), (0.6, 4)];
 
 impl PointList {
+    // The classic map, see terrain::TerrainConfig for others
+    #[cfg(test)]
     pub fn new(maxX: f32, maxY: f32) -> Self {
-        let mut start_points: Vec<Vec2d> = Vec::new();
-        let xStepSize = maxX / (X_START_POINTS as f32);
-        assert!(xStepSize > X_LANDING_AREA_SIZE);
-        for stepIdx in 0..=X_START_POINTS {
-            start_points.push(Vec2d::new(xStepSize * (stepIdx as f32), randomY(0.0, maxY)))
-        }
-        let landingPointsIdx =
-            Self::genLandingPointsIdx(2, Uniform::new(1, start_points.len() - 1));
-
-        let mut gen_map = Vec::new();
-
-        for idx in 0..(start_points.len() - 1) {
-            gen_map.push(start_points[idx].clone());
-            if landingPointsIdx.contains(&idx) {
-                let secLandingPoint =
-                    gen_map.last().unwrap().clone() + Vec2d::new(X_LANDING_AREA_SIZE, 0.0);
-                println!(
-                    "Landing platform, {:?} {:?}",
-                    gen_map.last().unwrap().clone(),
-                    secLandingPoint.clone()
-                );
-                gen_map.push(secLandingPoint);
-            }
-            split(
-                gen_map.last().unwrap().clone(),
-                start_points[idx + 1].clone(),
-                &mut gen_map,
-                X_MAX_DELTA,
-                Y_MAX_DELTA,
-            );
-        }
-        gen_map.push(start_points.last().unwrap().clone());
+        super::terrain::generate_with_width(&super::terrain::TerrainConfig::classic(), maxX, maxY)
+    }
 
+    // Ground along `profile`, sorted by x and with y up from the bottom, with
+    // `count` pads flattened into it. Every pad gets a slot of its own, so
+    // they never overlap.
+    pub fn with_pads(profile: Vec<Vec2d>, count: usize, pad_width: f32, rng: &mut ChaCha8Rng) -> Self {
         let mut n = PointList {
-            window_height: None,
-            values: Vec::from(gen_map),
+            world_height: None,
+            values: profile,
+            pads: Vec::new(),
+        };
+        let (Some(&first), Some(&last)) = (n.values.first(), n.values.last()) else {
+            return n;
         };
-        n.sort();
+        let (left, right) = (first.x, last.x);
+        if count == 0 || right <= left {
+            return n;
+        }
+        let slot = (right - left) / count as f32;
+        for i in 0..count {
+            let (relative, multiplier) = *PAD_KINDS.choose(rng).unwrap();
+            // narrow maps get smaller pads, a pad must fit into its slot
+            let width = (pad_width * relative).min(slot * 0.8);
+            let start = left + slot * i as f32 + (slot - width) / 2.0;
+            let from = start + rng.gen_range(-0.5..=0.5) * (slot - width) * 0.8;
+            let to = from + width;
+            let y = n.height_at((from + to) / 2.0).unwrap_or(first.y);
+            n.values.retain(|v| v.x < from || v.x > to);
+            n.values.push(Vec2d::new(from, y));
+            n.values.push(Vec2d::new(to, y));
+            n.sort();
+            n.pads.push(Pad { from, to, y, multiplier });
+        }
         n
     }
 
-    pub fn set_window_height(self: &mut Self, window_height: f32) {
-        if self.window_height.is_none() {
-            for val in self.values.iter_mut() {
-                val.y = window_height - val.y;
+    // Turns heights above the bottom into world coordinates, where y grows
+    // downwards from the top of a world `world_height` high
+    pub fn set_world_height(self: &mut Self, world_height: f32) {
+        let ys = self.values.iter_mut().map(|v| &mut v.y).chain(self.pads.iter_mut().map(|p| &mut p.y));
+        match self.world_height {
+            None => {
+                for y in ys {
+                    *y = world_height - *y;
+                }
             }
-        } else {
-            for val in self.values.iter_mut() {
-                val.y = window_height - (self.window_height.unwrap() - val.y)
+            Some(old_height) => {
+                for y in ys {
+                    *y = world_height - (old_height - *y)
+                }
             }
         }
-        self.window_height = Some(window_height);
+        self.world_height = Some(world_height);
     }
 
     pub fn get_values(self: &Self) -> &Vec<Vec2d> {
         &self.values
     }
 
-    fn sort(self: &mut Self) {
-        self.values.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
+    pub fn pads(&self) -> &[Pad] {
+        &self.pads
     }
 
-    fn genLandingPointsIdx(numLandings: usize, select: Uniform<usize>) -> Vec<usize> {
-        let mut rng = rand::thread_rng();
-        rng.sample_iter(select).take(numLandings).collect()
+    // The piece of ground below x, None outside of the map
+    pub fn segment_at(&self, x: f32) -> Option<(Vec2d, Vec2d)> {
+        let first = self.values.first()?;
+        if x < first.x || x > self.values.last()?.x {
+            return None;
+        }
+        // the first point right of x ends the segment, x on the last point
+        // belongs to the last segment
+        let end = self.values.partition_point(|v| v.x <= x).clamp(1, self.values.len() - 1);
+        Some((self.values[end - 1], self.values[end]))
     }
-}
 
-fn randomY(minValue: f32, maxValue: f32) -> f32 {
-    loop {
-        let mut rng = rand::thread_rng();
-        let distY = Uniform::new_inclusive(minValue, maxValue);
-        let num = rng.sample(distY);
-        if num >= 0.0 {
-            return num;
-        } else {
-            println!("randomY got illegal value below 0");
-        }
+    pub fn height_at(&self, x: f32) -> Option<f32> {
+        let (a, b) = self.segment_at(x)?;
+        Some(a.y + (b.y - a.y) * (x - a.x) / (b.x - a.x))
+    }
+
+    // dy/dx of the ground, positive where it goes down to the right
+    pub fn slope_at(&self, x: f32) -> Option<f32> {
+        let (a, b) = self.segment_at(x)?;
+        Some((b.y - a.y) / (b.x - a.x))
+    }
+
+    // points away from the ground, up on flat ground
+    pub fn normal_at(&self, x: f32) -> Option<Vec2d> {
+        let (a, b) = self.segment_at(x)?;
+        Some(Vec2d::new(b.y - a.y, a.x - b.x).normalized())
+    }
+
+    pub fn nearest_pad(&self, x: f32) -> Option<&Pad> {
+        let distance = |p: &Pad| ((x - p.centre()).abs() - p.width() / 2.0).max(0.0);
+        // pads are sorted, only the ones left and right of x can be nearest
+        let right = self.pads.partition_point(|p| p.to < x);
+        self.pads[right.saturating_sub(1)..self.pads.len().min(right + 1)]
+            .iter()
+            .min_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap())
     }
-}
 
-fn split(a: Vec2d, b: Vec2d, list: &mut Vec<Vec2d>, xMinDist: f32, yMaxDelta: f32) {
-    assert!(a.x < b.x);
-    let deltaX = (b.x - a.x) / 2.0;
-    let center = (a + b) / 2.0;
-    let newY = randomY(center.y - (yMaxDelta / 2.0), center.y + (yMaxDelta / 2.0));
-    let newPoint = Vec2d::new(a.x + deltaX, newY);
-    if deltaX > xMinDist {
-        split(
-            a,
-            newPoint.clone(),
-            list,
-            xMinDist,
-            yMaxDelta / Y_DELTA_DIVIDOR,
-        );
-        split(
-            newPoint.clone(),
-            b,
-            list,
-            xMinDist,
-            yMaxDelta / Y_DELTA_DIVIDOR,
-        );
-    }
-    list.push(newPoint);
+    fn sort(self: &mut Self) {
+        self.values.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::map::*;
+    use crate::alt::map::*;
+    use crate::alt::terrain::TerrainConfig;
     const X_SIZE: f32 = 800.0;
     const Y_SIZE: f32 = 400.0;
 
@@ -140,18 +183,8 @@ mod tests {
     fn test_list_gen_2() {
         let li = PointList::new(300.0, 20.0);
         println!("Point list: {:?}", li);
-        assert_eq!(li.values.first().unwrap().x == 0.0, true);
-        assert_eq!(li.values.last().unwrap().x == 300.0, true);
-    }
-
-    #[test]
-    fn test_split() {
-        let mut list = Vec::from([
-            Vec2d::new(0.0, randomY(0.0, 100.0)),
-            Vec2d::new(100.0, randomY(0.0, 100.0)),
-        ]);
-        split(list[0], list[1], &mut list, 5.0, 20.0);
-        println!("Point list: {:?}", list);
+        assert!(li.values.first().unwrap().x == 0.0);
+        assert!(li.values.last().unwrap().x == 300.0);
     }
 
     #[test]
@@ -159,15 +192,82 @@ mod tests {
         let list = PointList::new(X_SIZE, Y_SIZE);
         println!("Point list (sorted): {:?}", list);
         for idx in 1..list.values.len() {
-            assert_eq!(list.values[idx - 1].x < list.values[idx].x, true);
+            assert!(list.values[idx - 1].x < list.values[idx].x);
+        }
+    }
+
+    #[test]
+    fn test_pads_are_flat_and_apart() {
+        for _ in 0..20 {
+            let mut list = PointList::new(X_SIZE, Y_SIZE);
+            list.set_world_height(600.0);
+            let config = TerrainConfig::classic();
+            assert_eq!(list.pads().len(), config.pads);
+            for pad in list.pads().iter() {
+                assert!(pad.width() > 0.0);
+                assert!(PAD_KINDS
+                    .iter()
+                    .any(|(w, m)| (w * config.pad_width - pad.width()).abs() < 0.01 && *m == pad.multiplier));
+                for x in [pad.from, pad.to] {
+                    assert!(list.values.iter().any(|v| v.x == x && v.y == pad.y));
+                }
+            }
+            for pair in list.pads().windows(2) {
+                assert!(pair[0].to < pair[1].from);
+            }
         }
     }
 
+    #[test]
+    fn test_terrain_queries() {
+        let list = PointList {
+            world_height: None,
+            values: vec![
+                Vec2d::new(0.0, 100.0),
+                Vec2d::new(10.0, 100.0),
+                Vec2d::new(20.0, 120.0),
+                Vec2d::new(40.0, 80.0),
+                Vec2d::new(60.0, 80.0),
+            ],
+            pads: vec![
+                Pad { from: 0.0, to: 10.0, y: 100.0, multiplier: 1 },
+                Pad { from: 40.0, to: 60.0, y: 80.0, multiplier: 2 },
+            ],
+        };
+        assert_eq!(list.height_at(5.0), Some(100.0));
+        assert_eq!(list.height_at(15.0), Some(110.0));
+        assert_eq!(list.height_at(30.0), Some(100.0));
+        assert_eq!(list.height_at(60.0), Some(80.0));
+        assert_eq!(list.height_at(-1.0), None);
+        assert_eq!(list.height_at(61.0), None);
+
+        let (a, b) = list.segment_at(20.0).unwrap();
+        assert_eq!((a.x, b.x), (20.0, 40.0));
+        assert_eq!(list.slope_at(15.0), Some(2.0));
+        assert_eq!(list.slope_at(30.0), Some(-2.0));
+        let flat = list.normal_at(50.0).unwrap();
+        assert_eq!((flat.x, flat.y), (0.0, -1.0));
+        let uphill = list.normal_at(30.0).unwrap();
+        assert!(uphill.x < 0.0 && uphill.y < 0.0);
+
+        assert_eq!(list.nearest_pad(5.0).unwrap().multiplier, 1);
+        assert_eq!(list.nearest_pad(20.0).unwrap().multiplier, 1);
+        assert_eq!(list.nearest_pad(30.0).unwrap().multiplier, 2);
+        assert_eq!(list.nearest_pad(100.0).unwrap().multiplier, 2);
+
+        let pad = list.pads()[1];
+        assert!(pad.holds(50.0, 10.0, 0.0));
+        assert!(pad.holds(54.0, 10.0, 0.0));
+        assert!(!pad.holds(56.0, 10.0, 0.0));
+        assert!(pad.holds(56.0, 10.0, 1.0));
+        assert!(!pad.holds(50.0, 30.0, 0.0));
+    }
+
     #[test]
     fn test_point_list_y_is_non_negatie() {
         let list = PointList::new(X_SIZE, Y_SIZE);
         for val in list.values.iter() {
-            assert_eq!(val.y > 0.0, true);
+            assert!(val.y > 0.0);
         }
     }
 }
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Point;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+
+use crate::audio::{Cue, Sfx};
+use crate::scene::{Input, Scene, Transition};
+use crate::draw;
+use crate::{window_center, window_height, window_width};
+
+pub mod camera;
+pub mod collision;
+pub mod graphics;
+pub mod hud;
+pub mod landing;
+pub mod map;
+pub mod simulation;
+pub mod terrain;
+
+// The lunar lander, a game of its own next to the battleships
+
+pub struct Lander {
+    world: simulation::World,
+    last_tick: Instant,
+    cues: Vec<Cue>,
+    deaths: u32,
+    // the terrain file could not be read, the classic map is flown instead
+    error: Option<String>,
+}
+
+impl Lander {
+    pub fn new() -> Self {
+        let (world, error) = new_world();
+        Lander {
+            world,
+            last_tick: Instant::now(),
+            cues: Vec::new(),
+            deaths: 0,
+            error,
+        }
+    }
+
+    // the next flight waits until the wreck has come down
+    fn is_over(&self) -> bool {
+        *self.world.state() != simulation::State::Running && self.world.is_settled()
+    }
+
+    // A fresh lander over a new surface, crashes are counted
+    fn fly_again(&mut self) {
+        if self.world.landing().is_some_and(|l| l.verdict == landing::Verdict::Crash) {
+            self.deaths += 1;
+        }
+        (self.world, self.error) = new_world();
+        self.world.set_deaths(self.deaths);
+        self.last_tick = Instant::now();
+    }
+}
+
+// The terrain settings are read for every flight, they may have been tuned
+fn new_world() -> (simulation::World, Option<String>) {
+    let (config, error) = match terrain::TerrainConfig::load(terrain::TERRAIN_PATH) {
+        Ok(config) => (config, None),
+        Err(e) => (terrain::TerrainConfig::classic(), Some(e)),
+    };
+    (simulation::World::with_terrain(window_width, window_height, &config), error)
+}
+
+impl Scene for Lander {
+    fn update(&mut self) -> Option<Transition> {
+        let elapsed_ms = self.last_tick.elapsed().as_secs_f32() * 1000.0;
+        self.last_tick = Instant::now();
+        self.world.tick(elapsed_ms);
+        None
+    }
+
+    fn render(&mut self, canvas: &mut Canvas<Window>, _resources: &mut HashMap<String, Texture>) {
+        self.world.render(canvas);
+        if self.is_over() {
+            let help = "Return to fly again, Backspace to leave";
+            let at = Point::new(window_center.x as i32, window_center.y as i32 + 60);
+            let _ = draw::draw_text_centered(canvas, help, 16, at, Color::RGB(255, 255, 255));
+        }
+        if let Some(error) = self.error.as_ref() {
+            let at = Point::new(window_center.x as i32, window_height as i32 - 12);
+            let _ = draw::draw_text_centered(canvas, error, 12, at, Color::RGB(255, 0, 0));
+        }
+    }
+
+    fn handle_input(&mut self, input: Input) -> Option<Transition> {
+        match input {
+            Input::Key(Keycode::Backspace) => return Some(Transition::Pop),
+            Input::Key(Keycode::Return) if self.is_over() => self.fly_again(),
+            Input::Key(Keycode::Up | Keycode::Space) => self.world.thrust_toggle(true),
+            Input::KeyUp(Keycode::Up | Keycode::Space) => self.world.thrust_toggle(false),
+            Input::Key(Keycode::Left) => self.world.rotation_left_toggle(true),
+            Input::KeyUp(Keycode::Left) => self.world.rotation_left_toggle(false),
+            Input::Key(Keycode::Right) => self.world.rotation_right_toggle(true),
+            Input::KeyUp(Keycode::Right) => self.world.rotation_right_toggle(false),
+            _ => {}
+        }
+        None
+    }
+
+    fn on_enter(&mut self) {
+        // no time passes while paused
+        self.last_tick = Instant::now();
+    }
+
+    fn on_exit(&mut self) {
+        // the key may be released somewhere else
+        self.world.thrust_toggle(false);
+        self.cues.push(Cue::StopLoop(Sfx::Thrust));
+    }
+
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    fn take_cues(&mut self) -> Vec<Cue> {
+        let mut cues = std::mem::take(&mut self.cues);
+        cues.extend(self.world.take_cues());
+        cues
+    }
+}
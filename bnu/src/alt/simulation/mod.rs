@@ -1,34 +1,130 @@
-use std::{f32::consts::PI, num};
+use std::f32::consts::PI;
 
 use sdl2::pixels::Color;
 use sdl2::rect::Point;
 
 use crate::vecmath::TransformationMatrix;
-use crate::graphics::{self, renderGameOver, renderWonText};
+use super::graphics::{self, renderGameOver, renderWonText};
+use crate::audio::{Cue, Sfx};
+use crate::particles::{Emitter, EmitterConfig, ParticleSystem};
+use super::landing::{self, Landing, Touchdown, Verdict};
+use super::camera::Camera;
+use super::terrain::{self, TerrainConfig};
+use super::{collision::TerrainGrid, hud, map::PointList};
 use crate::{
-    collision, draw, hud,
-    map::PointList,
+    draw,
     vecmath::{self, Vec2d},
+    window_center,
 };
 
+// How a physics step moves an entity
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Integrator {
+    // speed first, then the position with the new speed
+    SemiImplicitEuler,
+    // position from speed and acceleration, then the speed
+    VelocityVerlet,
+}
+
+pub const INTEGRATOR_NAMES: [&str; 2] = ["euler", "verlet"];
+
+impl Integrator {
+    pub fn by_name(name: &str) -> Result<Integrator, String> {
+        match name {
+            "euler" => Ok(Integrator::SemiImplicitEuler),
+            "verlet" => Ok(Integrator::VelocityVerlet),
+            _ => Err(format!("Unknown integrator '{}', known are {}", name, INTEGRATOR_NAMES.join(", "))),
+        }
+    }
+}
+
+// Every physics step simulates this long, whatever the frame rate
+pub const STEP_MS: f32 = 1000.0 / 60.0;
+// a longer frame (the window was dragged, a debugger stopped) is cut short
+const MAX_STEPS_PER_FRAME: u32 = 16;
+
+// World coordinates don't depend on the window: x goes right from the
+// left end of the map, y down from the top of a world this high
+pub const WORLD_HEIGHT: f32 = 600.0;
+// the ground never rises higher than this above the bottom
+const MAX_GROUND: f32 = WORLD_HEIGHT / 3.0;
+const START_HEIGHT: f32 = 300.0;
+
+// how far above or below the ground a foot still counts as standing on it
+const LEG_REACH: f32 = 4.0;
+// the lander is about 30 wide, it stops this far from either end of the
+// map so it never hangs over where there's no ground
+const EDGE_MARGIN: f32 = 20.0;
+
 struct Physics {
     gravity: f32, // force applied per second!
     gravity_direction: Vec2d,
+    step_ms: f32,
+    integrator: Integrator,
+    // frame time not used up by a whole step yet, carried into the next frame
+    remainder_ms: f32,
 }
 
+// A rigid body: forces and torques set by the game are turned into
+// movement and rotation by the physics steps
 pub struct Entity {
     position: Vec2d,
-    direction: Vec2d,    // non normalized, has speed integrated!
-    acceleration: Vec2d, // non normalized, has force integrated!
+    direction: Vec2d, // non normalized, has speed integrated!
+    force: Vec2d,     // besides gravity, e.g. the main engine
+    mass: f32,
+    // radians, and radians per second
+    angle: f32,
+    angular_velocity: f32,
+    torque: f32,
+    moment_of_inertia: f32,
     update: bool,
 }
 
+// Lander mass without fuel, and what a second of fuel weighs
+const DRY_MASS: f32 = 2.0;
+const FUEL_MASS: f32 = 0.05;
+const ENGINE_FORCE: f32 = 15.0;
+// the lander turns like a ring of its mass this far from the centre
+const RADIUS_OF_GYRATION: f32 = 10.0;
+const RCS_TORQUE: f32 = 600.0;
+// fuel seconds the attitude thrusters burn per second
+const RCS_BURN: f32 = 0.1;
+
 pub struct Lander {
     entity_id: usize,
     fuel: f32,     // in seconds!
     facing: Vec2d, // This is the direction the engine is facing, i.e. any thrust is opposite to this!
     drive_enabled: bool,
-    rotation: f32,
+    // attitude thrusters: -1 turns left, 1 right, 0 off
+    rcs: f32,
+}
+
+impl Lander {
+    fn mass(&self) -> f32 {
+        DRY_MASS + self.fuel * FUEL_MASS
+    }
+
+    // Burns `dt` seconds worth of fuel, the engines go quiet when it runs out
+    fn burn(&mut self, dt: f32) {
+        let mut burn = if self.drive_enabled { 1.0 } else { 0.0 };
+        if self.rcs != 0.0 {
+            burn += RCS_BURN;
+        }
+        self.fuel = (self.fuel - burn * dt).max(0.0);
+        if self.fuel <= 0.0 {
+            self.drive_enabled = false;
+            self.rcs = 0.0;
+        }
+    }
+
+    // Engine force, thruster torque and the mass that is left go to the body
+    fn push(&self, entity: &mut Entity) {
+        let mass = self.mass();
+        let force = if self.drive_enabled { self.facing * -ENGINE_FORCE } else { Vec2d::default() };
+        entity.set_force(force);
+        entity.set_torque(self.rcs * RCS_TORQUE);
+        entity.set_mass(mass, mass * RADIUS_OF_GYRATION * RADIUS_OF_GYRATION);
+    }
 }
 
 #[derive(PartialEq)]
@@ -43,8 +139,17 @@ pub struct World {
     entities: Vec<Entity>,
     lander: Option<Lander>,
     map: PointList,
+    // broad phase over the map, for collisions
+    ground: TerrainGrid,
     hud: hud::Hud,
     game_state: State,
+    exhaust: Emitter,
+    particles: ParticleSystem,
+    cues: Vec<Cue>,
+    // crashes in earlier flights, shown on the hud
+    deaths: u32,
+    landing: Option<Landing>,
+    camera: Camera,
 }
 
 impl Entity {
@@ -52,13 +157,27 @@ impl Entity {
         Entity {
             position: Vec2d::default(),
             direction: Vec2d::default(),
-            acceleration: Vec2d::default(),
+            force: Vec2d::default(),
+            mass: 1.0,
+            angle: 0.0,
+            angular_velocity: 0.0,
+            torque: 0.0,
+            moment_of_inertia: 1.0,
             update: true,
         }
     }
 
-    pub fn set_acceleration(&mut self, accel: Vec2d) {
-        self.acceleration = accel;
+    pub fn set_force(&mut self, force: Vec2d) {
+        self.force = force;
+    }
+
+    pub fn set_torque(&mut self, torque: f32) {
+        self.torque = torque;
+    }
+
+    pub fn set_mass(&mut self, mass: f32, moment_of_inertia: f32) {
+        self.mass = mass;
+        self.moment_of_inertia = moment_of_inertia;
     }
 
     pub fn set_position(&mut self, position: Vec2d) {
@@ -71,70 +190,104 @@ impl Entity {
 }
 
 impl Physics {
-    pub fn default() -> Self {
+    pub fn new(step_ms: f32, integrator: Integrator) -> Self {
         Physics {
             gravity: 9.81 / 24.0,
             gravity_direction: Vec2d::new(0.0, 1.0),
+            step_ms,
+            integrator,
+            remainder_ms: 0.0,
         }
     }
 
-    pub fn tick(&self, time_in_ms: f32, tick_resolution_in_ms: f32, entities: &mut Vec<Entity>) {
-        let mut num_ticks = (time_in_ms / tick_resolution_in_ms) as u32;
-        if num_ticks == 0 {
-            num_ticks = 1;
+    // Runs as many whole steps as fit into the frame, `after_step` gets the
+    // entities and the length of a step in seconds after each of them
+    pub fn tick(&mut self, time_in_ms: f32, entities: &mut [Entity], mut after_step: impl FnMut(&mut [Entity], f32)) {
+        self.remainder_ms += time_in_ms;
+        let mut steps = 0;
+        while self.remainder_ms >= self.step_ms {
+            if steps == MAX_STEPS_PER_FRAME {
+                self.remainder_ms = 0.0;
+                break;
+            }
+            self.remainder_ms -= self.step_ms;
+            steps += 1;
+            for e in entities.iter_mut().filter(|e| e.update) {
+                self.step(e);
+            }
+            after_step(entities, self.step_ms / 1000.0);
         }
+    }
 
-        // Apply gravity and acceleration to each entity,
-        // Apply resulting speed to position of entity
-        for _ in 0..num_ticks {
-            for e in entities.iter_mut() {
-                let sim_time_in_seconds = time_in_ms / 1000.0;
-
-                // update direction by applying gravity:
-                let gravity_fragment =
-                    self.gravity_direction.clone() * (self.gravity * sim_time_in_seconds);
-
-                if e.update {
-                    e.direction = e.direction + gravity_fragment;
-                    // update direction by appliying acceleration:
-                    let accel_fragment = e.acceleration.clone() * (sim_time_in_seconds);
-                    e.direction = e.direction + accel_fragment;
-                    e.position = e.position + e.direction.clone() * (sim_time_in_seconds);
-                }
-
-                // TBD: Check if something like a terminal velocity would be a good idea
-                // -> This would probably make the game a bit easier and also make the physics
-                // simulation more robust
+    fn step(&self, e: &mut Entity) {
+        let dt = self.step_ms / 1000.0;
+        let accel = self.gravity_direction * self.gravity + e.force / e.mass;
+        let angular_accel = e.torque / e.moment_of_inertia;
+        match self.integrator {
+            Integrator::SemiImplicitEuler => {
+                e.direction = e.direction + accel * dt;
+                e.position = e.position + e.direction * dt;
+                e.angular_velocity += angular_accel * dt;
+                e.angle += e.angular_velocity * dt;
+            }
+            Integrator::VelocityVerlet => {
+                e.position = e.position + e.direction * dt + accel * (0.5 * dt * dt);
+                e.angle += e.angular_velocity * dt + angular_accel * (0.5 * dt * dt);
+                // forces and torques don't depend on the pose, so the
+                // accelerations at the end of the step are the same
+                e.direction = e.direction + accel * dt;
+                e.angular_velocity += angular_accel * dt;
             }
         }
     }
 }
 
 impl World {
+    // The game reads its map from a file, tests fly the classic one
+    #[cfg(test)]
     pub fn new(window_width: u32, window_height: u32) -> Self {
+        World::with_terrain(window_width, window_height, &TerrainConfig::classic())
+    }
+
+    // The window size is only used to show the world
+    pub fn with_terrain(window_width: u32, window_height: u32, terrain: &TerrainConfig) -> Self {
+        let start = Vec2d::new(terrain.width / 2.0, START_HEIGHT);
+        let mut map = terrain::generate(terrain, MAX_GROUND);
+        map.set_world_height(WORLD_HEIGHT);
         let mut w = World {
-            p: Physics::default(),
+            p: Physics::new(STEP_MS, terrain.integrator),
             entities: Vec::new(),
             lander: None,
-            map: PointList::new(window_width as f32, (window_height as f32) / 3.0),
+            ground: TerrainGrid::new(map.get_values()),
+            map,
             hud: hud::Hud::new(),
             game_state: State::Running,
+            exhaust: Emitter::new(EmitterConfig::exhaust(), Vec2d::default()),
+            particles: ParticleSystem::new(),
+            cues: Vec::new(),
+            deaths: 0,
+            landing: None,
+            camera: Camera::new(window_width, window_height, start),
         };
-        w.map.set_window_height(window_height as f32);
+        w.exhaust.stop();
         let landerId = w.create_entity();
+        w.get_entity(landerId).set_position(start);
+        // upright, the engine faces down
+        w.get_entity(landerId).angle = PI / 2.0;
         w.lander = Some(Lander {
             entity_id: landerId,
             fuel: 20.0,
             facing: Vec2d::new(0.0, 1.0),
             drive_enabled: false,
-            rotation: 0.0,
+            rcs: 0.0,
         });
+        w.apply_controls();
         w
     }
 
     pub fn create_entity(&mut self) -> usize {
         let mut e = Entity::default();
-        e.set_position(Vec2d::new(200.0, 300.0));
+        e.set_position(Vec2d::new(200.0, START_HEIGHT));
         self.entities.push(e);
         return self.entities.len() - 1;
     }
@@ -143,76 +296,141 @@ impl World {
         return &mut self.entities[id];
     }
 
-    pub fn tick(&mut self, time_in_ms: f32, tick_resolution_in_ms: f32) {
-        // Do physics (i.e. Gravity & Acceleration) tick
-        self.p
-            .tick(time_in_ms, tick_resolution_in_ms, &mut self.entities);
+    pub fn tick(&mut self, time_in_ms: f32) {
+        // where the lander was, to sweep it from there to where it ends up
+        let id = self.lander.as_ref().unwrap().entity_id;
+        let before = (self.entities[id].position, self.entities[id].angle);
 
-        // Consume fuel
-        let mut lander = self.lander.as_mut().unwrap();
-        let mut disableThrust = false; 
-        if lander.drive_enabled {
-            lander.fuel -= time_in_ms / 1000.0;
-            if lander.fuel <= 0.0
-            {
-                lander.drive_enabled = false;
-                disableThrust = true;
-            }
-        }
-
-        let mut next_angle = lander.facing.angle() + lander.rotation * (time_in_ms / 1000.0);
-        lander.facing = Vec2d::from_angle(next_angle);
-
-        self.thrust_toggle(disableThrust);
+        // Do physics (i.e. Gravity & Acceleration) tick, fuel burns with the
+        // steps that push the lander, not with the frames
+        let lander = self.lander.as_mut().unwrap();
+        self.p.tick(time_in_ms, &mut self.entities, |entities, dt| {
+            let entity = &mut entities[lander.entity_id];
+            lander.burn(dt);
+            lander.facing = Vec2d::from_angle(entity.angle);
+            lander.push(entity);
+        });
+        self.keep_on_map();
+        self.update_effects(time_in_ms / 1000.0);
 
 
         // Do collision detection, fail if we collided with the environment
         // or a landingpad (in pad case: if velocity was too high)
-        self.do_collision_detection();
+        self.do_collision_detection(before);
+        self.follow_lander(time_in_ms / 1000.0);
     }
 
     fn get_lander_transform(&self, lander_pos: Vec2d, lander_rot: Vec2d) -> TransformationMatrix
     {
         let scale = vecmath::TransformationMatrix::scale(graphics::LanderScale.x, graphics::LanderScale.y);
         let translate = vecmath::TransformationMatrix::translation_v(lander_pos);
-        let rotation = vecmath::TransformationMatrix::rotate(lander_rot.angle() + PI / 2.0);
+        let rotation = vecmath::TransformationMatrix::rotate(lander_rot.y.atan2(lander_rot.x) + PI / 2.0);
         let transform = translate * rotation * scale;
         transform
     }
 
+    // The map ends left and right, the lander bumps into the edge and loses
+    // its sideways speed
+    fn keep_on_map(&mut self) {
+        let id = self.lander.as_ref().unwrap().entity_id;
+        let width = self.map.get_values().last().map_or(0.0, |v| v.x);
+        let entity = self.get_entity(id);
+        let x = entity.position.x.clamp(EDGE_MARGIN, (width - EDGE_MARGIN).max(EDGE_MARGIN));
+        if x != entity.position.x {
+            entity.position.x = x;
+            entity.direction.x = 0.0;
+        }
+    }
+
+    fn follow_lander(&mut self, dt: f32) {
+        let id = self.lander.as_ref().unwrap().entity_id;
+        let position = self.get_entity(id).position;
+        let altitude = self.map.height_at(position.x).map(|ground| ground - position.y);
+        let size = Vec2d::new(self.map.get_values().last().map_or(0.0, |v| v.x), WORLD_HEIGHT);
+        self.camera.follow(position, altitude, dt, size);
+    }
+
+    // Engine exhaust and its sound follow the drive state
+    fn update_effects(&mut self, dt: f32) {
+        let (id, facing, drive_enabled) = {
+            let lander = self.lander.as_ref().unwrap();
+            (lander.entity_id, lander.facing, lander.drive_enabled)
+        };
+        let position = self.get_entity(id).position;
+
+        // The exhaust leaves the nozzle in the direction the engine is facing
+        let transform = self.get_lander_transform(position, facing);
+        self.exhaust.position = transform.transform(&Vec2d::new(0.0, -3.0));
+        self.exhaust.set_direction(facing.y.atan2(facing.x));
+        if drive_enabled && !self.exhaust.is_emitting() {
+            self.exhaust.restart();
+            self.cues.push(Cue::StartLoop(Sfx::Thrust));
+        } else if !drive_enabled && self.exhaust.is_emitting() {
+            self.exhaust.stop();
+            self.cues.push(Cue::StopLoop(Sfx::Thrust));
+        }
+        self.exhaust.update(dt);
+        self.particles.update(dt);
+    }
+
+    pub fn state(&self) -> &State {
+        &self.game_state
+    }
+
+    // Nothing flies around anymore, e.g. the wreck after a crash
+    pub fn is_settled(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    // How the flight ended, None while it goes on
+    pub fn landing(&self) -> Option<&Landing> {
+        self.landing.as_ref()
+    }
+
+    pub fn set_deaths(&mut self, deaths: u32) {
+        self.deaths = deaths;
+    }
+
+    pub fn take_cues(&mut self) -> Vec<Cue> {
+        std::mem::take(&mut self.cues)
+    }
+
     pub(crate) fn render(&mut self, canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) {
         match self.game_state {
             State::Won => renderWonText(canvas),
             State::Lost => renderGameOver(canvas),
             State::Running => (),
         }
+        if let Some(landing) = self.landing.as_ref() {
+            let text = format!("{}, {} points", landing.verdict.label(), landing.score);
+            let at = Point::new(window_center.x as i32, window_center.y as i32 + 40);
+            draw::draw_text_centered(canvas, &text, 20, at, Color::RGB(255, 255, 255)).unwrap();
+        }
+        let view = self.camera.transform();
         draw::draw_lines(
             canvas,
-            &self.map.get_values(),
+            &view.transform_many(self.map.get_values()),
             Color::RGB(255, 255, 255),
             false,
         )
         .unwrap();
+        self.render_pads(canvas, &view);
         self.renderHud(canvas);
 
         //draw the lander:
         let id;
-        let thrust_enabled;
         let lander_rot;
-        let fuel;
         {
             // This scope makes sure, that we only keep the lander
             // borrowed as long as necessary
             let lander = self.lander.as_ref().unwrap();
             id = lander.entity_id;
             lander_rot = lander.facing;
-            thrust_enabled = lander.drive_enabled;
-            fuel = lander.fuel;
         }
         let entity = self.get_entity(id);
         let lander_pos = entity.position;
 
-        let transform = self.get_lander_transform(lander_pos, lander_rot);
+        let transform = view * self.get_lander_transform(lander_pos, lander_rot);
         let items = [
             &graphics::LanderTop,
             &graphics::LanderMiddle,
@@ -225,93 +443,133 @@ impl World {
             draw::draw_lines(canvas, &geometry, Color::RGB(255, 255, 255), true).unwrap();
         }
 
-        if thrust_enabled {
-            let geometry;
-            if ((fuel * 10.0) as i32) % 2 == 0 {
-                geometry = transform.transform_many(&graphics::FlameA.to_vec());
-            } else {
-                geometry = transform.transform_many(&graphics::FlameB.to_vec());
-            }
-            draw::draw_lines(canvas, &geometry, Color::RGB(255, 255, 255), true).unwrap();
-        }
+        self.exhaust.render_through(canvas, None, &view);
+        self.particles.render_through(canvas, None, &view);
     }
 
     pub(crate) fn thrust_toggle(&mut self, enable: bool) {
         if self.game_state != State::Running {
             return;
         }
-        let id;
-        let thrust_dir;
-        let hasFuel;
-        {
-            // This scope makes sure, that we only keep the lander
-            // borrowed as long as necessary
-            let lander = self.lander.as_mut().unwrap();
-            thrust_dir = lander.facing;       
-            id = lander.entity_id;
-            hasFuel = lander.fuel > 0.0;
-            lander.drive_enabled = hasFuel && enable;
-        }
-        let entity = self.get_entity(id);
-        if enable && hasFuel{
-            entity.set_acceleration(thrust_dir * -5.0);
-        } else {
-            entity.set_acceleration(Vec2d::default());
-        }
+        let lander = self.lander.as_mut().unwrap();
+        lander.drive_enabled = enable && lander.fuel > 0.0;
+        self.apply_controls();
     }
 
     pub(crate) fn rotation_left_toggle(&mut self, enable: bool) {
-        if self.game_state != State::Running {
-            return;
-        }
-        let lander = self.lander.as_mut().unwrap();
-        if enable == true {
-            lander.rotation = -1.0;
-        } else {
-            lander.rotation = 0.0;
-        }
+        self.fire_rcs(if enable { -1.0 } else { 0.0 });
     }
 
     pub(crate) fn rotation_right_toggle(&mut self, enable: bool) {
+        self.fire_rcs(if enable { 1.0 } else { 0.0 });
+    }
+
+    // The lander keeps turning until the other thruster stops it
+    fn fire_rcs(&mut self, rcs: f32) {
         if self.game_state != State::Running {
             return;
         }
         let lander = self.lander.as_mut().unwrap();
-        if enable == true {
-            lander.rotation = 1.0;
-        } else {
-            lander.rotation = 0.0;
-        }
+        lander.rcs = if lander.fuel > 0.0 { rcs } else { 0.0 };
+        self.apply_controls();
+    }
+
+    fn apply_controls(&mut self) {
+        let lander = self.lander.as_ref().unwrap();
+        lander.push(&mut self.entities[lander.entity_id]);
     }
 
-    fn do_collision_detection(&mut self)
+    // `before` is the position and angle of the lander before this tick. The
+    // lander is swept from there, so it stops where it first hit the ground
+    // however far it went.
+    fn do_collision_detection(&mut self, before: (Vec2d, f32))
     {
+        if self.game_state != State::Running {
+            return;
+        }
         if let Some(lander) = self.lander.as_ref() {
             let id = lander.entity_id;
-            let position;
-            let direction;
-            {
+            let (from, from_angle) = before;
+            let (to, to_angle, direction) = {
                 let entity = self.get_entity(id);
-                position = entity.position;
-                direction = entity.direction;
-            }
-
-            let transform = self.get_lander_transform(position, direction);
-            let bbox = transform.transform_many(&graphics::BBox.to_vec());
-
-            if let Some(collision) = collision::detect_collision(
-                bbox, self.map.get_values())
+                (entity.position, entity.angle, entity.direction)
+            };
+
+            let pose_at = |t: f32| (from + (to - from) * t, from_angle + (to_angle - from_angle) * t);
+            let body_at = |t: f32| {
+                let (position, angle) = pose_at(t);
+                self.get_lander_transform(position, Vec2d::from_angle(angle))
+                    .transform_many(&graphics::BBox.to_vec())
+            };
+            let reach = graphics::BBox.iter().map(|p| p.len()).fold(0.0, f32::max) * graphics::LanderScale.x;
+            let travel = (to - from).len() + (to_angle - from_angle).abs() * reach;
+
+            if let Some((t, contact)) = self.ground.time_of_impact(body_at, travel)
             {
+                    let (position, angle) = pose_at(t);
+                    let facing = Vec2d::from_angle(angle);
+                    let transform = self.get_lander_transform(position, facing);
+                    self.lander.as_mut().unwrap().facing = facing;
                     let entity = self.get_entity(id);
+                    entity.set_position(position);
+                    entity.angle = angle;
                     entity.set_update(false);
-                    let angle = (collision[0].0 - collision[0].1).angle();
-                    if angle > PI / 16.0 && angle < PI - (PI /16.0){
-                        self.game_state = State::Lost
-                    }else {
-                        //TODO: check landing speed
-                        self.game_state = State::Won
+                    let feet = [&graphics::LeftLeg, &graphics::RightLeg].map(|leg| transform.transform(&leg[1]).x);
+                    let (middle, stance) = ((feet[0] + feet[1]) / 2.0, (feet[1] - feet[0]).abs());
+                    let touchdown = Touchdown {
+                        velocity: direction,
+                        // upright, the engine faces straight down
+                        tilt: (facing.y.atan2(facing.x) - PI / 2.0 + PI).rem_euclid(2.0 * PI) - PI,
+                        legs: [
+                            self.leg_touches(&transform, &graphics::LeftLeg),
+                            self.leg_touches(&transform, &graphics::RightLeg),
+                        ],
+                        // both feet on it, not just the middle
+                        pad: self.map.pads().iter().find(|p| p.holds(middle, stance, LEG_REACH)).map(|p| p.multiplier),
+                    };
+                    let landing = landing::evaluate(&touchdown);
+                    // the controls are dead from here on, a held key would
+                    // otherwise keep the engine burning
+                    let lander = self.lander.as_mut().unwrap();
+                    lander.drive_enabled = false;
+                    lander.rcs = 0.0;
+                    self.apply_controls();
+                    self.exhaust.stop();
+                    self.cues.push(Cue::StopLoop(Sfx::Thrust));
+                    if landing.verdict.is_win() {
+                        self.game_state = State::Won;
+                    } else {
+                        self.game_state = State::Lost;
                     }
+                    if landing.verdict == Verdict::Crash {
+                        self.particles.spawn(EmitterConfig::debris(), contact.points[0]);
+                        self.cues.push(Cue::Play(Sfx::Crash));
+                    }
+                    self.landing = Some(landing);
+            }
+        }
+    }
+
+    // The foot of the leg is on the ground, or within reach of it
+    fn leg_touches(&self, transform: &TransformationMatrix, leg: &[Vec2d; 2]) -> bool {
+        let foot = transform.transform(&leg[1]);
+        let reach = Vec2d::new(0.0, LEG_REACH);
+        self.ground.segment_hits(foot - reach, foot + reach)
+    }
+
+    // Pads stand out from the ground, with their multiplier below
+    fn render_pads(&self, canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, view: &TransformationMatrix) {
+        let color = Color::RGB(0, 255, 0);
+        for pad in self.map.pads().iter() {
+            let line = view.transform_many(&vec![Vec2d::new(pad.from, pad.y), Vec2d::new(pad.to, pad.y)]);
+            for dy in [0.0, 1.0] {
+                let line = line.iter().map(|p| *p + Vec2d::new(0.0, dy)).collect();
+                draw::draw_lines(canvas, &line, color, false).unwrap();
             }
+            let label = format!("x{}", pad.multiplier);
+            let middle = self.camera.to_screen(Vec2d::new(pad.centre(), pad.y));
+            let at = Point::new(middle.x as i32, middle.y as i32 + 12);
+            draw::draw_text_centered(canvas, &label, 12, at, color).unwrap();
         }
     }
 
@@ -322,45 +580,292 @@ impl World {
             let entity = self.get_entity(id);
             let position = entity.position;
             let direction = entity.direction;
-            self.hud.update(position, direction, fuel, 0);
+            let facing = Vec2d::from_angle(entity.angle);
+            self.hud.update(position, direction, fuel, self.deaths);
+            let altitude = self.map.height_at(position.x).map(|ground| ground - position.y);
+            // the engine points into the ground when the lander stands square on it
+            let lean = self
+                .map
+                .normal_at(position.x)
+                .map(|n| (n.x * facing.y - n.y * facing.x).atan2(-(n.x * facing.x + n.y * facing.y)));
+            let pad = self.map.nearest_pad(position.x).map(|p| (p.multiplier, p.offset(position.x)));
+            self.hud.update_ground(altitude, self.map.slope_at(position.x), lean, pad);
         }
         self.hud.render(canvas);
     }
 }
 
+#[cfg(test)]
 mod tests {
-    use crate::{simulation, vecmath::Vec2d};
+    use crate::audio::{Cue, Sfx};
+    use crate::vecmath::Vec2d;
 
-    use super::{Entity, Physics};
+    use super::{graphics, Entity, Integrator, Physics, TerrainConfig, Verdict, World, STEP_MS};
 
     #[test]
     fn can_apply_gravity() {
-        let w = Physics {
+        let mut w = Physics {
             gravity: 1.0,
             gravity_direction: Vec2d::new(0.0, -1.0),
+            ..Physics::new(1000.0, Integrator::SemiImplicitEuler)
         };
 
         let mut e = Entity::default();
 
         let mut v = vec![e];
 
-        w.tick(1000.0, 1000.0, &mut v);
+        w.tick(1000.0, &mut v, |_, _| {});
         assert_eq!(v[0].position.y, -1.0);
     }
 
     #[test]
     fn can_apply_acceleration() {
-        let w = Physics {
+        let mut w = Physics {
             gravity: 1.0,
             gravity_direction: Vec2d::default(),
+            ..Physics::new(1000.0, Integrator::SemiImplicitEuler)
         };
 
         let mut e = Entity::default();
-        e.acceleration = Vec2d::new(1.0, 0.0);
+        e.force = Vec2d::new(1.0, 0.0);
         let mut v = vec![e];
 
-        w.tick(1000.0, 1000.0, &mut v);
+        w.tick(1000.0, &mut v, |_, _| {});
         assert_eq!(v[0].position.x, 1.0);
         assert_eq!(v[0].direction.x, 1.0);
     }
+
+    // Falls for `seconds` in frames of `frame_ms`, 64 steps a second
+    fn fall(integrator: Integrator, seconds: f32, frame_ms: f32) -> Entity {
+        let mut p = Physics {
+            gravity: 10.0,
+            ..Physics::new(1000.0 / 64.0, integrator)
+        };
+        let mut v = vec![Entity::default()];
+        v[0].direction = Vec2d::new(3.0, -20.0);
+        for _ in 0..(seconds * 1000.0 / frame_ms) as usize {
+            p.tick(frame_ms, &mut v, |_, _| {});
+        }
+        v.pop().unwrap()
+    }
+
+    // kinetic plus potential energy, gravity pulls towards +y
+    fn energy(e: &Entity) -> f32 {
+        0.5 * (e.direction.x * e.direction.x + e.direction.y * e.direction.y) - 10.0 * e.position.y
+    }
+
+    #[test]
+    fn steps_ignore_the_frame_rate() {
+        for integrator in [Integrator::SemiImplicitEuler, Integrator::VelocityVerlet] {
+            // 20, 128 and 30 frames a second, the first leaves a remainder every frame
+            let slow = fall(integrator, 2.0, 50.0);
+            let fast = fall(integrator, 2.0, 7.8125);
+            let odd = fall(integrator, 2.0, 1000.0 / 30.0);
+            assert_eq!((slow.position.x, slow.position.y), (fast.position.x, fast.position.y));
+            assert!((odd.position.y - fast.position.y).abs() < 0.5);
+            // y = v0 t + g t² / 2, and not a multiple of it
+            assert!((fast.position.y - (-40.0 + 20.0)).abs() < 0.5);
+            assert!((fast.position.x - 6.0).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn leftover_time_is_carried_to_the_next_frame() {
+        let mut p = Physics::new(10.0, Integrator::SemiImplicitEuler);
+        let mut v = vec![Entity::default()];
+        p.tick(6.0, &mut v, |_, _| {});
+        assert_eq!(v[0].position.y, 0.0);
+        p.tick(6.0, &mut v, |_, _| {});
+        assert!(v[0].position.y > 0.0);
+        assert!((p.remainder_ms - 2.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn energy_does_not_drift() {
+        let start = energy(&fall(Integrator::VelocityVerlet, 0.0, 10.0));
+        let verlet = energy(&fall(Integrator::VelocityVerlet, 10.0, 10.0));
+        let euler = energy(&fall(Integrator::SemiImplicitEuler, 10.0, 10.0));
+        // exact for a constant force, the error of semi-implicit Euler stays
+        // bounded by g² t dt / 2 instead of growing with the speed
+        assert!((verlet - start).abs() < 0.5);
+        assert!((euler - start).abs() < 10.0 * 10.0 * 10.0 / 64.0);
+    }
+
+    #[test]
+    fn torque_spins_and_the_spin_lasts() {
+        let mut p = Physics {
+            gravity_direction: Vec2d::default(),
+            ..Physics::new(1000.0 / 64.0, Integrator::VelocityVerlet)
+        };
+        let mut v = vec![Entity::default()];
+        v[0].set_mass(1.0, 4.0);
+        v[0].set_torque(8.0);
+        for _ in 0..32 {
+            p.tick(31.25, &mut v, |_, _| {});
+        }
+        assert!((v[0].angular_velocity - 2.0).abs() < 0.001);
+        assert!((v[0].angle - 1.0).abs() < 0.001);
+
+        // nothing slows it down
+        v[0].set_torque(0.0);
+        for _ in 0..32 {
+            p.tick(31.25, &mut v, |_, _| {});
+        }
+        assert!((v[0].angular_velocity - 2.0).abs() < 0.001);
+        assert!((v[0].angle - 3.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn heavy_bodies_are_slow_to_push() {
+        let mut p = Physics {
+            gravity_direction: Vec2d::default(),
+            ..Physics::new(1000.0, Integrator::SemiImplicitEuler)
+        };
+        let mut v = vec![Entity::default(), Entity::default()];
+        for (e, mass) in v.iter_mut().zip([1.0, 4.0]) {
+            e.set_mass(mass, 1.0);
+            e.set_force(Vec2d::new(4.0, 0.0));
+        }
+        p.tick(1000.0, &mut v, |_, _| {});
+        assert_eq!((v[0].direction.x, v[1].direction.x), (4.0, 1.0));
+    }
+
+    #[test]
+    fn the_map_file_picks_the_integrator() {
+        let euler = TerrainConfig {
+            integrator: Integrator::SemiImplicitEuler,
+            ..TerrainConfig::classic()
+        };
+        assert_eq!(World::with_terrain(800, 600, &euler).p.integrator, Integrator::SemiImplicitEuler);
+        assert_eq!(World::new(800, 600).p.integrator, Integrator::VelocityVerlet);
+    }
+
+    #[test]
+    fn burning_fuel_makes_the_lander_lighter() {
+        let mut w = World::new(800, 600);
+        let id = w.lander.as_ref().unwrap().entity_id;
+        let full = w.get_entity(id).mass;
+        w.thrust_toggle(true);
+        w.tick(100.0);
+        let engine = 20.0 - w.lander.as_ref().unwrap().fuel;
+        assert!(w.get_entity(id).mass < full);
+
+        // the attitude thrusters burn a little too, and turn the lander
+        w.thrust_toggle(false);
+        w.rotation_right_toggle(true);
+        w.tick(100.0);
+        w.tick(100.0);
+        let thrusters = 20.0 - w.lander.as_ref().unwrap().fuel - engine;
+        assert!(thrusters > 0.0 && thrusters < engine);
+        assert!(w.get_entity(id).angular_velocity > 0.0);
+        assert!(w.lander.as_ref().unwrap().facing.x < 0.0);
+    }
+
+    #[test]
+    fn a_fast_lander_stops_at_the_ground() {
+        for seed in 0..10 {
+            let terrain = TerrainConfig {
+                seed: Some(seed),
+                ..TerrainConfig::classic()
+            };
+            let mut w = World::with_terrain(800, 600, &terrain);
+            let id = w.lander.as_ref().unwrap().entity_id;
+            // far enough in one tick to end up below the ground
+            w.get_entity(id).direction = Vec2d::new(0.0, 5000.0);
+            w.tick(100.0);
+
+            let landing = w.landing().expect("the lander should have hit the ground");
+            assert_eq!(landing.verdict, Verdict::Crash);
+            // it rests on the ground instead of below it
+            let position = w.get_entity(id).position;
+            assert!(position.y < w.map.height_at(position.x).unwrap());
+            let facing = w.lander.as_ref().unwrap().facing;
+            let body = w.get_lander_transform(position, facing).transform_many(&graphics::BBox.to_vec());
+            assert!(w.ground.collide(&body).unwrap().depth < 0.1);
+        }
+    }
+
+    #[test]
+    fn the_engines_stop_at_touchdown() {
+        let mut w = World::new(800, 600);
+        let id = w.lander.as_ref().unwrap().entity_id;
+        w.thrust_toggle(true);
+        w.rotation_left_toggle(true);
+        w.get_entity(id).direction = Vec2d::new(0.0, 5000.0);
+        w.tick(100.0);
+        assert!(w.landing().is_some());
+        assert!(w.take_cues().contains(&Cue::StopLoop(Sfx::Thrust)));
+
+        // releasing the key is ignored now, nothing burns anyway
+        let fuel = w.lander.as_ref().unwrap().fuel;
+        w.thrust_toggle(false);
+        w.tick(100.0);
+        let lander = w.lander.as_ref().unwrap();
+        assert!(!lander.drive_enabled && lander.rcs == 0.0);
+        assert_eq!(lander.fuel, fuel);
+        assert!(!w.exhaust.is_emitting());
+    }
+
+    #[test]
+    fn a_crash_settles_once_the_debris_is_gone() {
+        let mut w = World::new(800, 600);
+        let id = w.lander.as_ref().unwrap().entity_id;
+        w.get_entity(id).direction = Vec2d::new(0.0, 5000.0);
+        w.tick(100.0);
+        assert_eq!(w.landing().unwrap().verdict, Verdict::Crash);
+        assert!(!w.is_settled());
+        // debris lives for three seconds at most
+        for _ in 0..31 {
+            w.tick(100.0);
+        }
+        assert!(w.is_settled());
+    }
+
+    #[test]
+    fn the_lander_stays_over_the_map() {
+        let mut w = World::new(800, 600);
+        let id = w.lander.as_ref().unwrap().entity_id;
+        let width = w.map.get_values().last().unwrap().x;
+        for push in [2000.0, -2000.0] {
+            w.get_entity(id).position = Vec2d::new(width / 2.0, 100.0);
+            w.get_entity(id).direction = Vec2d::new(push, 0.0);
+            // long enough to fly past the end of the map, not to come down
+            for _ in 0..20 {
+                w.tick(100.0);
+            }
+            let position = w.get_entity(id).position;
+            assert!(position.x > 0.0 && position.x < width);
+            assert!(w.map.height_at(position.x).is_some());
+            assert_eq!(w.get_entity(id).direction.x, 0.0);
+        }
+    }
+
+    #[test]
+    fn fuel_burns_the_same_at_any_frame_rate() {
+        let burnt = |frame_ms: f32| {
+            let mut w = World::new(800, 600);
+            w.thrust_toggle(true);
+            for _ in 0..(960.0 / frame_ms) as usize {
+                w.tick(frame_ms);
+            }
+            20.0 - w.lander.as_ref().unwrap().fuel
+        };
+        // what the steps burnt, the bit of a step left over burns nothing yet
+        let steps = (960.0 / STEP_MS).floor();
+        assert!((burnt(40.0) - steps * STEP_MS / 1000.0).abs() < 0.001);
+        assert!((burnt(8.0) - burnt(40.0)).abs() < 0.001);
+    }
+
+    #[test]
+    fn thrust_lasts_until_released_or_out_of_fuel() {
+        let mut w = World::new(800, 600);
+        w.thrust_toggle(true);
+        w.tick(100.0);
+        w.tick(100.0);
+        assert!(w.lander.as_ref().unwrap().drive_enabled);
+
+        w.lander.as_mut().unwrap().fuel = 0.05;
+        w.tick(100.0);
+        assert!(!w.lander.as_ref().unwrap().drive_enabled);
+    }
 }
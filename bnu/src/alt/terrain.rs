@@ -0,0 +1,418 @@
+use std::fs;
+
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+use super::map::PointList;
+use super::simulation::Integrator;
+use crate::vecmath::Vec2d;
+
+// Lander maps come from a generator and a few knobs. They are read from a
+// file when a flight starts, so levels can be tuned without building the
+// game again. One `key value` per line, `#` starts a comment:
+//
+//   generator canyon
+//   width 4800
+//   roughness 0.7
+//   pads 3
+//   pad_width 40
+//   seed 1234
+//   integrator euler
+
+pub const TERRAIN_PATH: &str = "./assets/terrain.cfg";
+
+// distance between the points of the sampled generators
+const SAMPLE_STEP: f32 = 8.0;
+// the most a map file may ask for, fifty screens and a pad for each
+const MAX_WIDTH: f32 = 40000.0;
+const MAX_PADS: usize = 50;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GeneratorKind {
+    Midpoint,
+    Ridges,
+    Canyon,
+    Easy,
+}
+
+pub const GENERATOR_NAMES: [&str; 4] = ["midpoint", "ridges", "canyon", "easy"];
+
+impl GeneratorKind {
+    pub fn by_name(name: &str) -> Result<GeneratorKind, String> {
+        match name {
+            "midpoint" => Ok(GeneratorKind::Midpoint),
+            "ridges" => Ok(GeneratorKind::Ridges),
+            "canyon" => Ok(GeneratorKind::Canyon),
+            "easy" => Ok(GeneratorKind::Easy),
+            _ => Err(format!(
+                "Unknown terrain generator '{}', known are {}",
+                name,
+                GENERATOR_NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TerrainConfig {
+    pub generator: GeneratorKind,
+    // in world units, the screen shows 800 of them when zoomed out
+    pub width: f32,
+    // 0 is flat, 1 as wild as the generator gets
+    pub roughness: f32,
+    pub pads: usize,
+    // the widest pad, narrower ones pay more
+    pub pad_width: f32,
+    // None is a new map every flight
+    pub seed: Option<u64>,
+    // how the lander's physics is stepped
+    pub integrator: Integrator,
+}
+
+impl TerrainConfig {
+    // The lander's original ground, four screens wide
+    pub fn classic() -> Self {
+        TerrainConfig {
+            generator: GeneratorKind::Midpoint,
+            width: 3200.0,
+            roughness: 0.5,
+            pads: 4,
+            pad_width: 48.0,
+            seed: None,
+            integrator: Integrator::VelocityVerlet,
+        }
+    }
+
+    // Settings left out keep their classic value
+    pub fn parse(data: &str) -> Result<TerrainConfig, String> {
+        let mut config = TerrainConfig::classic();
+        let lines = data.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#'));
+        for line in lines {
+            let Some((key, value)) = line.split_once(char::is_whitespace) else {
+                return Err(format!("Can't read line '{}'", line));
+            };
+            let value = value.trim();
+            let invalid = || format!("Invalid value '{}' for '{}'", value, key);
+            // nan and inf parse as well, but no generator can work with them
+            let number = || value.parse().ok().filter(|v: &f32| v.is_finite()).ok_or_else(invalid);
+            match key {
+                "generator" => config.generator = GeneratorKind::by_name(value)?,
+                "width" => config.width = Some(number()?).filter(|w| *w > 0.0 && *w <= MAX_WIDTH).ok_or_else(invalid)?,
+                "roughness" => config.roughness = number()?.clamp(0.0, 1.0),
+                "pads" => {
+                    config.pads = value.parse().ok().filter(|p| *p <= MAX_PADS).ok_or_else(invalid)?
+                }
+                "pad_width" => config.pad_width = Some(number()?).filter(|w| *w > 0.0).ok_or_else(invalid)?,
+                "seed" => config.seed = Some(value.parse().map_err(|_| invalid())?),
+                "integrator" => config.integrator = Integrator::by_name(value)?,
+                _ => return Err(format!("Unknown setting '{}'", key)),
+            }
+        }
+        Ok(config)
+    }
+
+    // Without a file the classic map is used
+    pub fn load(path: &str) -> Result<TerrainConfig, String> {
+        match fs::read_to_string(path) {
+            Ok(data) => TerrainConfig::parse(&data).map_err(|e| format!("{}: {}", path, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(TerrainConfig::classic()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+pub trait TerrainGenerator {
+    // The ground from x = 0 to `width`, sorted by x, with y between 0 and
+    // `height` counted up from the bottom of the map
+    fn profile(&self, width: f32, height: f32, rng: &mut ChaCha8Rng) -> Vec<Vec2d>;
+}
+
+pub fn by_kind(kind: GeneratorKind, roughness: f32) -> Box<dyn TerrainGenerator> {
+    match kind {
+        GeneratorKind::Midpoint => Box::new(Midpoint { roughness }),
+        GeneratorKind::Ridges => Box::new(Ridges { roughness }),
+        GeneratorKind::Canyon => Box::new(Canyon { roughness }),
+        GeneratorKind::Easy => Box::new(Easy { roughness }),
+    }
+}
+
+// The whole map, pads included. The same seed always makes the same map.
+pub fn generate(config: &TerrainConfig, height: f32) -> PointList {
+    generate_with_width(config, config.width, height)
+}
+
+pub fn generate_with_width(config: &TerrainConfig, width: f32, height: f32) -> PointList {
+    let mut rng = ChaCha8Rng::seed_from_u64(config.seed.unwrap_or_else(rand::random));
+    let profile = by_kind(config.generator, config.roughness).profile(width, height, &mut rng);
+    PointList::with_pads(profile, config.pads, config.pad_width, &mut rng)
+}
+
+// The ground every SAMPLE_STEP pixels
+fn sample(width: f32, mut ground: impl FnMut(f32) -> f32) -> Vec<Vec2d> {
+    let steps = (width / SAMPLE_STEP).ceil().max(1.0) as usize;
+    (0..=steps)
+        .map(|i| {
+            let x = width * i as f32 / steps as f32;
+            Vec2d::new(x, ground(x))
+        })
+        .collect()
+}
+
+// one random height every so often, the rest comes from splitting
+const START_POINT_SPACING: f32 = 160.0;
+const MIN_SEGMENT: f32 = 10.0;
+// how far the first split may move a point at roughness 0.5
+const MAX_DISPLACEMENT: f32 = 200.0;
+const DISPLACEMENT_FALLOFF: f32 = 1.70;
+
+// The lander's original map: a few random heights, refined by moving the
+// middle of every segment up or down, less with every split
+pub struct Midpoint {
+    roughness: f32,
+}
+
+impl TerrainGenerator for Midpoint {
+    fn profile(&self, width: f32, height: f32, rng: &mut ChaCha8Rng) -> Vec<Vec2d> {
+        let count = (width / START_POINT_SPACING).round().max(1.0) as usize;
+        let step = width / count as f32;
+        let starts: Vec<Vec2d> = (0..=count)
+            .map(|i| Vec2d::new(step * i as f32, rng.gen_range(0.0..=height)))
+            .collect();
+        let mut points = vec![starts[0]];
+        for pair in starts.windows(2) {
+            split(pair[0], pair[1], MAX_DISPLACEMENT * self.roughness * 2.0, height, rng, &mut points);
+            points.push(pair[1]);
+        }
+        points
+    }
+}
+
+// Adds the points between a and b in order, without a and b themselves
+fn split(a: Vec2d, b: Vec2d, displacement: f32, height: f32, rng: &mut ChaCha8Rng, points: &mut Vec<Vec2d>) {
+    let half = (b.x - a.x) / 2.0;
+    let center = (a.y + b.y) / 2.0;
+    let low = (center - displacement / 2.0).max(0.0);
+    let high = (center + displacement / 2.0).min(height).max(low);
+    let middle = Vec2d::new(a.x + half, rng.gen_range(low..=high));
+    if half > MIN_SEGMENT {
+        split(a, middle, displacement / DISPLACEMENT_FALLOFF, height, rng, points);
+        points.push(middle);
+        split(middle, b, displacement / DISPLACEMENT_FALLOFF, height, rng, points);
+    } else {
+        points.push(middle);
+    }
+}
+
+// Gradient noise in one dimension, between -0.5 and 0.5
+struct Perlin {
+    gradients: Vec<f32>,
+}
+
+impl Perlin {
+    fn new(rng: &mut ChaCha8Rng) -> Self {
+        Perlin {
+            gradients: (0..256).map(|_| rng.gen_range(-1.0..=1.0)).collect(),
+        }
+    }
+
+    fn at(&self, x: f32) -> f32 {
+        let cell = x.floor();
+        let t = x - cell;
+        let gradient = |i: i64| self.gradients[i.rem_euclid(self.gradients.len() as i64) as usize];
+        let left = gradient(cell as i64) * t;
+        let right = gradient(cell as i64 + 1) * (t - 1.0);
+        let fade = t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        left + (right - left) * fade
+    }
+}
+
+const OCTAVES: usize = 5;
+// distance between the hills of the lowest octave
+const HILL_SPACING: f32 = 270.0;
+
+// Fractal noise folded into sharp ridges: every octave is twice as fine as
+// the one before, and weaker the smoother the map
+pub struct Ridges {
+    roughness: f32,
+}
+
+impl TerrainGenerator for Ridges {
+    fn profile(&self, width: f32, height: f32, rng: &mut ChaCha8Rng) -> Vec<Vec2d> {
+        let noise = Perlin::new(rng);
+        let gain = 0.3 + 0.4 * self.roughness;
+        let spread = 0.4 + 0.6 * self.roughness;
+        sample(width, |x| {
+            let (mut sum, mut total, mut amplitude, mut frequency) = (0.0, 0.0, 1.0, 1.0 / HILL_SPACING);
+            for octave in 0..OCTAVES {
+                // a ridge where the noise crosses zero
+                let ridge = 1.0 - (noise.at(x * frequency + octave as f32 * 17.3) * 2.0).abs();
+                sum += ridge * ridge * amplitude;
+                total += amplitude;
+                amplitude *= gain;
+                frequency *= 2.0;
+            }
+            height * (0.5 + (sum / total - 0.5) * spread)
+        })
+    }
+}
+
+// High plateaus cut by steep canyons, deeper and narrower the rougher
+pub struct Canyon {
+    roughness: f32,
+}
+
+impl TerrainGenerator for Canyon {
+    fn profile(&self, width: f32, height: f32, rng: &mut ChaCha8Rng) -> Vec<Vec2d> {
+        // a few canyons every 800 units
+        let screens = (width / 800.0).ceil().max(1.0) as usize;
+        let count = rng.gen_range(1..=2 + (self.roughness * 2.0) as usize) * screens;
+        let slot = width / count as f32;
+        // centre and half width of every canyon
+        let canyons: Vec<(f32, f32)> = (0..count)
+            .map(|i| {
+                let centre = slot * (i as f32 + 0.5) + rng.gen_range(-0.2..=0.2) * slot;
+                (centre, slot * rng.gen_range(0.2..=0.35))
+            })
+            .collect();
+        let rim = height * 0.85;
+        let floor = height * (0.5 - 0.4 * self.roughness);
+        let noise = Perlin::new(rng);
+        sample(width, |x| {
+            let depth = canyons
+                .iter()
+                .map(|(centre, half)| {
+                    let wall = half * (0.6 - 0.4 * self.roughness);
+                    // 1 on the floor, 0 on the rim
+                    ((half - (x - centre).abs()) / wall).clamp(0.0, 1.0)
+                })
+                .fold(0.0, f32::max);
+            let bumps = noise.at(x / 40.0) * height * 0.1 * self.roughness;
+            (rim + (floor - rim) * depth + bumps).clamp(0.0, height)
+        })
+    }
+}
+
+// Mostly flat ground in wide steps, with gentle ramps between them
+pub struct Easy {
+    roughness: f32,
+}
+
+impl TerrainGenerator for Easy {
+    fn profile(&self, width: f32, height: f32, rng: &mut ChaCha8Rng) -> Vec<Vec2d> {
+        let mut y = rng.gen_range(0.3..=0.6) * height;
+        let mut x = 0.0;
+        let mut points = vec![Vec2d::new(x, y)];
+        loop {
+            x = (x + rng.gen_range(80.0..200.0)).min(width);
+            points.push(Vec2d::new(x, y));
+            if x >= width {
+                return points;
+            }
+            y = (y + rng.gen_range(-1.0..=1.0) * height * 0.25 * self.roughness).clamp(0.0, height);
+            x = (x + rng.gen_range(30.0..60.0)).min(width);
+            points.push(Vec2d::new(x, y));
+            if x >= width {
+                return points;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [GeneratorKind; 4] = [
+        GeneratorKind::Midpoint,
+        GeneratorKind::Ridges,
+        GeneratorKind::Canyon,
+        GeneratorKind::Easy,
+    ];
+
+    // how far the ground goes up and down in total
+    fn travel(profile: &[Vec2d]) -> f32 {
+        profile.windows(2).map(|p| (p[1].y - p[0].y).abs()).sum()
+    }
+
+    #[test]
+    fn every_generator_covers_the_map() {
+        for kind in KINDS {
+            for seed in 0..10 {
+                let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                let profile = by_kind(kind, 0.7).profile(800.0, 200.0, &mut rng);
+                assert_eq!(profile.first().unwrap().x, 0.0, "{:?}", kind);
+                assert_eq!(profile.last().unwrap().x, 800.0, "{:?}", kind);
+                for pair in profile.windows(2) {
+                    assert!(pair[0].x < pair[1].x, "{:?} goes back at {}", kind, pair[1].x);
+                }
+                assert!(profile.iter().all(|p| p.y >= 0.0 && p.y <= 200.0), "{:?}", kind);
+            }
+        }
+    }
+
+    #[test]
+    fn maps_repeat_with_their_seed() {
+        let config = TerrainConfig {
+            generator: GeneratorKind::Ridges,
+            pads: 3,
+            seed: Some(9),
+            ..TerrainConfig::classic()
+        };
+        let (a, b) = (generate(&config, 200.0), generate(&config, 200.0));
+        assert_eq!(a.pads(), b.pads());
+        assert_eq!(a.pads().len(), 3);
+        let ys = |m: &PointList| m.get_values().iter().map(|v| v.y).collect::<Vec<f32>>();
+        assert_eq!(ys(&a), ys(&b));
+    }
+
+    #[test]
+    fn roughness_makes_wilder_maps() {
+        for kind in KINDS {
+            let (mut smooth, mut rough) = (0.0, 0.0);
+            for seed in 0..10 {
+                smooth += travel(&by_kind(kind, 0.0).profile(800.0, 200.0, &mut ChaCha8Rng::seed_from_u64(seed)));
+                rough += travel(&by_kind(kind, 1.0).profile(800.0, 200.0, &mut ChaCha8Rng::seed_from_u64(seed)));
+            }
+            assert!(rough > smooth, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn split_fills_in_between() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut points = Vec::new();
+        split(Vec2d::new(0.0, 50.0), Vec2d::new(100.0, 50.0), 20.0, 100.0, &mut rng, &mut points);
+        assert!(points.len() > 1);
+        assert!(points.windows(2).all(|p| p[0].x < p[1].x));
+        assert!(points.iter().all(|p| p.x > 0.0 && p.x < 100.0));
+    }
+
+    #[test]
+    fn settings_are_read_from_text() {
+        let config =
+            TerrainConfig::parse("# easy start\ngenerator easy\nwidth 1600\nroughness 2\n\npads 4\npad_width 60\nseed 5\nintegrator euler\n")
+                .unwrap();
+        assert_eq!(
+            config,
+            TerrainConfig {
+                generator: GeneratorKind::Easy,
+                width: 1600.0,
+                roughness: 1.0,
+                pads: 4,
+                pad_width: 60.0,
+                seed: Some(5),
+                integrator: Integrator::SemiImplicitEuler,
+            }
+        );
+        assert_eq!(TerrainConfig::parse("").unwrap(), TerrainConfig::classic());
+        assert!(TerrainConfig::parse("generator swiss_cheese").is_err());
+        assert!(TerrainConfig::parse("pad_width -3").is_err());
+        assert!(TerrainConfig::parse("gravity 3").is_err());
+        assert!(TerrainConfig::parse("integrator runge_kutta").is_err());
+        assert!(TerrainConfig::parse("pads").is_err());
+        // hand edited files can say anything
+        for broken in ["roughness nan", "width inf", "width 1e30", "pad_width NaN", "pads 1000000", "pads -1"] {
+            assert!(TerrainConfig::parse(broken).is_err(), "{}", broken);
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::time::{Duration, Instant};
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+
+use crate::draw;
+use crate::vecmath::{TransformationMatrix, Vec2d};
+
+const CELL_SIZE: f32 = 32.0;
+
+#[derive(Clone)]
+pub enum Effect {
+    // A shell flying on a ballistic arc between two pixel positions
+    Projectile { from: Vec2d, to: Vec2d },
+    Splash { at: Vec2d },
+    Explosion { at: Vec2d },
+    // top left pixel positions of all cells of the ship that goes down
+    Sinking { cells: Vec<Vec2d> },
+}
+
+impl Effect {
+    pub fn duration(&self) -> Duration {
+        match self {
+            Effect::Projectile { .. } => Duration::from_millis(800),
+            Effect::Splash { .. } => Duration::from_millis(600),
+            Effect::Explosion { .. } => Duration::from_millis(500),
+            Effect::Sinking { .. } => Duration::from_millis(2500),
+        }
+    }
+
+    fn render(&self, canvas: &mut Canvas<Window>, progress: f32) {
+        match self {
+            Effect::Projectile { from, to } => render_projectile(canvas, *from, *to, progress),
+            Effect::Splash { at } => render_splash(canvas, *at, progress),
+            Effect::Explosion { at } => render_explosion(canvas, *at, progress),
+            Effect::Sinking { cells } => render_sinking(canvas, cells, progress),
+        }
+    }
+}
+
+// Maps the unit interval on the x axis onto the line from -> to and lifts
+// it by a parabola, so that t = 0.0 is the start and t = 1.0 the target.
+pub fn arc_position(from: Vec2d, to: Vec2d, t: f32) -> Vec2d {
+    let delta = to - from;
+    let distance = delta.len();
+    let base = TransformationMatrix::translation_v(from)
+        * TransformationMatrix::rotate(delta.y.atan2(delta.x))
+        * TransformationMatrix::scale(distance, distance);
+    let arc_height = (distance * 0.35).max(60.0);
+    let lift = TransformationMatrix::translation(0.0, -arc_height * 4.0 * t * (1.0 - t));
+    (lift * base).transform(&Vec2d::new(t, 0.0))
+}
+
+fn fill_square(canvas: &mut Canvas<Window>, center: Vec2d, size: f32, color: Color) {
+    canvas.set_draw_color(color);
+    let _ = canvas.fill_rect(Rect::new(
+        (center.x - size / 2.0) as i32,
+        (center.y - size / 2.0) as i32,
+        size.max(1.0) as u32,
+        size.max(1.0) as u32,
+    ));
+}
+
+fn render_projectile(canvas: &mut Canvas<Window>, from: Vec2d, to: Vec2d, t: f32) {
+    canvas.set_blend_mode(BlendMode::Blend);
+    for idx in 1..=5 {
+        let trail_t = t - idx as f32 * 0.03;
+        if trail_t < 0.0 {
+            break;
+        }
+        let alpha = 160 - idx as u8 * 25;
+        fill_square(
+            canvas,
+            arc_position(from, to, trail_t),
+            2.0,
+            Color::RGBA(90, 90, 90, alpha),
+        );
+    }
+    // The shell grows towards the apex of its flight to fake some height
+    let size = 4.0 + 4.0 * (PI * t).sin();
+    fill_square(
+        canvas,
+        arc_position(from, to, t),
+        size,
+        Color::RGB(220, 220, 200),
+    );
+}
+
+fn render_splash(canvas: &mut Canvas<Window>, at: Vec2d, t: f32) {
+    canvas.set_blend_mode(BlendMode::Blend);
+    let alpha = (255.0 * (1.0 - t)) as u8;
+    for ring in 0..3 {
+        let ring_t = t - ring as f32 * 0.15;
+        if ring_t <= 0.0 {
+            continue;
+        }
+        let _ = draw::draw_circle(
+            canvas,
+            &at,
+            4.0 + 14.0 * ring_t,
+            Color::RGBA(180, 220, 255, alpha),
+        );
+    }
+    let column_height = 20.0 * (PI * t).sin();
+    canvas.set_draw_color(Color::RGBA(220, 240, 255, alpha));
+    let _ = canvas.fill_rect(Rect::new(
+        at.x as i32 - 3,
+        (at.y - column_height) as i32,
+        6,
+        column_height.max(1.0) as u32,
+    ));
+}
+
+fn render_explosion(canvas: &mut Canvas<Window>, at: Vec2d, t: f32) {
+    canvas.set_blend_mode(BlendMode::Add);
+    let fade = 1.0 - t;
+    let size = 8.0 + 24.0 * (PI * t.min(0.5)).sin();
+    fill_square(
+        canvas,
+        at,
+        size,
+        Color::RGBA(255, (220.0 * fade) as u8, (80.0 * fade) as u8, 255),
+    );
+    fill_square(canvas, at, size / 2.0, Color::RGBA(255, 255, 180, 255));
+    let _ = draw::draw_circle(
+        canvas,
+        &at,
+        size * 0.75 + 10.0 * t,
+        Color::RGBA(255, 140, 0, (255.0 * fade) as u8),
+    );
+}
+
+fn render_sinking(canvas: &mut Canvas<Window>, cells: &[Vec2d], t: f32) {
+    canvas.set_blend_mode(BlendMode::Blend);
+    // Cells go down one after another, the stern follows the bow
+    let stagger = 0.1;
+    let span = 1.0 - stagger * (cells.len().max(1) - 1) as f32;
+    for (idx, cell) in cells.iter().enumerate() {
+        let cell_t = ((t - idx as f32 * stagger) / span).clamp(0.0, 1.0);
+        let hull_height = CELL_SIZE * (1.0 - cell_t);
+        canvas.set_draw_color(Color::RGBA(90, 90, 90, 220));
+        let _ = canvas.fill_rect(Rect::new(
+            cell.x as i32 + 4,
+            (cell.y + CELL_SIZE - hull_height) as i32,
+            CELL_SIZE as u32 - 8,
+            hull_height as u32,
+        ));
+
+        // Bubbles rise from wherever the hull went under
+        if cell_t > 0.0 && cell_t < 1.0 {
+            for bubble in 0..3 {
+                let phase = (cell_t * 3.0 + bubble as f32 / 3.0).fract();
+                let pos = *cell
+                    + Vec2d::new(8.0 + bubble as f32 * 8.0, CELL_SIZE - phase * CELL_SIZE);
+                let _ = draw::draw_circle(
+                    canvas,
+                    &pos,
+                    1.0 + 2.0 * phase,
+                    Color::RGBA(200, 230, 255, (200.0 * (1.0 - phase)) as u8),
+                );
+            }
+        }
+    }
+}
+
+// A sequence of steps, each step consisting of effects that play in parallel.
+// The next step starts once every effect of the current one has finished.
+#[derive(Clone)]
+pub struct Animation {
+    steps: VecDeque<Vec<Effect>>,
+    step_started: Instant,
+    // 2.0 plays everything twice as fast
+    speed: f32,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Animation::new()
+    }
+}
+
+impl Animation {
+    pub fn new() -> Self {
+        Animation {
+            steps: VecDeque::new(),
+            step_started: Instant::now(),
+            speed: 1.0,
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    fn scaled(&self, duration: Duration) -> Duration {
+        duration.div_f32(self.speed)
+    }
+
+    pub fn then(&mut self, effects: Vec<Effect>) {
+        if self.steps.is_empty() {
+            self.step_started = Instant::now();
+        }
+        self.steps.push_back(effects);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn is_projectile_in_flight(&self) -> bool {
+        match self.steps.front() {
+            Some(step) => step
+                .iter()
+                .any(|e| matches!(e, Effect::Projectile { .. })),
+            None => false,
+        }
+    }
+
+    fn step_duration(step: &[Effect]) -> Duration {
+        step.iter()
+            .map(|e| e.duration())
+            .max()
+            .unwrap_or(Duration::ZERO)
+    }
+
+    pub fn tick(&mut self) {
+        while let Some(step) = self.steps.front() {
+            let duration = self.scaled(Animation::step_duration(step));
+            let elapsed = self.step_started.elapsed();
+            if elapsed < duration {
+                break;
+            }
+            self.steps.pop_front();
+            // Carry the overshoot over, so that a slow frame doesn't
+            // stretch the whole sequence
+            self.step_started += duration;
+        }
+    }
+
+    pub fn render(&self, canvas: &mut Canvas<Window>) {
+        if let Some(step) = self.steps.front() {
+            let elapsed = self.step_started.elapsed();
+            for effect in step.iter() {
+                let progress =
+                    (elapsed.as_secs_f32() / self.scaled(effect.duration()).as_secs_f32()).min(1.0);
+                if progress < 1.0 {
+                    effect.render(canvas, progress);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arc_starts_and_ends_on_target() {
+        let from = Vec2d::new(100.0, 200.0);
+        let to = Vec2d::new(500.0, 100.0);
+        let start = arc_position(from, to, 0.0);
+        let end = arc_position(from, to, 1.0);
+        assert!((start - from).len() < 0.01);
+        assert!((end - to).len() < 0.01);
+    }
+
+    #[test]
+    fn arc_goes_up_in_both_directions() {
+        let left = Vec2d::new(100.0, 200.0);
+        let right = Vec2d::new(500.0, 200.0);
+        assert!(arc_position(left, right, 0.5).y < 200.0);
+        assert!(arc_position(right, left, 0.5).y < 200.0);
+    }
+
+    #[test]
+    fn empty_animation_is_finished() {
+        let mut a = Animation::new();
+        assert!(a.is_finished());
+        a.then(vec![Effect::Splash {
+            at: Vec2d::default(),
+        }]);
+        assert!(!a.is_finished());
+        assert!(!a.is_projectile_in_flight());
+    }
+
+    #[test]
+    fn faster_animations_finish_sooner() {
+        let mut a = Animation::new();
+        a.set_speed(100.0);
+        a.then(vec![Effect::Sinking { cells: Vec::new() }]);
+        std::thread::sleep(Duration::from_millis(30));
+        a.tick();
+        assert!(a.is_finished());
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use sdl2::mixer::{self, Channel, Chunk, Music, Sdl2MixerContext};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Sfx {
+    ShotFired,
+    Splash,
+    Hit,
+    Sink,
+    UnicornLaugh,
+    Thrust,
+    Crash,
+}
+
+impl Sfx {
+    const ALL: [Sfx; 7] = [
+        Sfx::ShotFired,
+        Sfx::Splash,
+        Sfx::Hit,
+        Sfx::Sink,
+        Sfx::UnicornLaugh,
+        Sfx::Thrust,
+        Sfx::Crash,
+    ];
+
+    fn file_name(&self) -> &'static str {
+        match self {
+            Sfx::ShotFired => "shot.wav",
+            Sfx::Splash => "splash.wav",
+            Sfx::Hit => "hit.wav",
+            Sfx::Sink => "sink.wav",
+            Sfx::UnicornLaugh => "laugh.wav",
+            Sfx::Thrust => "thrust.wav",
+            Sfx::Crash => "crash.wav",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Track {
+    Title,
+    Battle,
+    Victory,
+    Defeat,
+    Lander,
+}
+
+impl Track {
+    const ALL: [Track; 5] = [
+        Track::Title,
+        Track::Battle,
+        Track::Victory,
+        Track::Defeat,
+        Track::Lander,
+    ];
+
+    fn file_name(&self) -> &'static str {
+        match self {
+            Track::Title => "title.wav",
+            Track::Battle => "battle.wav",
+            Track::Victory => "victory.wav",
+            Track::Defeat => "defeat.wav",
+            Track::Lander => "lander.wav",
+        }
+    }
+}
+
+// Game states don't talk to the audio device directly, they queue cues
+// which the main loop hands over to `Audio::cue`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Cue {
+    Play(Sfx),
+    StartLoop(Sfx),
+    StopLoop(Sfx),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AudioSettings {
+    // all volumes range from 0 to mixer::MAX_VOLUME
+    pub master_volume: i32,
+    pub music_volume: i32,
+    pub sfx_volume: i32,
+    pub muted: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            master_volume: mixer::MAX_VOLUME,
+            music_volume: mixer::MAX_VOLUME / 2,
+            sfx_volume: mixer::MAX_VOLUME,
+            muted: false,
+        }
+    }
+}
+
+impl AudioSettings {
+    fn scaled(&self, volume: i32) -> i32 {
+        if self.muted {
+            return 0;
+        }
+        volume * self.master_volume / mixer::MAX_VOLUME
+    }
+
+    pub fn effective_sfx_volume(&self) -> i32 {
+        self.scaled(self.sfx_volume)
+    }
+
+    pub fn effective_music_volume(&self) -> i32 {
+        self.scaled(self.music_volume)
+    }
+
+    pub fn change_master_volume(&mut self, delta: i32) {
+        self.master_volume = (self.master_volume + delta).clamp(0, mixer::MAX_VOLUME);
+    }
+}
+
+struct Device {
+    _subsystem: sdl2::AudioSubsystem,
+    _mixer: Sdl2MixerContext,
+    chunks: HashMap<Sfx, Chunk>,
+    music: HashMap<Track, Music<'static>>,
+    loops: HashMap<Sfx, Channel>,
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        mixer::close_audio();
+    }
+}
+
+pub struct Audio {
+    // None if there is no usable audio device, every call is a no-op then
+    device: Option<Device>,
+    settings: AudioSettings,
+    current_track: Option<Track>,
+}
+
+impl Audio {
+    pub fn new(sdl_context: &sdl2::Sdl) -> Self {
+        match Audio::open_device(sdl_context) {
+            Ok(device) => Audio {
+                device: Some(device),
+                settings: AudioSettings::default(),
+                current_track: None,
+            },
+            Err(e) => {
+                eprintln!("Audio disabled: {}", e);
+                Audio::silent()
+            }
+        }
+    }
+
+    pub fn silent() -> Self {
+        Audio {
+            device: None,
+            settings: AudioSettings::default(),
+            current_track: None,
+        }
+    }
+
+    fn open_device(sdl_context: &sdl2::Sdl) -> Result<Device, String> {
+        // Fails without a device, e.g. when SDL_AUDIODRIVER points nowhere
+        let subsystem = sdl_context.audio()?;
+        let context = mixer::init(mixer::InitFlag::empty())?;
+        mixer::open_audio(
+            mixer::DEFAULT_FREQUENCY,
+            mixer::DEFAULT_FORMAT,
+            mixer::DEFAULT_CHANNELS,
+            1024,
+        )?;
+        mixer::allocate_channels(16);
+
+        // Missing files only cost us the sound, not the game. They are
+        // reported once, here, and never again while playing.
+        let mut failed = Vec::new();
+        let mut chunks = HashMap::new();
+        for sfx in Sfx::ALL.iter() {
+            let path = Path::new("assets/sfx").join(sfx.file_name());
+            match Chunk::from_file(&path) {
+                Ok(chunk) => {
+                    chunks.insert(*sfx, chunk);
+                }
+                Err(e) => failed.push(format!("{} ({})", path.display(), e)),
+            }
+        }
+
+        let mut music = HashMap::new();
+        for track in Track::ALL.iter() {
+            let path = Path::new("assets/music").join(track.file_name());
+            if !path.exists() {
+                continue;
+            }
+            match Music::from_file(&path) {
+                Ok(m) => {
+                    music.insert(*track, m);
+                }
+                Err(e) => failed.push(format!("{} ({})", path.display(), e)),
+            }
+        }
+        if !failed.is_empty() {
+            eprintln!("Could not load {}", failed.join(", "));
+        }
+
+        Ok(Device {
+            _subsystem: subsystem,
+            _mixer: context,
+            chunks,
+            music,
+            loops: HashMap::new(),
+        })
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.device.is_some()
+    }
+
+    pub fn settings(&self) -> AudioSettings {
+        self.settings
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.settings.muted = !self.settings.muted;
+        self.apply_volume();
+    }
+
+    pub fn change_volume(&mut self, delta: i32) {
+        self.settings.change_master_volume(delta);
+        self.apply_volume();
+    }
+
+    fn apply_volume(&mut self) {
+        if self.device.is_some() {
+            Channel::all().set_volume(self.settings.effective_sfx_volume());
+            Music::set_volume(self.settings.effective_music_volume());
+        }
+    }
+
+    pub fn cue(&mut self, cue: Cue) {
+        match cue {
+            Cue::Play(sfx) => self.play(sfx),
+            Cue::StartLoop(sfx) => self.start_loop(sfx),
+            Cue::StopLoop(sfx) => self.stop_loop(sfx),
+        }
+    }
+
+    pub fn play(&mut self, sfx: Sfx) {
+        if let Some(device) = self.device.as_ref() {
+            if let Some(chunk) = device.chunks.get(&sfx) {
+                if let Ok(channel) = Channel::all().play(chunk, 0) {
+                    channel.set_volume(self.settings.effective_sfx_volume());
+                }
+            }
+        }
+    }
+
+    pub fn start_loop(&mut self, sfx: Sfx) {
+        let volume = self.settings.effective_sfx_volume();
+        if let Some(device) = self.device.as_mut() {
+            if device.loops.contains_key(&sfx) {
+                return;
+            }
+            if let Some(chunk) = device.chunks.get(&sfx) {
+                if let Ok(channel) = Channel::all().play(chunk, -1) {
+                    channel.set_volume(volume);
+                    device.loops.insert(sfx, channel);
+                }
+            }
+        }
+    }
+
+    pub fn stop_loop(&mut self, sfx: Sfx) {
+        if let Some(device) = self.device.as_mut() {
+            if let Some(channel) = device.loops.remove(&sfx) {
+                channel.halt();
+            }
+        }
+    }
+
+    // Switches the background music, keeps playing if the track is unchanged
+    pub fn play_music(&mut self, track: Option<Track>) {
+        if self.current_track == track {
+            return;
+        }
+        self.current_track = track;
+        if let Some(device) = self.device.as_ref() {
+            Music::halt();
+            if let Some(music) = track.and_then(|t| device.music.get(&t)) {
+                let _ = music.play(-1);
+                Music::set_volume(self.settings.effective_music_volume());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mute_silences_everything() {
+        let s = AudioSettings {
+            muted: true,
+            ..AudioSettings::default()
+        };
+        assert_eq!(s.effective_sfx_volume(), 0);
+        assert_eq!(s.effective_music_volume(), 0);
+    }
+
+    #[test]
+    fn master_volume_scales_and_clamps() {
+        let mut s = AudioSettings::default();
+        s.change_master_volume(-mixer::MAX_VOLUME / 2);
+        assert_eq!(s.effective_sfx_volume(), mixer::MAX_VOLUME / 2);
+        s.change_master_volume(-1000);
+        assert_eq!(s.master_volume, 0);
+        s.change_master_volume(1000);
+        assert_eq!(s.master_volume, mixer::MAX_VOLUME);
+    }
+
+    #[test]
+    fn silent_audio_ignores_cues() {
+        let mut a = Audio::silent();
+        assert!(!a.is_available());
+        a.cue(Cue::Play(Sfx::Hit));
+        a.cue(Cue::StartLoop(Sfx::Thrust));
+        a.play_music(Some(Track::Battle));
+        a.toggle_mute();
+        assert!(a.settings().muted);
+    }
+}
@@ -0,0 +1,58 @@
+use std::fs;
+
+use bns::engine::arena;
+use bns::engine::rules::Rules;
+use bns::engine::strategy::STRATEGY_NAMES;
+
+// Pits two AIs against each other without opening a window:
+//
+//   bns-arena [--games N] [--seed S] [--csv FILE] [--json FILE] <ai> <ai>
+//
+// The report goes to stdout, per-game results optionally to CSV and JSON.
+
+const DEFAULT_GAMES: usize = 1000;
+
+fn usage() -> String {
+    format!(
+        "usage: bns-arena [--games N] [--seed S] [--csv FILE] [--json FILE] <ai> <ai>\nknown AIs: {}",
+        STRATEGY_NAMES.join(", ")
+    )
+}
+
+fn main() -> Result<(), String> {
+    let mut games = DEFAULT_GAMES;
+    let mut seed = 0;
+    let mut csv = None;
+    let mut json = None;
+    let mut names = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value\n{}", arg, usage()));
+        match arg.as_str() {
+            "--games" => games = value()?.parse().map_err(|_| "--games needs a number".to_string())?,
+            "--seed" => seed = value()?.parse().map_err(|_| "--seed needs a number".to_string())?,
+            "--csv" => csv = Some(value()?),
+            "--json" => json = Some(value()?),
+            "--help" | "-h" => {
+                println!("{}", usage());
+                return Ok(());
+            }
+            _ => names.push(arg),
+        }
+    }
+    let names: [String; 2] = names.try_into().map_err(|_| usage())?;
+
+    let rules = Rules::classic();
+    let results = arena::run(&names, &rules, games, seed)?;
+    let summary = arena::summarize(&names, &results);
+    print!("{}", summary.to_text());
+
+    if let Some(path) = csv {
+        fs::write(&path, arena::to_csv(&results)).map_err(|e| format!("Can't write {}: {}", path, e))?;
+    }
+    if let Some(path) = json {
+        fs::write(&path, summary.to_json(&results)).map_err(|e| format!("Can't write {}: {}", path, e))?;
+    }
+    Ok(())
+}
@@ -70,6 +70,21 @@ pub fn draw_rect(
     }
 }
 
+pub fn draw_circle(
+    canvas: &mut Canvas<Window>,
+    center: &Vec2d,
+    radius: f32,
+    color: Color,
+) -> Result<(), String> {
+    const SEGMENTS: i32 = 16;
+    let mut points = Vec::new();
+    for idx in 0..SEGMENTS {
+        let angle = (idx as f32 / SEGMENTS as f32) * 2.0 * std::f32::consts::PI;
+        points.push(*center + Vec2d::from_angle(angle) * radius);
+    }
+    draw_lines(canvas, &points, color, true)
+}
+
 pub fn draw_text(
     canvas: &mut Canvas<Window>,
     text: &str,
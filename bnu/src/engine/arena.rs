@@ -0,0 +1,305 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use super::rules::Rules;
+use super::strategy;
+use super::{Playfield, ShotResult};
+
+// Plays strategies against each other without any rendering and sums up how
+// they did. Every game is seeded, so a run can be repeated shot for shot.
+
+#[derive(Clone, Debug)]
+pub struct GameResult {
+    pub seed: u64,
+    // the side that fired first, sides take turns opening
+    pub first: usize,
+    pub winner: usize,
+    // shots fired by each side
+    pub shots: [usize; 2],
+    pub duration: Duration,
+}
+
+impl GameResult {
+    pub fn winning_shots(&self) -> usize {
+        self.shots[self.winner]
+    }
+}
+
+// One game between the named strategies. Fleets and every random choice of
+// both strategies come from `seed`.
+pub fn play(names: &[String; 2], rules: &Rules, seed: u64, first: usize) -> Result<GameResult, String> {
+    rules.validate()?;
+    let started = Instant::now();
+    let mut strategies = [strategy::by_name(&names[0])?, strategy::by_name(&names[1])?];
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut fields = [Playfield::new(), Playfield::new()];
+    for field in fields.iter_mut() {
+        field.distribute_ships(&rules.ship_sizes, &mut rng);
+    }
+
+    let mut shots = [0, 0];
+    let mut current = first;
+    loop {
+        let target = 1 - current;
+        let index = strategies[current].choose(&fields[target], &mut rng);
+        if index >= fields[target].tiles.len() || fields[target].was_shot_at(index) {
+            return Err(format!("{} fired at tile {}, which it can't", names[current], index));
+        }
+        let result = fields[target].fire_at(index);
+        let hit = result != ShotResult::Miss;
+        strategies[current].observe(index, &result);
+        shots[current] += 1;
+        if fields[target].is_destroyed() {
+            return Ok(GameResult {
+                seed,
+                first,
+                winner: current,
+                shots,
+                duration: started.elapsed(),
+            });
+        }
+        if !(hit && rules.hit_again) {
+            current = target;
+        }
+    }
+}
+
+// Game i is played with seed `seed + i`, the opening side alternates
+pub fn run(names: &[String; 2], rules: &Rules, games: usize, seed: u64) -> Result<Vec<GameResult>, String> {
+    (0..games)
+        .map(|i| play(names, rules, seed.wrapping_add(i as u64), i % 2))
+        .collect()
+}
+
+// How one side did over a whole run
+#[derive(Clone, Debug, PartialEq)]
+pub struct SideSummary {
+    pub name: String,
+    pub wins: usize,
+    pub win_rate: f64,
+    // shots needed for the games it won
+    pub mean_shots: f64,
+    pub median_shots: f64,
+    // shots to win -> number of games
+    pub distribution: BTreeMap<usize, usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Summary {
+    pub games: usize,
+    pub sides: [SideSummary; 2],
+    pub mean_time: Duration,
+    pub total_time: Duration,
+}
+
+pub fn median(values: &[usize]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) as f64 / 2.0
+    } else {
+        sorted[mid] as f64
+    }
+}
+
+pub fn summarize(names: &[String; 2], results: &[GameResult]) -> Summary {
+    let side = |s: usize| {
+        let won: Vec<usize> = results
+            .iter()
+            .filter(|r| r.winner == s)
+            .map(|r| r.winning_shots())
+            .collect();
+        let mut distribution = BTreeMap::new();
+        for shots in won.iter() {
+            *distribution.entry(*shots).or_insert(0) += 1;
+        }
+        SideSummary {
+            name: names[s].clone(),
+            wins: won.len(),
+            win_rate: if results.is_empty() { 0.0 } else { won.len() as f64 / results.len() as f64 },
+            mean_shots: if won.is_empty() { 0.0 } else { won.iter().sum::<usize>() as f64 / won.len() as f64 },
+            median_shots: median(&won),
+            distribution,
+        }
+    };
+    let total_time: Duration = results.iter().map(|r| r.duration).sum();
+    Summary {
+        games: results.len(),
+        sides: [side(0), side(1)],
+        mean_time: total_time.checked_div(results.len() as u32).unwrap_or_default(),
+        total_time,
+    }
+}
+
+impl Summary {
+    pub fn to_text(&self) -> String {
+        let mut out = format!(
+            "{} games of {} vs {}\n\n",
+            self.games, self.sides[0].name, self.sides[1].name
+        );
+        for (n, side) in self.sides.iter().enumerate() {
+            out += &format!(
+                "{} ({}): {} wins ({:.1}%), mean {:.2} shots to win, median {:.1}\n",
+                n + 1,
+                side.name,
+                side.wins,
+                side.win_rate * 100.0,
+                side.mean_shots,
+                side.median_shots
+            );
+        }
+        out += &format!(
+            "\ntime: {:.3} ms per game, {:.3} s in total\n",
+            self.mean_time.as_secs_f64() * 1000.0,
+            self.total_time.as_secs_f64()
+        );
+
+        // one bar per shot count, scaled to the most common one
+        for side in self.sides.iter() {
+            out += &format!("\nshots to win for {}:\n", side.name);
+            let most = side.distribution.values().copied().max().unwrap_or(1);
+            for (shots, count) in side.distribution.iter() {
+                let bar = "#".repeat((count * 40).div_ceil(most));
+                out += &format!("{:4} {:6} {}\n", shots, count, bar);
+            }
+        }
+        out
+    }
+
+    pub fn to_json(&self, results: &[GameResult]) -> String {
+        let sides: Vec<String> = self
+            .sides
+            .iter()
+            .map(|s| {
+                let distribution: Vec<String> = s
+                    .distribution
+                    .iter()
+                    .map(|(shots, count)| format!("\"{}\": {}", shots, count))
+                    .collect();
+                format!(
+                    "    {{\"name\": \"{}\", \"wins\": {}, \"win_rate\": {:.4}, \"mean_shots\": {:.4}, \"median_shots\": {:.1}, \"distribution\": {{{}}}}}",
+                    s.name,
+                    s.wins,
+                    s.win_rate,
+                    s.mean_shots,
+                    s.median_shots,
+                    distribution.join(", ")
+                )
+            })
+            .collect();
+        let games: Vec<String> = results
+            .iter()
+            .map(|r| {
+                format!(
+                    "    {{\"seed\": {}, \"first\": {}, \"winner\": {}, \"shots\": [{}, {}], \"micros\": {}}}",
+                    r.seed,
+                    r.first + 1,
+                    r.winner + 1,
+                    r.shots[0],
+                    r.shots[1],
+                    r.duration.as_micros()
+                )
+            })
+            .collect();
+        format!(
+            "{{\n  \"games\": {},\n  \"mean_micros\": {},\n  \"total_micros\": {},\n  \"sides\": [\n{}\n  ],\n  \"results\": [\n{}\n  ]\n}}\n",
+            self.games,
+            self.mean_time.as_micros(),
+            self.total_time.as_micros(),
+            sides.join(",\n"),
+            games.join(",\n")
+        )
+    }
+}
+
+// One row per game, sides are numbered from 1 like in the text report
+pub fn to_csv(results: &[GameResult]) -> String {
+    let mut out = "game,seed,first,winner,shots1,shots2,micros\n".to_string();
+    for (n, r) in results.iter().enumerate() {
+        out += &format!(
+            "{},{},{},{},{},{},{}\n",
+            n + 1,
+            r.seed,
+            r.first + 1,
+            r.winner + 1,
+            r.shots[0],
+            r.shots[1],
+            r.duration.as_micros()
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(a: &str, b: &str) -> [String; 2] {
+        [a.to_string(), b.to_string()]
+    }
+
+    #[test]
+    fn seeded_games_repeat() {
+        let rules = Rules::classic();
+        let first = run(&names("hunt", "random"), &rules, 20, 7).unwrap();
+        let second = run(&names("hunt", "random"), &rules, 20, 7).unwrap();
+        for (a, b) in first.iter().zip(second.iter()) {
+            assert_eq!((a.winner, a.shots), (b.winner, b.shots));
+        }
+        // the loser fired as often as the winner, or once less if it went second
+        for r in first.iter() {
+            let loser = r.shots[1 - r.winner];
+            let expected = if r.first == r.winner { r.winning_shots() - 1 } else { r.winning_shots() };
+            assert_eq!(loser, expected);
+        }
+    }
+
+    #[test]
+    fn targeting_beats_random_shots() {
+        let rules = Rules::classic();
+        let results = run(&names("parity", "random"), &rules, 100, 1).unwrap();
+        let summary = summarize(&names("parity", "random"), &results);
+        assert_eq!(summary.sides[0].wins + summary.sides[1].wins, 100);
+        assert!(summary.sides[0].win_rate > 0.8);
+    }
+
+    #[test]
+    fn hits_keep_the_turn_when_the_rules_say_so() {
+        let rules = Rules {
+            hit_again: true,
+            ..Rules::classic()
+        };
+        let results = run(&names("hunt", "hunt"), &rules, 10, 3).unwrap();
+        assert!(results.iter().any(|r| r.shots[0].abs_diff(r.shots[1]) > 1));
+    }
+
+    #[test]
+    fn statistics() {
+        assert_eq!(median(&[]), 0.0);
+        assert_eq!(median(&[5, 1, 3]), 3.0);
+        assert_eq!(median(&[4, 1, 3, 2]), 2.5);
+
+        let game = |winner, shots| GameResult {
+            seed: 0,
+            first: 0,
+            winner,
+            shots,
+            duration: Duration::from_millis(2),
+        };
+        let results = [game(0, [40, 39]), game(0, [50, 49]), game(1, [44, 45])];
+        let summary = summarize(&names("a", "b"), &results);
+        assert_eq!(summary.sides[0].wins, 2);
+        assert_eq!(summary.sides[0].mean_shots, 45.0);
+        assert_eq!(summary.sides[1].distribution.get(&45), Some(&1));
+        assert_eq!(summary.mean_time, Duration::from_millis(2));
+        assert_eq!(to_csv(&results).lines().count(), 4);
+        assert!(summary.to_json(&results).contains("\"wins\": 2"));
+    }
+}
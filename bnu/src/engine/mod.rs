@@ -0,0 +1,142 @@
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+pub mod arena;
+pub mod rules;
+pub mod strategy;
+
+// The board model of a battle: tiles, ships and what a shot does to them.
+// Tiles are indices into the 10x10 board, row by row.
+
+pub const BOARD_SIZE: usize = 10;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TileState {
+    Empty,
+    ShotAt,
+    ShotAndHit,
+    HasShip,
+}
+
+#[derive(Clone, Debug)]
+pub struct Ship {
+    // indices of all tiles this ship occupies, bow first
+    pub tiles: Vec<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Playfield {
+    pub tiles: [TileState; BOARD_SIZE * BOARD_SIZE],
+    pub ships: Vec<Ship>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ShotResult {
+    Miss,
+    Hit,
+    // carries the tiles of the ship that went down
+    Sunk(Vec<usize>),
+}
+
+impl Playfield {
+    pub fn new() -> Self {
+        Playfield {
+            tiles: [TileState::Empty; BOARD_SIZE * BOARD_SIZE],
+            ships: Vec::new(),
+        }
+    }
+
+    pub fn ship_at(&self, index: usize) -> Option<&Ship> {
+        self.ships.iter().find(|s| s.tiles.contains(&index))
+    }
+
+    pub fn is_sunk(&self, ship: &Ship) -> bool {
+        ship.tiles.iter().all(|t| self.tiles[*t] == TileState::ShotAndHit)
+    }
+
+    pub fn was_shot_at(&self, index: usize) -> bool {
+        matches!(self.tiles[index], TileState::ShotAt | TileState::ShotAndHit)
+    }
+
+    // Every ship is sunk, an empty field counts as destroyed as well
+    pub fn is_destroyed(&self) -> bool {
+        self.ships.iter().all(|s| self.is_sunk(s))
+    }
+
+    pub fn fire_at(&mut self, index: usize) -> ShotResult {
+        if self.tiles[index] != TileState::HasShip {
+            if self.tiles[index] == TileState::Empty {
+                self.tiles[index] = TileState::ShotAt;
+            }
+            return ShotResult::Miss;
+        }
+
+        self.tiles[index] = TileState::ShotAndHit;
+        if let Some(ship) = self.ship_at(index) {
+            if self.is_sunk(ship) {
+                return ShotResult::Sunk(ship.tiles.clone());
+            }
+        }
+        ShotResult::Hit
+    }
+
+    // Places ships of the given sizes at random, without overlaps. The sizes
+    // must have passed `Rules::validate`, or this may never find room.
+    pub fn distribute_ships(&mut self, ship_sizes: &[usize], rng: &mut ChaCha8Rng) {
+        for s in ship_sizes.iter().copied() {
+            loop {
+                let x = rng.gen_range(0..BOARD_SIZE);
+                let y = rng.gen_range(0..BOARD_SIZE);
+                let horizontal = rng.gen_bool(0.5);
+                // one past the last tile of the ship
+                let end = if horizontal { x + s } else { y + s };
+                if end > BOARD_SIZE {
+                    continue;
+                }
+
+                let step = if horizontal { 1 } else { BOARD_SIZE };
+                let start = x + y * BOARD_SIZE;
+                let tiles: Vec<usize> = (0..s).map(|i| start + i * step).collect();
+
+                // Ships must not overlap, otherwise we can't tell which one sank
+                if tiles.iter().any(|t| self.tiles[*t] != TileState::Empty) {
+                    continue;
+                }
+
+                for index in tiles.iter() {
+                    self.tiles[*index] = TileState::HasShip;
+                }
+                self.ships.push(Ship { tiles });
+                break;
+            }
+        }
+    }
+}
+
+impl Default for Playfield {
+    fn default() -> Self {
+        Playfield::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn ships_sink_when_every_tile_is_hit() {
+        let mut field = Playfield::new();
+        field.distribute_ships(&[2], &mut ChaCha8Rng::seed_from_u64(3));
+        let ship = field.ships[0].tiles.clone();
+        let water = (0..100).find(|i| !ship.contains(i)).unwrap();
+        assert_eq!(field.fire_at(water), ShotResult::Miss);
+        assert_eq!(field.fire_at(ship[0]), ShotResult::Hit);
+        assert!(!field.is_destroyed());
+        assert_eq!(field.fire_at(ship[1]), ShotResult::Sunk(ship.clone()));
+        assert!(field.is_destroyed());
+        // hitting a wreck again is a miss
+        assert_eq!(field.fire_at(ship[1]), ShotResult::Miss);
+    }
+}
@@ -0,0 +1,61 @@
+use super::BOARD_SIZE;
+
+// The parameters a battle is played with. The board is always square,
+// only 10x10 boards are supported by the playfield for now.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Rules {
+    pub board_size: usize,
+    pub ship_sizes: Vec<usize>,
+    // a hit earns another shot
+    pub hit_again: bool,
+}
+
+impl Rules {
+    pub fn classic() -> Self {
+        Rules {
+            board_size: 10,
+            ship_sizes: vec![2, 3, 3, 4, 5],
+            hit_again: false,
+        }
+    }
+
+    // Rules from a file or the command line are checked before a fleet is
+    // placed with them
+    pub fn validate(&self) -> Result<(), String> {
+        if self.board_size != BOARD_SIZE {
+            return Err(format!("Board size {} is not supported, expected {}", self.board_size, BOARD_SIZE));
+        }
+        if self.ship_sizes.is_empty() {
+            return Err("There are no ships".to_string());
+        }
+        if let Some(s) = self.ship_sizes.iter().find(|s| **s == 0 || **s > BOARD_SIZE) {
+            return Err(format!("Ship size {} doesn't fit the board", s));
+        }
+        // random placement needs room to spare
+        if self.ship_sizes.iter().sum::<usize>() > BOARD_SIZE * BOARD_SIZE / 2 {
+            return Err("The ships take up more than half of the board".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_fleets_that_fit_are_valid() {
+        assert!(Rules::classic().validate().is_ok());
+        let with = |board_size, ship_sizes: &[usize]| Rules {
+            board_size,
+            ship_sizes: ship_sizes.to_vec(),
+            hit_again: false,
+        };
+        assert!(with(10, &[10, 1]).validate().is_ok());
+        assert!(with(12, &[2, 3]).validate().is_err());
+        assert!(with(10, &[]).validate().is_err());
+        assert!(with(10, &[0, 3]).validate().is_err());
+        assert!(with(10, &[11]).validate().is_err());
+        assert!(with(10, &[10; 6]).validate().is_err());
+    }
+}
@@ -0,0 +1,237 @@
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+use super::{Playfield, ShotResult, TileState, BOARD_SIZE};
+
+// How an AI picks its targets. The battle and the arena both drive
+// strategies the same way: `choose` a tile, fire at it, then `observe` the
+// result. A strategy gets the whole target field, honest ones only look at
+// the tiles that were shot at.
+pub trait Strategy {
+    fn name(&self) -> &'static str;
+    // a tile of `target` that wasn't shot at yet
+    fn choose(&mut self, target: &Playfield, rng: &mut ChaCha8Rng) -> usize;
+    fn observe(&mut self, index: usize, result: &ShotResult);
+}
+
+pub const STRATEGY_NAMES: [&str; 4] = ["unicorn", "random", "hunt", "parity"];
+
+pub fn by_name(name: &str) -> Result<Box<dyn Strategy>, String> {
+    match name {
+        "unicorn" => Ok(Box::new(Unicorn::default())),
+        "random" => Ok(Box::new(RandomShots)),
+        "hunt" => Ok(Box::new(Hunter::new(false))),
+        "parity" => Ok(Box::new(Hunter::new(true))),
+        _ => Err(format!(
+            "Unknown strategy '{}', known are {}",
+            name,
+            STRATEGY_NAMES.join(", ")
+        )),
+    }
+}
+
+fn unshot_tiles(target: &Playfield) -> Vec<usize> {
+    (0..target.tiles.len()).filter(|i| !target.was_shot_at(*i)).collect()
+}
+
+fn pick(tiles: &[usize], rng: &mut ChaCha8Rng) -> usize {
+    tiles[rng.gen_range(0..tiles.len())]
+}
+
+// The tiles left, right, above and below, as far as they are on the board
+fn neighbours(index: usize) -> Vec<usize> {
+    let (x, y) = (index % BOARD_SIZE, index / BOARD_SIZE);
+    let mut result = Vec::new();
+    if x > 0 {
+        result.push(index - 1);
+    }
+    if x + 1 < BOARD_SIZE {
+        result.push(index + 1);
+    }
+    if y > 0 {
+        result.push(index - BOARD_SIZE);
+    }
+    if y + 1 < BOARD_SIZE {
+        result.push(index + BOARD_SIZE);
+    }
+    result
+}
+
+// The unicorn's own logic: shoot around the last hit, otherwise at random.
+// Half of its random shots it peeks at the board and goes straight for a ship.
+#[derive(Clone, Default)]
+pub struct Unicorn {
+    pub last_hit: Option<usize>,
+}
+
+impl Unicorn {
+    // the first free tile in the row, then the column, of the last hit
+    fn next_to_hit(&self, target: &Playfield, hit: usize) -> Option<usize> {
+        let (hx, hy) = (hit % BOARD_SIZE, hit / BOARD_SIZE);
+        let row = (hx.saturating_sub(1)..=(hx + 1).min(BOARD_SIZE - 1)).map(|x| x + hy * BOARD_SIZE);
+        let column = (hy.saturating_sub(1)..=(hy + 1).min(BOARD_SIZE - 1)).map(|y| hx + y * BOARD_SIZE);
+        row.chain(column).find(|i| !target.was_shot_at(*i))
+    }
+
+    fn random_shot(&self, target: &Playfield, rng: &mut ChaCha8Rng) -> usize {
+        if rng.gen_bool(0.5) {
+            if let Some(i) = (0..target.tiles.len()).find(|i| target.tiles[*i] == TileState::HasShip) {
+                return i;
+            }
+        }
+        loop {
+            let index = rng.gen_range(0..100) as usize;
+            if !target.was_shot_at(index) {
+                return index;
+            }
+        }
+    }
+}
+
+impl Strategy for Unicorn {
+    fn name(&self) -> &'static str {
+        "unicorn"
+    }
+
+    fn choose(&mut self, target: &Playfield, rng: &mut ChaCha8Rng) -> usize {
+        self.last_hit
+            .and_then(|hit| self.next_to_hit(target, hit))
+            .unwrap_or_else(|| self.random_shot(target, rng))
+    }
+
+    fn observe(&mut self, index: usize, result: &ShotResult) {
+        match result {
+            ShotResult::Miss => {}
+            ShotResult::Hit => self.last_hit = Some(index),
+            // nothing left to hunt around the last hit
+            ShotResult::Sunk(_) => self.last_hit = None,
+        }
+    }
+}
+
+// Any tile not shot at yet, the baseline every strategy should beat
+pub struct RandomShots;
+
+impl Strategy for RandomShots {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn choose(&mut self, target: &Playfield, rng: &mut ChaCha8Rng) -> usize {
+        pick(&unshot_tiles(target), rng)
+    }
+
+    fn observe(&mut self, _index: usize, _result: &ShotResult) {}
+}
+
+// Hunt and target without peeking: random shots until something is hit,
+// then the tiles around every hit that hasn't sunk yet, following the line
+// once two hits are next to each other. With `parity` it hunts on a
+// checkerboard only, which still finds every ship of size two or more.
+pub struct Hunter {
+    parity: bool,
+    open_hits: Vec<usize>,
+}
+
+impl Hunter {
+    pub fn new(parity: bool) -> Self {
+        Hunter {
+            parity,
+            open_hits: Vec::new(),
+        }
+    }
+
+    fn target(&self, target: &Playfield) -> Option<usize> {
+        let mut candidates = Vec::new();
+        for hit in self.open_hits.iter().copied() {
+            for next in neighbours(hit) {
+                if target.was_shot_at(next) {
+                    continue;
+                }
+                // the hit on the opposite side makes this the line's end
+                let behind = (2 * hit).checked_sub(next);
+                let in_line = behind.is_some_and(|b| neighbours(hit).contains(&b) && self.open_hits.contains(&b));
+                if in_line {
+                    return Some(next);
+                }
+                candidates.push(next);
+            }
+        }
+        candidates.first().copied()
+    }
+
+    fn hunt(&self, target: &Playfield, rng: &mut ChaCha8Rng) -> usize {
+        let free = unshot_tiles(target);
+        if self.parity {
+            let even: Vec<usize> = free
+                .iter()
+                .copied()
+                .filter(|i| (i % BOARD_SIZE + i / BOARD_SIZE).is_multiple_of(2))
+                .collect();
+            if !even.is_empty() {
+                return pick(&even, rng);
+            }
+        }
+        pick(&free, rng)
+    }
+}
+
+impl Strategy for Hunter {
+    fn name(&self) -> &'static str {
+        if self.parity {
+            "parity"
+        } else {
+            "hunt"
+        }
+    }
+
+    fn choose(&mut self, target: &Playfield, rng: &mut ChaCha8Rng) -> usize {
+        self.target(target).unwrap_or_else(|| self.hunt(target, rng))
+    }
+
+    fn observe(&mut self, index: usize, result: &ShotResult) {
+        match result {
+            ShotResult::Miss => {}
+            ShotResult::Hit => self.open_hits.push(index),
+            ShotResult::Sunk(tiles) => self.open_hits.retain(|t| !tiles.contains(t)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn every_strategy_sinks_a_fleet_without_repeating_itself() {
+        for name in STRATEGY_NAMES.iter() {
+            let mut strategy = by_name(name).unwrap();
+            let mut rng = ChaCha8Rng::seed_from_u64(5);
+            let mut field = Playfield::new();
+            field.distribute_ships(&[2, 3, 3, 4, 5], &mut rng);
+            let mut shots = 0;
+            while !field.is_destroyed() {
+                let index = strategy.choose(&field, &mut rng);
+                assert!(!field.was_shot_at(index), "{} shot at {} twice", name, index);
+                let result = field.fire_at(index);
+                strategy.observe(index, &result);
+                shots += 1;
+            }
+            assert!(shots <= 100);
+        }
+        assert!(by_name("psychic").is_err());
+    }
+
+    #[test]
+    fn hunter_follows_the_line_of_its_hits() {
+        let mut hunter = Hunter::new(false);
+        let field = Playfield::new();
+        hunter.observe(44, &ShotResult::Hit);
+        hunter.observe(45, &ShotResult::Hit);
+        // 43 and 46 extend the line, 34 or 54 don't
+        let next = hunter.target(&field).unwrap();
+        assert!(next == 43 || next == 46);
+    }
+}
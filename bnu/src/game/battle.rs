@@ -1,8 +1,11 @@
 use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
 
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
 
 
+use sdl2::keyboard::Keycode;
 use sdl2::rect::Rect;
 use sdl2::render::{Texture, BlendMode};
 
@@ -10,10 +13,27 @@ use sdl2::{rect::Point, render::Canvas, video::Window};
 
 use std::time::Instant;
 
+use crate::anim::{Animation, Effect};
+use crate::audio::{Cue, Sfx, Track};
 use crate::draw;
+use crate::engine::strategy::{self, Strategy, Unicorn, STRATEGY_NAMES};
+use crate::engine::{Playfield, Ship, ShotResult, TileState};
+use crate::net::commit::{self, Verdict};
+use crate::net::{Connection, Message, Outcome};
+use crate::particles::{EmitterConfig, ParticleSystem};
+use crate::scene::{Input, Scene, Transition};
 use crate::vecmath::Vec2d;
 
-use super::GameState;
+use super::bot::{self, Bot};
+use super::campaign::{self, BOSSES};
+use super::overworld::{Overworld, Squadron, Vessel};
+use super::record::{Record, RecordedShot, ShotOutcome, RECORD_DIR};
+use super::rules::Rules;
+use super::title::Title;
+use super::outtro::Outtro;
+
+mod save;
+pub use save::{delete_save, save_exists, SAVE_PATH};
 
 const px:   i32 = 40;
 const aix:  i32 = 420;
@@ -44,6 +64,43 @@ const INSULTS_AFTER_BEING_HIT: [&str; 4] = [
     "I salute my fallen enemy!"
 ];
 
+// After UNi sank a player ship
+const INSULTS_AFTER_SINKING: [&str; 3] = [
+    "Glub glub glub. That was your ship.",
+    "Another one for the fishes",
+    "Say hello to Davy Jones for me"
+];
+
+// After UNi lost a ship
+const INSULTS_AFTER_LOSING_SHIP: [&str; 3] = [
+    "That one was a decoy anyway",
+    "You will pay for this!",
+    "I have more ships than you have brain cells"
+];
+
+// What an AI opponent says during a battle
+pub struct Taunts
+{
+    // if it doesn't know anything else to say
+    pub random: &'static [&'static str],
+    pub after_hit: &'static [&'static str],
+    pub after_being_hit: &'static [&'static str],
+    pub after_sinking: &'static [&'static str],
+    pub after_losing_ship: &'static [&'static str],
+    pub victory: &'static str,
+    pub defeat: &'static str
+}
+
+const UNICORN_TAUNTS: Taunts = Taunts {
+    random: &RANDOM_INSULTS,
+    after_hit: &INSULTS_AFTER_HIT,
+    after_being_hit: &INSULTS_AFTER_BEING_HIT,
+    after_sinking: &INSULTS_AFTER_SINKING,
+    after_losing_ship: &INSULTS_AFTER_LOSING_SHIP,
+    victory: "I broke all of your toys. You cryin' now?.",
+    defeat: "You definitely cheated."
+};
+
 #[derive(PartialEq, Clone, Copy)]
 enum TurnEvents
 {
@@ -55,33 +112,86 @@ enum TurnEvents
     PlayerLostShip
 }
 
+// Who makes the decisions for one side of the battle
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Controller
+{
+    Human,
+    Unicorn,
+    // a player on another machine, see crate::net
+    Remote,
+    // an external program, see game::bot
+    Bot,
+    // one of the strategies in engine::strategy, for AI-vs-AI games
+    Computer
+}
+
+impl Controller
+{
+    fn name(&self) -> &'static str
+    {
+        match self
+        {
+            Controller::Human => "human",
+            Controller::Unicorn => "unicorn",
+            Controller::Remote => "remote",
+            Controller::Bot => "bot",
+            Controller::Computer => "computer",
+        }
+    }
+}
+
 #[derive(Clone, PartialEq)]
 enum State
 {
-    PlayerTurn,
-    WaitingForPlayerProjectile,
-    AITurn,
-    WaitingForAiProjectile,
-    UniLost,
-    PlayerLost
+    // The current side puts its ships on the board
+    Placing,
+    // Hot-seat only, hides both boards until the next player is ready
+    PassDevice,
+    // Network only, our fleet is placed but the remote one isn't yet
+    WaitingForOpponent,
+    // The current side picks a target
+    Aiming,
+    WaitingForProjectile,
+    // carries the side that won
+    Won(usize),
+    // Nobody plays, shots are fed in from a match record
+    Replay
 }
 
-#[derive(Copy, Clone, PartialEq)]
-pub enum TileState
+// Animation speed when AIs play each other, and in the title screen demo
+const SPECTATOR_SPEED: f32 = 2.0;
+const DEMO_SPEED: f32 = 4.0;
+
+// How long a finished network game waits for the opponent's fleet
+const REVEAL_TIMEOUT_SECS: u64 = 10;
+
+// Keeps the remote side honest, see net::commit. Each side commits to its
+// fleet before the first shot and reveals it when the game is over.
+#[derive(Clone, Default)]
+struct Commitments
 {
-    Empty,
-    ShotAt,
-    ShotAndHit,
-    HasShip
+    salt: String,
+    theirs: Option<String>,
+    revealed: Option<(String, Vec<Vec<usize>>)>,
+    // what the remote side answered to each of our shots
+    reported: Vec<(usize, Outcome)>,
+    verdict: Option<Verdict>,
+    peer_gone: bool
 }
 
-#[derive(Copy, Clone)]
-pub struct playfield
+// The tile a projectile is currently flying towards. Its overlay is not
+// drawn until the projectile has landed.
+#[derive(Clone, Copy, PartialEq)]
+struct InFlight
 {
-    pub tiles: [TileState; 10 * 10]
+    // the side whose field is shot at
+    side: usize,
+    index: usize,
+    hit: bool,
+    sunk: bool
 }
 
-#[derive(Clone)]
 pub struct Battle
 {
     turn_event: TurnEvents,
@@ -90,468 +200,1437 @@ pub struct Battle
     text: VecDeque<String>,
     has_click: bool,
     cursor_pos: Vec2d,
-    // the field storing the player's ships, the unicorn shoots at this field
-    player_field: playfield,
-    // the field storing the unicorns's ships, the player shoots at this field
-    unicorn_field: playfield,
-    // The last place, where the ai hit a ship
-    last_hit: Option<Vec2d>
+    // one field per side, each storing that side's ships. Side 0 starts.
+    fields: [Playfield; 2],
+    controllers: [Controller; 2],
+    // the side whose turn it is
+    current: usize,
+    // orientation of the next ship while placing the fleet
+    placing_horizontal: bool,
+    // The last place, where the ai of a side hit a ship
+    last_hit: [Option<Vec2d>; 2],
+    animation: Animation,
+    in_flight: Option<InFlight>,
+    particles: ParticleSystem,
+    last_tick: Instant,
+    // sounds to be played by the main loop, see take_cues
+    cues: Vec<Cue>,
+    // every random decision of the battle comes from this generator, so
+    // that seed and stream position are enough to restore it
+    seed: u64,
+    rng: ChaCha8Rng,
+    rules: Rules,
+    // written to record_path after every shot, replays don't have a path
+    record: Record,
+    record_path: Option<PathBuf>,
+    // Network games only
+    network: Option<Connection>,
+    // fire and result messages waiting for their turn
+    inbox: VecDeque<Message>,
+    // a shot sent to the remote side, waiting for its result
+    pending_shot: Option<usize>,
+    opponent_ready: bool,
+    // the chat line being typed, if any
+    chat_input: Option<String>,
+    commitments: Commitments,
+    // the external bots and strategies playing the Bot and Computer sides
+    bots: [Option<Bot>; 2],
+    strategies: [Option<Box<dyn Strategy>>; 2],
+    // the attract mode of the title screen, any input ends it
+    demo: bool,
+    // index into campaign::BOSSES when fighting one
+    boss: Option<usize>,
+    // the sea expedition to return to once this battle is over
+    voyage: Option<Box<Overworld>>
 }
 
 impl Battle {
-    pub fn new() -> Self {        
+    pub fn new() -> Self {
+        Battle::with_seed(rand::thread_rng().gen())
+    }
+
+    // Two players taking turns on the same machine
+    pub fn hot_seat() -> Self {
+        Battle::with_controllers(rand::thread_rng().gen(), [Controller::Human, Controller::Human])
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Battle::with_controllers(seed, [Controller::Human, Controller::Unicorn])
+    }
+
+    pub fn with_controllers(seed: u64, controllers: [Controller; 2]) -> Self {
+        Battle::with_rules(seed, controllers, Rules::classic())
+    }
+
+    pub fn with_rules(seed: u64, controllers: [Controller; 2], rules: Rules) -> Self {
+        let hot_seat = controllers.iter().all(|c| *c == Controller::Human);
+        let players = if hot_seat
+        {
+            ["player 1".to_string(), "player 2".to_string()]
+        }
+        else
+        {
+            [controllers[0].name().to_string(), controllers[1].name().to_string()]
+        };
+        let record = Record::new(rules.clone(), seed, players);
+        let record_path = Some(Path::new(RECORD_DIR).join(record.file_name()));
         let mut b = Self {   
             turn_event: TurnEvents::NoEvent,
             time_state_entered: Instant::now(),   
-            state: State::PlayerTurn,      
+            state: State::Aiming,      
             text: VecDeque::new(),
             has_click: false,
             cursor_pos: Vec2d { x: 0.0, y: 0.0 },
-            player_field: playfield { tiles: [TileState::Empty; 10 * 10] },
-            unicorn_field: playfield { tiles: [TileState::Empty; 10 * 10] },
-            last_hit: None
+            fields: [Playfield::new(), Playfield::new()],
+            controllers,
+            current: 0,
+            placing_horizontal: true,
+            last_hit: [None, None],
+            animation: Animation::new(),
+            in_flight: None,
+            particles: ParticleSystem::new(),
+            last_tick: Instant::now(),
+            cues: Vec::new(),
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            rules,
+            record,
+            record_path,
+            network: None,
+            inbox: VecDeque::new(),
+            pending_shot: None,
+            opponent_ready: false,
+            chat_input: None,
+            commitments: Commitments::default(),
+            bots: [None, None],
+            strategies: [None, None],
+            demo: false,
+            boss: None,
+            voyage: None
         };
 
-        Battle::distribute_ships(&mut b.player_field);
-        Battle::distribute_ships(&mut b.unicorn_field);
+        if hot_seat
+        {
+            // Both players place their own fleets, the first one starts
+            b.state = State::PassDevice;
+        }
+        else
+        {
+            for field in b.fields.iter_mut()
+            {
+                field.distribute_ships(&b.rules.ship_sizes, &mut b.rng);
+            }
+            b.record.fleets = [Battle::fleet_of(&b.fields[0]), Battle::fleet_of(&b.fields[1])];
+        }
 
         b
     }
 
-    fn vec2index(v: &Vec2d) -> usize
-    {
-        (v.x as i32 + v.y as i32 * 10) as usize
+    // A game against another machine. The host is side 0 and starts.
+    pub fn networked(connection: Connection, local: usize, opponent: &str) -> Self {
+        let mut controllers = [Controller::Remote, Controller::Remote];
+        controllers[local] = Controller::Human;
+        let mut b = Battle::with_controllers(rand::thread_rng().gen(), controllers);
+        b.fields = [Playfield::new(), Playfield::new()];
+        b.record.players[1 - local] = opponent.to_string();
+        b.network = Some(connection);
+        b.current = local;
+        b.state = State::Placing;
+        b.notify(&format!("Connected to {}. Return chats, F10 resigns.", opponent));
+        b
     }
 
-    fn index2vec(i: usize) -> Vec2d
-    {
-        Vec2d::from_ints((i % 10) as i32, (i / 10) as i32)
+    // A game against an external bot, which places its own fleet
+    pub fn against_bot(command: &str) -> Result<Self, String> {
+        let mut b = Battle::with_controllers(rand::thread_rng().gen(), [Controller::Human, Controller::Bot]);
+        b.seat_bot(1, command)?;
+        b.notify(&format!("{} is ready for battle.", b.record.players[1]));
+        Ok(b)
     }
 
-    fn distribute_ships(field: &mut playfield)
-    {
-        let ship_sizes = vec![2, 3, 3, 4, 5];
-        for s in ship_sizes
+    // Two AIs playing each other with both fleets in view. A player is the
+    // name of a strategy or "bot:" followed by the command of a bot.
+    pub fn spectate(players: [&str; 2], seed: u64) -> Result<Self, String> {
+        let mut b = Battle::with_controllers(seed, [Controller::Computer, Controller::Computer]);
+        for (side, player) in players.iter().enumerate()
         {
-            loop 
+            match player.strip_prefix("bot:")
             {
-                let x = rand::thread_rng().gen_range(0..10) as usize;
-                let y = rand::thread_rng().gen_range(0..10) as usize;
-                let dir = rand::thread_rng().gen_bool(0.5);
-                let step;
-                if dir 
-                {
-                    step = Vec2d::from_ints(1, 0);
-                }
-                else {
-                    step = Vec2d::from_ints(0, 1);
-                }
-
-                let len = step * s as f32;
-                let start = Vec2d::new(x as f32, y as f32);
-                let end = start + len;
-                if end.x > 9.0 || end.y > 9.0
-                {
-                    continue;
-                }
-
-                for i in 0..s
-                {
-                    let cur_pos = start + (step * i as f32);
-                    let index = Battle::vec2index(&cur_pos);
-                    field.tiles[index] = TileState::HasShip;
-                }
-                break;
+                Some(command) => {
+                    b.controllers[side] = Controller::Bot;
+                    b.seat_bot(side, command)?;
+                },
+                None => b.seat_strategy(side, strategy::by_name(player)?),
             }
-
         }
-
+        b.animation.set_speed(SPECTATOR_SPEED);
+        Ok(b)
     }
 
-    fn draw_overlay(sdl_context: &mut Canvas<Window>, overlay_raster_pos: Vec2d, source_tiles: &playfield, overlay_pixel_pos: Vec2d)
-    {
-        let green= sdl2::pixels::Color {
-            r: 0,
-            g: 128,
-            b: 0,
-            a: 128,
-        };
-
-        let red= sdl2::pixels::Color {
-            r: 128,
-            g: 0,
-            b: 0,
-            a: 128,
-        };
-
-        let grey = sdl2::pixels::Color {
-            r: 128,
-            g: 128,
-            b: 128,
-            a: 128,
-        };
-
-        let field_index = (overlay_raster_pos.x as i32 + overlay_raster_pos.y as i32 * 10) as usize;
-        let used_color;
-        match source_tiles.tiles[field_index]
+    // The idle demo of the title screen: two random strategies, faster and
+    // without a match record
+    pub fn attract() -> Self {
+        let mut b = Battle::with_controllers(rand::thread_rng().gen(), [Controller::Computer, Controller::Computer]);
+        for side in 0..2
         {
-            TileState::Empty => return,
-            TileState::ShotAt => used_color = green,
-            TileState::ShotAndHit => used_color = red,
-            TileState::HasShip => used_color = grey,
+            let name = STRATEGY_NAMES[b.rng.gen_range(0..STRATEGY_NAMES.len())];
+            if let Ok(s) = strategy::by_name(name)
+            {
+                b.seat_strategy(side, s);
+            }
         }
-
-        sdl_context.set_blend_mode(BlendMode::Add);
-        sdl_context.set_draw_color(used_color);
-        let _ = sdl_context.fill_rect(Rect::new(overlay_pixel_pos.x as i32, overlay_pixel_pos.y as i32, 32, 32));
-
-    }
-
-    pub fn render(&self, sdl_context: &mut Canvas<Window>, resources: &HashMap<String, Texture>) {
-        let _ = draw::draw_text(
-            sdl_context,
-            "A raging battle against a unicorn",
-            16,
-            Point::new(10, 10),
-            sdl2::pixels::Color {
-                r: 255,
-                g: 255,
-                b: 255,
-                a: 255,
-            },
-        );
-
-        let uni = resources.get("unicorn").unwrap();
-        let water = resources.get("water").unwrap();
-
-        let _ = sdl_context.copy(uni, None, Rect::new(40, 400, 150, 150));
-        let white = sdl2::pixels::Color {
-            r: 255,
-            g: 255,
-            b: 255,
-            a: 255,
-        };
-
-        let dialogbox_corner = Vec2d::new(200.0, 400.0);
-        let cursor_raster_pos = ((self.cursor_pos.clone() - Vec2d::new(aix as f32, offy as f32)) / 32.0).truncate();
-        // Draw 2 playfields, one for the player's ships and one for the targets:
-        self.draw_playfields(sdl_context, water);
-        self.draw_cursor(sdl_context, cursor_raster_pos);        
-        self.draw_dialog_box(dialogbox_corner, sdl_context, white);
-
+        b.record_path = None;
+        b.demo = true;
+        b.animation.set_speed(DEMO_SPEED);
+        b.notify("Press any key");
+        b
     }
 
-    fn draw_playfields(&self, sdl_context: &mut Canvas<Window>, water: &Texture) {        
-        sdl_context.set_blend_mode(BlendMode::None);
-        for y in 0..=9
+    // A campaign fight, the boss brings its own rules and strategy
+    pub fn against_boss(index: usize) -> Self {
+        let boss = &BOSSES[index];
+        let mut b = Battle::with_rules(rand::thread_rng().gen(), [Controller::Human, Controller::Unicorn], boss.rules());
+        if let Ok(s) = strategy::by_name(boss.strategy)
         {
-            for x in 0..=9
-            {                
-                let _ = sdl_context.copy(water, None, Rect::new(px + x * 32, offy + y * 32, 32, 32));                
-                let _ = sdl_context.copy(water, None, Rect::new(aix + x * 32, offy + y * 32, 32, 32));
-
-                // Draw overlay for player and unicorn:
-                let raster_pos = Vec2d::from_ints(x,y);
-                let pixel_pos_player = Vec2d::from_ints(px + x * 32, offy + y * 32);
-                let pixel_pos_uni = Vec2d::from_ints(aix + x * 32, offy + y * 32);
-
-                Battle::draw_overlay(sdl_context, raster_pos, &self.player_field, pixel_pos_player);
-                Battle::draw_overlay(sdl_context, raster_pos, &self.unicorn_field, pixel_pos_uni);                            
-            }            
+            b.strategies[1] = Some(s);
         }
-    }
-
-    fn draw_dialog_box(&self, dialogbox_corner: Vec2d, sdl_context: &mut Canvas<Window>, white: sdl2::pixels::Color) {
-        let _ = draw::draw_rect(sdl_context, &dialogbox_corner, 580, 150, white, false);
-        let mut org = dialogbox_corner.clone();
-        for t in self.text.iter()
+        b.record.players[1] = boss.name.to_string();
+        b.boss = Some(index);
+        if boss.opens
         {
-            let _= draw::draw_text(sdl_context, t, 16, org.to_point(), white);
-            org = org + Vec2d::new(0.0, 24.0);
+            b.current = 1;
         }
+        b.notify(boss.greeting);
+        b
     }
 
-    fn draw_cursor(&self, sdl_context: &mut Canvas<Window>, cursor_raster_pos: Vec2d) {
-        if self.state == State::PlayerTurn
+    // A squadron met on the sea expedition. The player sails in with the
+    // ships that are left, the hits they took earlier are still there.
+    pub fn at_sea(voyage: Overworld, enemy: Squadron) -> Self {
+        let rules = Rules {
+            ship_sizes: enemy.ship_sizes.to_vec(),
+            ..Rules::classic()
+        };
+        let mut b = Battle::with_rules(rand::thread_rng().gen(), [Controller::Human, Controller::Unicorn], rules);
+        let fleet: Vec<Vessel> = voyage.fleet().iter().copied().filter(|v| !v.is_sunk()).collect();
+        let sizes: Vec<usize> = fleet.iter().map(|v| v.size).collect();
+        b.fields[0] = Playfield::new();
+        b.fields[0].distribute_ships(&sizes, &mut b.rng);
+        for (ship, vessel) in b.fields[0].ships.iter().zip(fleet.iter())
         {
-            sdl_context.set_blend_mode(BlendMode::Add);
-            sdl_context.set_draw_color(sdl2::pixels::Color::RGBA(128, 128,128,128));
-            let _ = sdl_context.fill_rect(Rect::new(aix + cursor_raster_pos.x as i32 * 32, offy + cursor_raster_pos.y as i32 * 32, 32, 32));
+            for t in ship.tiles.iter().take(vessel.hits)
+            {
+                b.fields[0].tiles[*t] = TileState::ShotAndHit;
+            }
         }
+        b.record.fleets[0] = Battle::fleet_of(&b.fields[0]);
+        // the earlier damage isn't in the record, a replay couldn't end
+        b.record_path = None;
+        if let Ok(s) = strategy::by_name(enemy.strategy)
+        {
+            b.strategies[1] = Some(s);
+        }
+        b.record.players[1] = enemy.name.to_string();
+        b.voyage = Some(Box::new(voyage));
+        b.notify(&format!("A {} blocks your way!", enemy.name.to_lowercase()));
+        b
     }
 
-    fn count_alive_fields(&self, field: &playfield) -> usize
+    // The player's fleet as it leaves the battle
+    fn damage(&self) -> Vec<Vessel>
     {
-        field.tiles.iter().fold(0, |acc, x| {
-            if *x == TileState::HasShip
-            {
-                return acc + 1
-            }
-            acc
-        })
+        let field = &self.fields[0];
+        field.ships.iter()
+            .map(|s| Vessel {
+                size: s.tiles.len(),
+                hits: s.tiles.iter().filter(|t| field.tiles[**t] == TileState::ShotAndHit).count()
+            })
+            .collect()
     }
 
-    pub fn tick(&mut self) -> GameState
+    fn taunts(&self) -> &'static Taunts
     {
-        self.cull_texts();
-        let s = self.state.clone();
-        match s
+        match self.boss
         {
-            State::PlayerTurn => {
-                if self.player_turn()
-                {
-                    self.state = State::WaitingForPlayerProjectile;
-                    self.time_state_entered = Instant::now();
-                }
-            },
-            State::WaitingForPlayerProjectile => 
-            {
-                if self.waiting_for_player_projectile()
-                {
-                    // check if player won:
-                    if self.count_alive_fields(&self.unicorn_field) <= 0
-                    {
-                        self.state = State::UniLost;
-                        self.text.push_back("You definitely cheated.".to_string());
-                    }
-                    else
-                    {
-                        self.state = State::AITurn;
-                        if self.turn_event == TurnEvents::NoEvent
-                        {
-                            self.turn_event = TurnEvents::RandomTaunt;
-                        }
-                    }
-                    self.time_state_entered = Instant::now();
-                }
-            },
-            State::AITurn =>             {
-                if self.aiturn()
-                {
-                    self.state = State::WaitingForAiProjectile;
-                    self.time_state_entered = Instant::now();
-                }
-            },
-            State::WaitingForAiProjectile =>             {
-                if self.waiting_for_ai_projectile()
-                {
-                    if self.count_alive_fields(&self.unicorn_field) <= 0
-                    {
-                        self.state = State::PlayerLost;
-                        self.text.push_back("I broke all of your toys. You cryin' now?.".to_string());
-                    }
-                    else
-                    {
-                        self.state = State::PlayerTurn;                     
-                        self.has_click = false;
-                    }
-                    self.time_state_entered = Instant::now();
-                }
-            },
-            State::UniLost => {
-                if self.time_state_entered.elapsed().as_secs() >= 1
-                {
-                    return GameState::Outtro{playerLost: false};
-                }
-            },
-            State::PlayerLost => 
-            {
-                if self.time_state_entered.elapsed().as_secs() >= 1
-                {
-                    return GameState::Outtro{playerLost: true};
-                }
-            },
+            Some(index) => &BOSSES[index].taunts,
+            None => &UNICORN_TAUNTS,
         }
-        GameState::Battle(self.clone())
     }
 
-    fn player_turn(&mut self) -> bool
+    fn seat_bot(&mut self, side: usize, command: &str) -> Result<(), String>
     {
-        if self.has_click
-        {
-            let cursor_raster_pos = ((self.cursor_pos.clone() - Vec2d::new(aix as f32, offy as f32)) / 32.0).truncate();    
-            let field_index = (cursor_raster_pos.x as i32 + cursor_raster_pos.y as i32 * 10) as usize;
+        let mut bot = Bot::launch(command.trim(), &self.rules, bot::DEFAULT_TIMEOUT)?;
+        let fleet = bot.place_fleet(&self.rules)?;
+        self.fields[side] = Battle::field_from_fleet(&fleet);
+        self.record.fleets[side] = fleet;
+        self.record.players[side] = bot.name().to_string();
+        self.bots[side] = Some(bot);
+        Ok(())
+    }
 
-            let next_state = match self.unicorn_field.tiles[field_index]
-            {
-                TileState::Empty => TileState::ShotAt,
-                TileState::ShotAt => TileState::ShotAt,
-                TileState::ShotAndHit => TileState::ShotAndHit,
-                TileState::HasShip => {
-                    self.turn_event = TurnEvents::UniWasHit;
-                    TileState::ShotAndHit
-                }
-            };
-            
-            self.unicorn_field.tiles[field_index] = next_state;
-        }
-        self.has_click
+    fn seat_strategy(&mut self, side: usize, strategy: Box<dyn Strategy>)
+    {
+        self.record.players[side] = strategy.name().to_string();
+        self.strategies[side] = Some(strategy);
     }
 
-    fn waiting_for_player_projectile(&mut self) -> bool {
-        true
+    // Nobody at this machine plays, both fleets can be shown
+    fn is_spectated(&self) -> bool
+    {
+        self.controllers.iter().all(|c| matches!(c, Controller::Computer | Controller::Bot))
     }
 
-    fn select_taunt(&mut self, list: &[&str])
+    // Games with another program or machine can't be restored from a file
+    pub fn can_save(&self) -> bool
     {
-        let index = rand::thread_rng().gen_range(0..list.len()) as usize;
-        self.text.push_back(list[index].to_string());
-        self.turn_event = TurnEvents::NoEvent;
+        self.network.is_none() && self.boss.is_none() && self.voyage.is_none() && !self.controllers.iter().any(|c| matches!(c, Controller::Bot | Controller::Computer))
     }
 
-    fn aiturn(&mut self) -> bool {
+    pub fn is_networked(&self) -> bool
+    {
+        self.network.is_some()
+    }
 
-        // Select insult, based on gamestate
-        if self.turn_event != TurnEvents::NoEvent
+    fn send(&mut self, message: Message) -> Result<(), String>
+    {
+        match self.network.as_mut()
         {
-            self.cull_texts();
+            Some(connection) => connection.send(&message),
+            None => Ok(()),
         }
+    }
 
-        match self.turn_event{
-            TurnEvents::NoEvent => {},
-            TurnEvents::UniWasHit => self.select_taunt(&INSULTS_AFTER_BEING_HIT),
-            TurnEvents::PlayerWasHit => self.select_taunt(&INSULTS_AFTER_HIT),
-            TurnEvents::UniLostShip => todo!(),
-            TurnEvents::PlayerLostShip => todo!(),
-            TurnEvents::RandomTaunt => self.select_taunt(&RANDOM_INSULTS),
+    // Handles chat and resignations right away, everything else waits in
+    // the inbox until the state machine gets to it.
+    fn poll_network(&mut self) -> Result<(), String>
+    {
+        if self.commitments.peer_gone
+        {
+            return Ok(());
         }
-        
-
-        // Fire shot
-            // Select firing position:
-            // if no recent shot hit anything, select random location
-            // otherwise, try to hit close to the last shot
-
-        true
+        let messages = match self.network.as_mut()
+        {
+            Some(connection) => match connection.poll()
+            {
+                Ok(messages) => messages,
+                // after the game only the reveal is missing, see verify_opponent
+                Err(_) if self.is_over() => {
+                    self.commitments.peer_gone = true;
+                    return Ok(());
+                },
+                Err(e) => return Err(e),
+            },
+            None => return Ok(()),
+        };
+        for message in messages
+        {
+            self.receive(message);
+        }
+        Ok(())
     }
-    
-    fn can_shoot_at(&self, v: &Vec2d) -> bool
+
+    pub fn receive(&mut self, message: Message)
     {
-        let idx = Battle::vec2index(v);
-        if self.player_field.tiles[idx] != TileState::ShotAndHit &&
-        self.player_field.tiles[idx] != TileState::ShotAt
+        match message
         {
-            return true
+            Message::Chat(text) => self.notify(&format!("{}: {}", self.record.players[self.remote_side()], text)),
+            Message::Resign => {
+                if !self.is_over()
+                {
+                    let local = 1 - self.remote_side();
+                    self.notify("Your opponent resigned.");
+                    self.finish(local);
+                }
+            },
+            Message::Ready => self.opponent_ready = true,
+            Message::Commit(hash) => self.commitments.theirs = Some(hash),
+            Message::Reveal { salt, fleet } => self.commitments.revealed = Some((salt, fleet)),
+            Message::Hello { .. } => {},
+            m => self.inbox.push_back(m),
         }
-        false
     }
 
-    fn has_ship(&self, v: &Vec2d) -> bool
+    fn remote_side(&self) -> usize
     {
-        let idx = Battle::vec2index(v);
-        self.player_field.tiles[idx] == TileState::HasShip
+        self.controllers.iter().position(|c| *c == Controller::Remote).unwrap_or(1)
     }
 
-    fn select_shooting_pos(&self) -> Option<Vec2d>
+    // Gives up a network game, e.g. when the window is closed
+    pub fn resign(&mut self)
     {
-        let hit_pos = self.last_hit.clone().unwrap();
+        if self.is_networked() && !self.is_over()
+        {
+            let _ = self.send(Message::Resign);
+            self.notify("You resigned.");
+            let remote = self.remote_side();
+            self.finish(remote);
+        }
+    }
 
-        // check surroundings of hitpos for empty space and return
-        // that as next pos:
+    pub fn is_typing(&self) -> bool
+    {
+        self.chat_input.is_some()
+    }
 
-        // check row:
-        let check_start_row = hit_pos - Vec2d::new(1.0, 0.0);
-        for x in check_start_row.x as i32..=(check_start_row.x as i32 + 2)
+    fn finish(&mut self, winner: usize)
+    {
+        self.state = State::Won(winner);
+        self.record.winner = Some(winner);
+        self.write_record();
+        self.time_state_entered = Instant::now();
+        if let Some(index) = self.boss
         {
-            if x < 0 || x > 9
-            {
-                continue;
-            }
-            let possible_target =Vec2d::from_ints(x, hit_pos.y as i32);
-            if self.can_shoot_at(&possible_target)
+            if self.controllers[winner] == Controller::Human
             {
-                return Some(possible_target)
+                let line = campaign::boss_beaten(index);
+                self.notify(&line);
             }
         }
-
-        let check_start_col = hit_pos - Vec2d::new(0.0, 1.0);
-        for y in check_start_col.y as i32..=(check_start_col.y as i32 + 2)
+        for (side, bot) in self.bots.iter_mut().enumerate()
         {
-            if y < 0 || y > 9
-            {
-                continue;
-            }
-            let possible_target =Vec2d::from_ints(hit_pos.x as i32, y  );
-            if self.can_shoot_at(&possible_target)
+            if let Some(bot) = bot.as_mut()
             {
-                return Some(possible_target)
+                bot.tell_end(side == winner);
             }
         }
-        None
+        // nothing was committed if the game ended during placement
+        if self.is_networked() && !self.commitments.salt.is_empty()
+        {
+            let local = 1 - self.remote_side();
+            let reveal = Message::Reveal {
+                salt: self.commitments.salt.clone(),
+                fleet: Battle::fleet_of(&self.fields[local]),
+            };
+            let _ = self.send(reveal);
+        }
     }
 
-    fn select_random_shooting_pos(&mut self) -> usize
+    // Once the opponent revealed its fleet, checks every result it reported.
+    // Returns None while still waiting for the reveal.
+    fn verify_opponent(&mut self) -> Option<Verdict>
     {
-        // with a probability of 10% we'll allow the unicorn to
-        // cheat and just find a ship, if it has no clue where one
-        // cuold be.
-        let let_it_cheat  =rand::thread_rng().gen_bool(0.5);
-        if let_it_cheat
+        if self.commitments.verdict.is_none()
         {
-            for i in 0..100
+            let c = &self.commitments;
+            let waited_enough = c.peer_gone || self.time_state_entered.elapsed().as_secs() >= REVEAL_TIMEOUT_SECS;
+            let verdict = match (&c.theirs, &c.revealed)
             {
-                if self.has_ship(&Battle::index2vec(i))
+                (Some(hash), Some((salt, fleet))) => commit::verify(hash, salt, fleet, &self.rules.ship_sizes, &c.reported),
+                // resigned before the first shot, nothing to check
+                (None, _) if c.reported.is_empty() => Verdict::Honest,
+                (None, _) => Verdict::Disputed("the opponent never committed to a fleet".to_string()),
+                (Some(_), None) if waited_enough => Verdict::Disputed("the opponent never revealed its fleet".to_string()),
+                (Some(_), None) => return None,
+            };
+            if let Verdict::Disputed(reason) = &verdict
+            {
+                self.notify(&format!("Result disputed: {}", reason));
+            }
+            self.commitments.verdict = Some(verdict);
+        }
+        self.commitments.verdict.clone()
+    }
+
+    // All ships known on that side went down. The ships of a remote side are
+    // only known once sunk, so this also holds for those.
+    fn fleet_destroyed(&self, side: usize) -> bool
+    {
+        let field = &self.fields[side];
+        // a remote fleet is only known as far as it was sunk, the player's
+        // fleet at sea can be smaller than the rules say
+        let expected = if self.controllers[side] == Controller::Remote { self.rules.ship_sizes.len() } else { 1 };
+        field.ships.len() >= expected && field.ships.iter().all(|s| field.is_sunk(s))
+    }
+
+    fn is_hot_seat(&self) -> bool
+    {
+        self.controllers.iter().all(|c| *c == Controller::Human)
+    }
+
+    fn has_unicorn(&self) -> bool
+    {
+        self.controllers.contains(&Controller::Unicorn)
+    }
+
+    // The side whose fleet is shown on the left, the other side is the target
+    fn viewer(&self) -> usize
+    {
+        if self.is_hot_seat()
+        {
+            self.current
+        }
+        else
+        {
+            self.controllers.iter().position(|c| *c == Controller::Human).unwrap_or(0)
+        }
+    }
+
+    fn side_name(&self, side: usize) -> String
+    {
+        if self.is_hot_seat()
+        {
+            format!("Player {}", side + 1)
+        }
+        else
+        {
+            match self.controllers[side]
+            {
+                Controller::Human => "You".to_string(),
+                Controller::Unicorn => match self.boss
+                {
+                    Some(index) => BOSSES[index].name.to_string(),
+                    None => "The unicorn".to_string(),
+                },
+                Controller::Remote => "Your opponent".to_string(),
+                Controller::Bot | Controller::Computer => self.record.players[side].clone(),
+            }
+        }
+    }
+
+    fn fleet_complete(&self, side: usize) -> bool
+    {
+        self.fields[side].ships.len() >= self.rules.ship_sizes.len()
+    }
+
+    // Sets up both fleets of a record, without any shots fired
+    pub fn from_record(record: &Record) -> Self {
+        let mut b = Battle::with_seed(record.seed);
+        b.rules = record.rules.clone();
+        b.record = record.clone();
+        b.record_path = None;
+        b.state = State::Replay;
+        b.fields = [Battle::field_from_fleet(&record.fleets[0]), Battle::field_from_fleet(&record.fleets[1])];
+        b
+    }
+
+    fn fleet_of(field: &Playfield) -> Vec<Vec<usize>>
+    {
+        field.ships.iter().map(|s| s.tiles.clone()).collect()
+    }
+
+    fn field_from_fleet(fleet: &[Vec<usize>]) -> Playfield
+    {
+        let mut field = Playfield::new();
+        for ship in fleet.iter()
+        {
+            for t in ship.iter()
+            {
+                field.tiles[*t] = TileState::HasShip;
+            }
+            field.ships.push(Ship { tiles: ship.clone() });
+        }
+        field
+    }
+
+    fn record_shot(&mut self, side: usize, index: usize, result: &ShotResult)
+    {
+        let outcome = match result
+        {
+            ShotResult::Miss => ShotOutcome::Miss,
+            ShotResult::Hit => ShotOutcome::Hit,
+            ShotResult::Sunk(_) => ShotOutcome::Sunk,
+        };
+        self.record.shots.push(RecordedShot { side, index, outcome });
+        self.write_record();
+
+        // bots hear about every shot, their own and the other side's
+        let mut errors = Vec::new();
+        for (bot_side, bot) in self.bots.iter_mut().enumerate()
+        {
+            if let Some(bot) = bot.as_mut()
+            {
+                if let Err(e) = bot.tell_result(bot_side == side, index, outcome)
+                {
+                    errors.push(e);
+                }
+            }
+        }
+        for e in errors
+        {
+            self.notify(&e);
+        }
+    }
+
+    // A record that can't be written is given up, instead of failing again
+    // with every shot
+    fn write_record(&mut self)
+    {
+        if let Some(path) = self.record_path.as_ref()
+        {
+            if let Err(e) = self.record.write(path)
+            {
+                self.record_path = None;
+                self.notify(&format!("Could not write match record: {}", e));
+            }
+        }
+    }
+
+    // Fires a shot from a record at the field of the other side
+    pub fn replay_shot(&mut self, shot: &RecordedShot, animate: bool)
+    {
+        let side = 1 - shot.side;
+        let result = self.fields[side].fire_at(shot.index);
+        if animate
+        {
+            let hit = !matches!(result, ShotResult::Miss);
+            let sunk = matches!(result, ShotResult::Sunk(_));
+            self.launch_projectile(InFlight { side, index: shot.index, hit, sunk }, &result);
+        }
+    }
+
+    pub fn is_animating(&self) -> bool
+    {
+        !self.animation.is_finished()
+    }
+
+    pub fn is_over(&self) -> bool
+    {
+        matches!(self.state, State::Won(_))
+    }
+
+    // Shows a line in the dialog box, e.g. to confirm saving
+    pub fn notify(&mut self, line: &str)
+    {
+        self.text.push_back(line.to_string());
+    }
+
+    fn vec2index(v: &Vec2d) -> usize
+    {
+        (v.x as i32 + v.y as i32 * 10) as usize
+    }
+
+    fn index2vec(i: usize) -> Vec2d
+    {
+        Vec2d::from_ints((i % 10) as i32, (i / 10) as i32)
+    }
+
+    // The tiles a ship of the given size would cover, None if it doesn't
+    // fit on the board or overlaps another ship
+    fn ship_tiles(field: &Playfield, start: usize, size: usize, horizontal: bool) -> Option<Vec<usize>>
+    {
+        let (x, y) = (start % 10, start / 10);
+        if (horizontal && x + size > 10) || (!horizontal && y + size > 10)
+        {
+            return None;
+        }
+        let step = if horizontal { 1 } else { 10 };
+        let tiles: Vec<usize> = (0..size).map(|i| start + i * step).collect();
+        if tiles.iter().any(|t| field.tiles[*t] != TileState::Empty)
+        {
+            return None;
+        }
+        Some(tiles)
+    }
+
+    fn next_ship_size(&self) -> Option<usize>
+    {
+        self.rules.ship_sizes.get(self.fields[self.current].ships.len()).copied()
+    }
+
+    // The tiles the next ship would cover if it was placed at the cursor
+    fn placement_preview(&self) -> Option<(Vec<usize>, bool)>
+    {
+        let size = self.next_ship_size()?;
+        let raster = ((self.cursor_pos - Vec2d::new(px as f32, offy as f32)) / 32.0).truncate();
+        if raster.x < 0.0 || raster.y < 0.0 || raster.x > 9.0 || raster.y > 9.0
+        {
+            return None;
+        }
+        let start = Battle::vec2index(&raster);
+        let field = &self.fields[self.current];
+        match Battle::ship_tiles(field, start, size, self.placing_horizontal)
+        {
+            Some(tiles) => Some((tiles, true)),
+            None => {
+                // show the part that is on the board, marked as blocked
+                let step = if self.placing_horizontal { 1 } else { 10 };
+                let tiles = (0..size)
+                    .map(|i| start + i * step)
+                    .filter(|t| *t < 100 && (!self.placing_horizontal || t / 10 == start / 10))
+                    .collect();
+                Some((tiles, false))
+            }
+        }
+    }
+
+    fn place_ship_at_cursor(&mut self)
+    {
+        if let Some((tiles, true)) = self.placement_preview()
+        {
+            let field = &mut self.fields[self.current];
+            for t in tiles.iter()
+            {
+                field.tiles[*t] = TileState::HasShip;
+            }
+            field.ships.push(Ship { tiles });
+        }
+    }
+
+    fn remove_last_ship(&mut self)
+    {
+        let field = &mut self.fields[self.current];
+        if let Some(ship) = field.ships.pop()
+        {
+            for t in ship.tiles.iter()
+            {
+                field.tiles[*t] = TileState::Empty;
+            }
+        }
+    }
+
+    // Places all ships that are still missing at random
+    fn place_remaining_ships(&mut self)
+    {
+        let placed = self.fields[self.current].ships.len();
+        let remaining = self.rules.ship_sizes[placed..].to_vec();
+        self.fields[self.current].distribute_ships(&remaining, &mut self.rng);
+    }
+
+    fn draw_overlay(sdl_context: &mut Canvas<Window>, overlay_raster_pos: Vec2d, source_tiles: &Playfield, overlay_pixel_pos: Vec2d, show_ships: bool)
+    {
+        let green= sdl2::pixels::Color {
+            r: 0,
+            g: 128,
+            b: 0,
+            a: 128,
+        };
+
+        let red= sdl2::pixels::Color {
+            r: 128,
+            g: 0,
+            b: 0,
+            a: 128,
+        };
+
+        let grey = sdl2::pixels::Color {
+            r: 128,
+            g: 128,
+            b: 128,
+            a: 128,
+        };
+
+        let field_index = (overlay_raster_pos.x as i32 + overlay_raster_pos.y as i32 * 10) as usize;
+        let used_color;
+        match source_tiles.tiles[field_index]
+        {
+            TileState::Empty => return,
+            TileState::ShotAt => used_color = green,
+            TileState::ShotAndHit => used_color = red,
+            // the enemy fleet stays hidden
+            TileState::HasShip if !show_ships => return,
+            TileState::HasShip => used_color = grey,
+        }
+
+        sdl_context.set_blend_mode(BlendMode::Add);
+        sdl_context.set_draw_color(used_color);
+        let _ = sdl_context.fill_rect(Rect::new(overlay_pixel_pos.x as i32, overlay_pixel_pos.y as i32, 32, 32));
+
+    }
+
+    pub fn render(&self, sdl_context: &mut Canvas<Window>, resources: &mut HashMap<String, Texture>) {
+        let white = sdl2::pixels::Color {
+            r: 255,
+            g: 255,
+            b: 255,
+            a: 255,
+        };
+
+        if self.state == State::PassDevice
+        {
+            self.draw_privacy_screen(sdl_context, white);
+            return;
+        }
+
+        let _ = draw::draw_text(
+            sdl_context,
+            &self.headline(),
+            16,
+            Point::new(10, 10),
+            white,
+        );
+        let (left, right) = if self.is_spectated()
+        {
+            (self.side_name(self.viewer()), self.side_name(1 - self.viewer()))
+        }
+        else
+        {
+            ("Your fleet".to_string(), "Enemy waters".to_string())
+        };
+        let _ = draw::draw_text(sdl_context, &left, 14, Point::new(px, offy - 20), white);
+        let _ = draw::draw_text(sdl_context, &right, 14, Point::new(aix, offy - 20), white);
+
+        let water = resources.get("water").unwrap();
+
+        let dialogbox_corner = Vec2d::new(200.0, 400.0);
+        let cursor_raster_pos = ((self.cursor_pos.clone() - Vec2d::new(aix as f32, offy as f32)) / 32.0).truncate();
+        // Draw 2 playfields, one for the viewer's ships and one for the targets:
+        self.draw_playfields(sdl_context, water);
+        self.draw_placement(sdl_context);
+        self.animation.render(sdl_context);
+        self.particles.render(sdl_context, Some(resources));
+        self.draw_cursor(sdl_context, cursor_raster_pos);        
+        if let Some(index) = self.boss
+        {
+            campaign::draw_portrait(sdl_context, resources, &BOSSES[index], Rect::new(40, 400, 150, 150));
+        }
+        else if self.has_unicorn()
+        {
+            let uni = resources.get("unicorn").unwrap();
+            let _ = sdl_context.copy(uni, None, Rect::new(40, 400, 150, 150));
+        }
+        self.draw_dialog_box(dialogbox_corner, sdl_context, white);
+        if let Some(line) = self.chat_input.as_ref()
+        {
+            let _ = draw::draw_text(sdl_context, &format!("Say: {}_", line), 16, Point::new(200, 560), white);
+        }
+
+    }
+
+    fn headline(&self) -> String
+    {
+        if let Some(index) = self.boss
+        {
+            return format!("Campaign: a raging battle against {}", BOSSES[index].name);
+        }
+        if self.has_unicorn()
+        {
+            return "A raging battle against a unicorn".to_string();
+        }
+        if self.is_spectated()
+        {
+            let matchup = format!("{} vs {}", self.side_name(0), self.side_name(1));
+            return match self.state
+            {
+                State::Won(side) => format!("{} - {} won", matchup, self.side_name(side)),
+                _ if self.demo => format!("{} - demo", matchup),
+                _ => format!("{} - {} is aiming", matchup, self.side_name(self.current)),
+            };
+        }
+        match self.state
+        {
+            State::Placing => format!(
+                "{}: place your ships. R rotates, A places the rest, Backspace takes one back",
+                self.side_name(self.current)
+            ),
+            State::WaitingForOpponent => "Waiting for your opponent to place their fleet".to_string(),
+            State::Won(side) => format!("{} won", self.side_name(side)),
+            _ if self.pending_shot.is_some() => "Waiting for the result of your shot".to_string(),
+            _ if self.controllers[self.current] == Controller::Remote => "Your opponent is aiming".to_string(),
+            _ if self.controllers[self.current] == Controller::Bot => format!("{} is thinking", self.side_name(self.current)),
+            _ if !self.is_hot_seat() => "Your turn".to_string(),
+            _ => format!("{}'s turn", self.side_name(self.current)),
+        }
+    }
+
+    // Shown between hot-seat turns, so that nobody sees the other fleet
+    fn draw_privacy_screen(&self, sdl_context: &mut Canvas<Window>, white: sdl2::pixels::Color) {
+        let next = self.side_name(self.current);
+        let other = self.side_name(1 - self.current);
+        let center = Point::new(crate::window_center.x as i32, crate::window_center.y as i32);
+        let task = if self.fleet_complete(self.current) { "it's your turn" } else { "place your fleet" };
+        let _ = draw::draw_text_centered(sdl_context, &format!("{}, {}", next, task), 32, center.offset(0, -60), white);
+        let _ = draw::draw_text_centered(sdl_context, &format!("{}, please look away", other), 20, center, white);
+        let _ = draw::draw_text_centered(sdl_context, "Click when you are ready", 16, center.offset(0, 60), white);
+    }
+
+    fn draw_playfields(&self, sdl_context: &mut Canvas<Window>, water: &Texture) {        
+        let hidden = if self.animation.is_projectile_in_flight() { self.in_flight } else { None };
+        let own = self.viewer();
+        let enemy = 1 - own;
+        // replays and AI games are watched from the outside and show everything
+        let reveal = self.state == State::Replay || self.is_spectated();
+        sdl_context.set_blend_mode(BlendMode::None);
+        for y in 0..=9
+        {
+            for x in 0..=9
+            {                
+                let _ = sdl_context.copy(water, None, Rect::new(px + x * 32, offy + y * 32, 32, 32));                
+                let _ = sdl_context.copy(water, None, Rect::new(aix + x * 32, offy + y * 32, 32, 32));
+
+                // Draw overlay for both sides:
+                let raster_pos = Vec2d::from_ints(x,y);
+                let pixel_pos_own = Vec2d::from_ints(px + x * 32, offy + y * 32);
+                let pixel_pos_enemy = Vec2d::from_ints(aix + x * 32, offy + y * 32);
+                let index = Battle::vec2index(&raster_pos);
+
+                if !hidden.is_some_and(|h| h.side == own && h.index == index)
+                {
+                    Battle::draw_overlay(sdl_context, raster_pos, &self.fields[own], pixel_pos_own, true);
+                }
+                if !hidden.is_some_and(|h| h.side == enemy && h.index == index)
+                {
+                    Battle::draw_overlay(sdl_context, raster_pos, &self.fields[enemy], pixel_pos_enemy, reveal);
+                }
+            }            
+        }
+    }
+
+    // Outline of the next ship under the cursor, red if it doesn't fit
+    fn draw_placement(&self, sdl_context: &mut Canvas<Window>) {
+        if self.state != State::Placing
+        {
+            return;
+        }
+        if let Some((tiles, fits)) = self.placement_preview()
+        {
+            let color = if fits { sdl2::pixels::Color::RGBA(0, 160, 255, 128) } else { sdl2::pixels::Color::RGBA(200, 0, 0, 128) };
+            sdl_context.set_blend_mode(BlendMode::Add);
+            sdl_context.set_draw_color(color);
+            for t in tiles
+            {
+                let pos = Battle::tile_pixel_pos(true, t);
+                let _ = sdl_context.fill_rect(Rect::new(pos.x as i32, pos.y as i32, 32, 32));
+            }
+        }
+    }
+
+    fn draw_dialog_box(&self, dialogbox_corner: Vec2d, sdl_context: &mut Canvas<Window>, white: sdl2::pixels::Color) {
+        let _ = draw::draw_rect(sdl_context, &dialogbox_corner, 580, 150, white, false);
+        let mut org = dialogbox_corner.clone();
+        for t in self.text.iter()
+        {
+            let _= draw::draw_text(sdl_context, t, 16, org.to_point(), white);
+            org = org + Vec2d::new(0.0, 24.0);
+        }
+    }
+
+    fn draw_cursor(&self, sdl_context: &mut Canvas<Window>, cursor_raster_pos: Vec2d) {
+        if self.state == State::Aiming && self.controllers[self.current] == Controller::Human
+        {
+            sdl_context.set_blend_mode(BlendMode::Add);
+            sdl_context.set_draw_color(sdl2::pixels::Color::RGBA(128, 128,128,128));
+            let _ = sdl_context.fill_rect(Rect::new(aix + cursor_raster_pos.x as i32 * 32, offy + cursor_raster_pos.y as i32 * 32, 32, 32));
+        }
+    }
+
+    // Advances everything that plays on its own, independent of the turns
+    pub fn tick_effects(&mut self)
+    {
+        self.cull_texts();
+        self.animation.tick();
+        self.update_particles();
+    }
+
+    // Updates the battle in place, returns where to go once it is over
+    pub fn tick(&mut self) -> Option<Transition>
+    {
+        self.tick_effects();
+        if let Err(e) = self.poll_network()
+        {
+            // nothing to continue without the other side
+            return Battle::leave(Title::with_error(format!("Network game ended: {}", e)));
+        }
+        let s = self.state.clone();
+        match s
+        {
+            State::Placing if self.is_networked() => {
+                if self.fleet_complete(self.current)
+                {
+                    let fleet = Battle::fleet_of(&self.fields[self.current]);
+                    self.commitments.salt = commit::new_salt();
+                    let hash = commit::commitment(&fleet, &self.commitments.salt);
+                    self.record.fleets[self.current] = fleet;
+                    // the commitment goes out first, so it has arrived by the time the opponent sees us ready
+                    let sent = self.send(Message::Commit(hash)).and_then(|_| self.send(Message::Ready));
+                    if let Err(e) = sent
+                    {
+                        return Battle::leave(Title::with_error(format!("Network game ended: {}", e)));
+                    }
+                    self.state = State::WaitingForOpponent;
+                }
+            },
+            State::WaitingForOpponent => {
+                if self.opponent_ready && self.commitments.theirs.is_some()
+                {
+                    self.current = 0;
+                    self.state = State::Aiming;
+                    self.time_state_entered = Instant::now();
+                }
+            },
+            State::Placing => {
+                if self.fleet_complete(self.current)
+                {
+                    self.record.fleets[self.current] = Battle::fleet_of(&self.fields[self.current]);
+                    // once both fleets are out, the first player opens fire
+                    self.current = if self.fleet_complete(1 - self.current) { 0 } else { 1 - self.current };
+                    self.state = State::PassDevice;
+                    self.has_click = false;
+                    self.time_state_entered = Instant::now();
+                }
+            },
+            State::PassDevice => {
+                if self.has_click
+                {
+                    self.has_click = false;
+                    self.state = if self.fleet_complete(self.current) { State::Aiming } else { State::Placing };
+                    self.time_state_entered = Instant::now();
+                }
+            },
+            State::Aiming => {
+                let fired = match self.controllers[self.current]
+                {
+                    Controller::Human => self.player_turn(),
+                    Controller::Unicorn => self.aiturn(),
+                    Controller::Remote => self.remote_turn(),
+                    Controller::Bot => self.bot_turn(),
+                    Controller::Computer => self.computer_turn(),
+                };
+                if fired
+                {
+                    self.state = State::WaitingForProjectile;
+                    self.time_state_entered = Instant::now();
+                }
+            },
+            State::WaitingForProjectile => 
+            {
+                // Shots at a remote side wait for its result, however long
+                // the network takes.
+                if self.pending_shot.is_some()
+                {
+                    self.receive_result();
+                }
+                // The shot was fired when the turn started, the state
+                // ends once the projectile and its impact have played out.
+                if self.animation.is_finished() && self.pending_shot.is_none()
+                {
+                    let target = 1 - self.current;
+                    if self.fleet_destroyed(target)
+                    {
+                        self.announce_winner(self.current);
+                        self.finish(self.current);
+                    }
+                    else
+                    {
+                        // with hit_again a hit keeps the turn
+                        let again = self.rules.hit_again
+                            && matches!(self.record.shots.last(), Some(shot) if shot.outcome != ShotOutcome::Miss);
+                        let next = if again { self.current } else { target };
+                        self.current = next;
+                        self.has_click = false;
+                        if self.is_hot_seat() && !again
+                        {
+                            self.state = State::PassDevice;
+                        }
+                        else
+                        {
+                            self.state = State::Aiming;
+                        }
+                        if self.controllers[next] == Controller::Unicorn && self.turn_event == TurnEvents::NoEvent
+                        {
+                            self.turn_event = TurnEvents::RandomTaunt;
+                        }
+                    }
+                    self.time_state_entered = Instant::now();
+                }
+            },
+            State::Won(_) if self.demo => {
+                if self.time_state_entered.elapsed().as_secs() >= 3
+                {
+                    return Battle::leave(Title::new());
+                }
+            },
+            State::Won(side) => {
+                if self.time_state_entered.elapsed().as_secs() >= 1
                 {
-                    return i;
+                    if let Some(voyage) = self.voyage.take()
+                    {
+                        let won = self.controllers[side] == Controller::Human;
+                        return Some(voyage.after_battle(won, self.damage()));
+                    }
+                    if !self.controllers.contains(&Controller::Remote)
+                    {
+                        return self.outtro(side);
+                    }
+                    match self.verify_opponent()
+                    {
+                        Some(Verdict::Honest) => return self.outtro(side),
+                        Some(Verdict::Disputed(_)) => {
+                            return Battle::end_screen(false, "Result disputed".to_string());
+                        },
+                        None => {},
+                    }
                 }
-            }
+            },
+            State::Replay => {},
+        }
+        None
+    }
+
+    fn leave(title: Title) -> Option<Transition>
+    {
+        Some(Transition::Reset(Box::new(title)))
+    }
+
+    // A finished battle can't be continued
+    fn end_screen(player_lost: bool, headline: String) -> Option<Transition>
+    {
+        delete_save(SAVE_PATH);
+        Some(Transition::Replace(Box::new(Outtro::new(player_lost, headline))))
+    }
+
+    fn announce_winner(&mut self, winner: usize)
+    {
+        let loser = 1 - winner;
+        if self.controllers[loser] == Controller::Unicorn
+        {
+            self.text.push_back(self.taunts().defeat.to_string());
+        }
+        else if self.controllers[winner] == Controller::Unicorn
+        {
+            self.text.push_back(self.taunts().victory.to_string());
+            self.cues.push(Cue::Play(Sfx::UnicornLaugh));
+        }
+        else if self.is_hot_seat() || self.is_spectated()
+        {
+            let line = format!("{} sank the entire fleet of {}.", self.side_name(winner), self.side_name(loser));
+            self.text.push_back(line);
+        }
+        else if self.controllers[winner] == Controller::Human
+        {
+            self.text.push_back("You sank the entire enemy fleet.".to_string());
+        }
+        else
+        {
+            self.text.push_back("Your opponent sank your entire fleet.".to_string());
         }
+    }
 
-        loop {
-            let index = rand::thread_rng().gen_range(0..100) as usize;
-            if self.player_field.tiles[index] == TileState::Empty ||
-               self.player_field.tiles[index] == TileState::HasShip
+    fn outtro(&self, winner: usize) -> Option<Transition>
+    {
+        let player_lost = !self.is_hot_seat() && !self.is_spectated() && self.controllers[winner] != Controller::Human;
+        let headline = if self.is_hot_seat()
+        {
+            format!("{} won", self.side_name(winner))
+        }
+        else
+        {
+            match self.controllers[winner]
             {
-                return index;
-            } 
-        } 
+                Controller::Human => "You won".to_string(),
+                Controller::Unicorn => format!("{} won", self.side_name(winner)),
+                Controller::Remote => "Your opponent won".to_string(),
+                Controller::Bot | Controller::Computer => format!("{} won", self.side_name(winner)),
+            }
+        };
+        Battle::end_screen(player_lost, headline)
     }
 
-    fn waiting_for_ai_projectile(&mut self) -> bool
+    fn player_turn(&mut self) -> bool
     {
-        // We wait for 2 seconds before the projectile "hits"
-        // if a player ship was hit and/or sunk, we issue another
-        // insult, before exiting this state.
-        if self.time_state_entered.elapsed().as_secs() >= 1
+        if self.has_click
         {
-            let tile_to_target;
-            if self.last_hit.is_some()
+            let cursor_raster_pos = ((self.cursor_pos.clone() - Vec2d::new(aix as f32, offy as f32)) / 32.0).truncate();    
+            let field_index = (cursor_raster_pos.x as i32 + cursor_raster_pos.y as i32 * 10) as usize;
+
+            let target = 1 - self.current;
+            if self.controllers[target] == Controller::Remote
             {
-                let sp = self.select_shooting_pos();                
-                if let Some(pos) = sp
+                // the result arrives later, see receive_result
+                self.pending_shot = Some(field_index);
+                if let Err(e) = self.send(Message::Fire { index: field_index })
                 {
-                    tile_to_target = Battle::vec2index(&pos);
+                    // not sent again every frame, the player may try once more
+                    self.pending_shot = None;
+                    self.has_click = false;
+                    self.notify(&format!("Your shot didn't go out: {}", e));
+                    return false;
                 }
-                else 
+                return true;
+            }
+            let result = self.fields[target].fire_at(field_index);
+            self.record_shot(self.current, field_index, &result);
+            // only the unicorn has something to say about it
+            if self.controllers[target] == Controller::Unicorn
+            {
+                match result
                 {
-                    tile_to_target = self.select_random_shooting_pos();
+                    ShotResult::Miss => {},
+                    ShotResult::Hit => self.turn_event = TurnEvents::UniWasHit,
+                    ShotResult::Sunk(_) => self.turn_event = TurnEvents::UniLostShip,
                 }
             }
-            else
-            {
-                tile_to_target = self.select_random_shooting_pos();
+
+            let hit = !matches!(result, ShotResult::Miss);
+            let sunk = matches!(result, ShotResult::Sunk(_));
+            self.launch_projectile(InFlight { side: target, index: field_index, hit, sunk }, &result);
+        }
+        self.has_click
+    }
+
+    // Takes the next shot of the remote side from the inbox and answers it
+    fn remote_turn(&mut self) -> bool
+    {
+        let index = match self.inbox.pop_front()
+        {
+            Some(Message::Fire { index }) => index,
+            Some(other) => {
+                eprintln!("Ignoring unexpected message '{}'", other.to_line());
+                return false;
+            },
+            None => return false,
+        };
+        let target = 1 - self.current;
+        let result = self.fields[target].fire_at(index);
+        let outcome = match &result
+        {
+            ShotResult::Miss => Outcome::Miss,
+            ShotResult::Hit => Outcome::Hit,
+            ShotResult::Sunk(tiles) => Outcome::Sunk(tiles.clone()),
+        };
+        let _ = self.send(Message::Result { index, outcome });
+        self.record_shot(self.current, index, &result);
+
+        let hit = !matches!(result, ShotResult::Miss);
+        let sunk = matches!(result, ShotResult::Sunk(_));
+        self.launch_projectile(InFlight { side: target, index, hit, sunk }, &result);
+        true
+    }
+
+    fn computer_turn(&mut self) -> bool
+    {
+        let target = 1 - self.current;
+        let strategy = match self.strategies[self.current].as_mut()
+        {
+            Some(strategy) => strategy,
+            None => return false,
+        };
+        let index = strategy.choose(&self.fields[target], &mut self.rng);
+        let result = self.fields[target].fire_at(index);
+        strategy.observe(index, &result);
+        self.record_shot(self.current, index, &result);
+        let hit = !matches!(result, ShotResult::Miss);
+        let sunk = matches!(result, ShotResult::Sunk(_));
+        self.launch_projectile(InFlight { side: target, index, hit, sunk }, &result);
+        true
+    }
+
+    // Waits for the bot's next shot. A bot that is too slow or shoots
+    // somewhere it can't loses the game.
+    fn bot_turn(&mut self) -> bool
+    {
+        let target = 1 - self.current;
+        let bot = match self.bots[self.current].as_mut()
+        {
+            Some(bot) => bot,
+            None => return false,
+        };
+        let field = &self.fields[target];
+        let already_shot = |i: usize| field.was_shot_at(i);
+        let polled = bot.poll_shot(&already_shot);
+        let index = match polled
+        {
+            Ok(Some(index)) => index,
+            Ok(None) => return false,
+            Err(e) => {
+                self.notify(&format!("{} forfeits: {}", self.side_name(self.current), e));
+                self.announce_winner(target);
+                self.finish(target);
+                return false;
+            },
+        };
+
+        let result = self.fields[target].fire_at(index);
+        self.record_shot(self.current, index, &result);
+        let hit = !matches!(result, ShotResult::Miss);
+        let sunk = matches!(result, ShotResult::Sunk(_));
+        self.launch_projectile(InFlight { side: target, index, hit, sunk }, &result);
+        true
+    }
+
+    // Applies the remote side's answer to our pending shot
+    fn receive_result(&mut self)
+    {
+        let (index, outcome) = match self.inbox.front()
+        {
+            Some(Message::Result { index, outcome }) if Some(*index) == self.pending_shot => (*index, outcome.clone()),
+            Some(_) => {
+                let other = self.inbox.pop_front().unwrap();
+                eprintln!("Ignoring unexpected message '{}'", other.to_line());
+                return;
+            },
+            None => return,
+        };
+        self.inbox.pop_front();
+        self.pending_shot = None;
+        self.commitments.reported.push((index, outcome.clone()));
+
+        let target = 1 - self.current;
+        let field = &mut self.fields[target];
+        let result = match outcome
+        {
+            Outcome::Miss => {
+                if field.tiles[index] == TileState::Empty
+                {
+                    field.tiles[index] = TileState::ShotAt;
+                }
+                ShotResult::Miss
+            },
+            Outcome::Hit => {
+                field.tiles[index] = TileState::ShotAndHit;
+                ShotResult::Hit
+            },
+            Outcome::Sunk(tiles) => {
+                field.tiles[index] = TileState::ShotAndHit;
+                field.ships.push(Ship { tiles: tiles.clone() });
+                ShotResult::Sunk(tiles)
+            },
+        };
+        self.record_shot(self.current, index, &result);
+
+        let hit = !matches!(result, ShotResult::Miss);
+        let sunk = matches!(result, ShotResult::Sunk(_));
+        self.launch_projectile(InFlight { side: target, index, hit, sunk }, &result);
+    }
+
+    // The viewer's own field is on the left, the target field on the right
+    fn grid_origin(left_grid: bool) -> Vec2d
+    {
+        if left_grid
+        {
+            Vec2d::from_ints(px, offy)
+        }
+        else
+        {
+            Vec2d::from_ints(aix, offy)
+        }
+    }
+
+    fn tile_pixel_pos(left_grid: bool, index: usize) -> Vec2d
+    {
+        Battle::grid_origin(left_grid) + Battle::index2vec(index) * 32.0
+    }
+
+    // Queues the flight of a shell from the center of the firing side's grid to
+    // the target tile, followed by the impact and, if needed, the sinking ship.
+    fn launch_projectile(&mut self, target: InFlight, result: &ShotResult)
+    {
+        let cell_center = Vec2d::new(16.0, 16.0);
+        let left_grid = target.side == self.viewer();
+        let from = Battle::grid_origin(!left_grid) + Vec2d::new(160.0, 160.0);
+        let to = Battle::tile_pixel_pos(left_grid, target.index) + cell_center;
+
+        self.animation.then(vec![Effect::Projectile { from, to }]);
+        match result
+        {
+            ShotResult::Miss => self.animation.then(vec![Effect::Splash { at: to }]),
+            ShotResult::Hit | ShotResult::Sunk(_) => {
+                self.animation.then(vec![Effect::Explosion { at: to }])
             }
+        }
+        if let ShotResult::Sunk(tiles) = result
+        {
+            let cells = tiles.iter()
+                .map(|t| Battle::tile_pixel_pos(left_grid, *t))
+                .collect();
+            self.animation.then(vec![Effect::Sinking { cells }]);
+        }
+        self.in_flight = Some(target);
+        self.cues.push(Cue::Play(Sfx::ShotFired));
+    }
 
+    pub fn take_cues(&mut self) -> Vec<Cue>
+    {
+        std::mem::take(&mut self.cues)
+    }
+
+    // Once the projectile has landed, the impact leaves splashes or
+    // fire and smoke behind, which outlive the waiting state.
+    fn update_particles(&mut self)
+    {
+        let dt = self.last_tick.elapsed().as_secs_f32();
+        self.last_tick = Instant::now();
+        self.particles.update(dt);
 
-            if self.player_field.tiles[tile_to_target] == TileState::Empty
+        if let Some(target) = self.in_flight
+        {
+            if !self.animation.is_projectile_in_flight()
             {
-                self.player_field.tiles[tile_to_target] = TileState::ShotAt;
-                self.turn_event = TurnEvents::RandomTaunt;
+                let at = Battle::tile_pixel_pos(target.side == self.viewer(), target.index) + Vec2d::new(16.0, 16.0);
+                if target.hit
+                {
+                    self.particles.spawn(EmitterConfig::explosion(), at);
+                    self.particles.spawn(EmitterConfig::smoke(), at);
+                    self.cues.push(Cue::Play(Sfx::Hit));
+                }
+                else
+                {
+                    self.particles.spawn(EmitterConfig::splash(), at);
+                    self.cues.push(Cue::Play(Sfx::Splash));
+                }
+                if target.sunk
+                {
+                    self.cues.push(Cue::Play(Sfx::Sink));
+                }
+                self.in_flight = None;
             }
-            else 
-            {
-                self.player_field.tiles[tile_to_target] = TileState::ShotAndHit;
-                self.turn_event = TurnEvents::PlayerWasHit;
-                self.last_hit = Some(Battle::index2vec(tile_to_target));
-
-                // ToDo: Check if the ship was actually sunk and select
-                // a different taunt.
-            }                                    
-            return true              
         }
-        false
     }
 
+    fn select_taunt(&mut self, list: &[&str])
+    {
+        let index = self.rng.gen_range(0..list.len());
+        self.text.push_back(list[index].to_string());
+        self.turn_event = TurnEvents::NoEvent;
+    }
+
+    fn aiturn(&mut self) -> bool {
+
+        // Select insult, based on gamestate
+        if self.turn_event != TurnEvents::NoEvent
+        {
+            self.cull_texts();
+        }
+
+        let taunts = self.taunts();
+        match self.turn_event{
+            TurnEvents::NoEvent => {},
+            TurnEvents::UniWasHit => self.select_taunt(taunts.after_being_hit),
+            TurnEvents::PlayerWasHit => {
+                self.select_taunt(taunts.after_hit);
+                self.cues.push(Cue::Play(Sfx::UnicornLaugh));
+            },
+            TurnEvents::UniLostShip => self.select_taunt(taunts.after_losing_ship),
+            TurnEvents::PlayerLostShip => {
+                self.select_taunt(taunts.after_sinking);
+                self.cues.push(Cue::Play(Sfx::UnicornLaugh));
+            },
+            TurnEvents::RandomTaunt => self.select_taunt(taunts.random),
+        }
+        
+
+        // Fire shot: around the last hit if there is one, at random otherwise
+        let target = 1 - self.current;
+        let tile_to_target;
+        let result;
+        if let Some(strategy) = self.strategies[self.current].as_mut()
+        {
+            // a campaign boss with a strategy of its own
+            tile_to_target = strategy.choose(&self.fields[target], &mut self.rng);
+            result = self.fields[target].fire_at(tile_to_target);
+            strategy.observe(tile_to_target, &result);
+        }
+        else
+        {
+            let mut unicorn = Unicorn { last_hit: self.last_hit[self.current].map(|v| Battle::vec2index(&v)) };
+            tile_to_target = unicorn.choose(&self.fields[target], &mut self.rng);
+            result = self.fields[target].fire_at(tile_to_target);
+            unicorn.observe(tile_to_target, &result);
+            self.last_hit[self.current] = unicorn.last_hit.map(Battle::index2vec);
+        }
+        self.record_shot(self.current, tile_to_target, &result);
+        self.turn_event = match result
+        {
+            ShotResult::Miss => TurnEvents::RandomTaunt,
+            ShotResult::Hit => TurnEvents::PlayerWasHit,
+            ShotResult::Sunk(_) => TurnEvents::PlayerLostShip,
+        };
+
+        let hit = !matches!(result, ShotResult::Miss);
+        let sunk = matches!(result, ShotResult::Sunk(_));
+        self.launch_projectile(InFlight { side: target, index: tile_to_target, hit, sunk }, &result);
+        true
+    }
+    
     fn cull_texts(&mut self)
     {
         while self.text.len() > 4
@@ -561,33 +1640,306 @@ impl Battle {
     }
 
     pub fn mouseevent(&mut self, event: super::MouseEvent) {
-        if self.state == State::PlayerTurn
+        match self.state
         {
-            match event
-            {
-                super::MouseEvent::Motion { x, y } => 
+            State::Aiming if self.controllers[self.current] == Controller::Human => self.grid_mouseevent(event, aix),
+            State::Placing => self.grid_mouseevent(event, px),
+            State::PassDevice => {
+                if let super::MouseEvent::Click { .. } = event
                 {
-                    if x >= 420 && x < (420 + 320)
+                    self.has_click = true;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    // Tracks the cursor over the grid starting at grid_x, clicks aim or place a ship
+    fn grid_mouseevent(&mut self, event: super::MouseEvent, grid_x: i32) {
+        let (x, y, click) = match event
+        {
+            super::MouseEvent::Motion { x, y } => (x as i32, y as i32, false),
+            super::MouseEvent::Click { x, y } => (x as i32, y as i32, true),
+        };
+        if !(grid_x..grid_x + 320).contains(&x) || !(offy..offy + 320).contains(&y)
+        {
+            return;
+        }
+        self.cursor_pos = Vec2d::new(x as f32, y as f32);
+        if click
+        {
+            if self.state == State::Placing
+            {
+                self.place_ship_at_cursor();
+            }
+            else
+            {
+                self.has_click = true;
+            }
+        }
+    }
+
+    pub fn keyevent(&mut self, key: Keycode) {
+        if let Some(line) = self.chat_input.as_mut()
+        {
+            match key
+            {
+                Keycode::Return | Keycode::KpEnter => {
+                    let line = self.chat_input.take().unwrap_or_default();
+                    if !line.trim().is_empty()
                     {
-                        if y >= 60 && y <= 380
+                        match self.send(Message::Chat(line.clone()))
                         {
-                            self.cursor_pos = Vec2d::new(x as f32, y as f32);
+                            Ok(()) => self.notify(&format!("You: {}", line)),
+                            Err(e) => self.notify(&format!("Chat failed: {}", e)),
                         }
                     }
                 },
-                super::MouseEvent::Click { x, y } => 
-                {
-                    if x >= 420 && x < (420 + 320)
+                Keycode::Backspace => { line.pop(); },
+                _ => {
+                    if let Some(c) = super::typed_char(key)
                     {
-                        if y >= 60 && y <= 380
-                        {
-                            self.cursor_pos = Vec2d::new(x as f32, y as f32);
-                            self.has_click = true;
-                        }
-                    }                    
+                        line.push(c);
+                    }
+                },
+            }
+            return;
+        }
+        if self.is_networked()
+        {
+            match key
+            {
+                Keycode::Return | Keycode::KpEnter => {
+                    self.chat_input = Some(String::new());
+                    return;
                 },
+                Keycode::F10 => {
+                    self.resign();
+                    return;
+                },
+                _ => {}
+            }
+        }
+        if self.state == State::Placing
+        {
+            match key
+            {
+                Keycode::R => self.placing_horizontal = !self.placing_horizontal,
+                Keycode::A => self.place_remaining_ships(),
+                Keycode::Backspace => self.remove_last_ship(),
+                _ => {}
+            }
+        }
+    }
+
+}
+
+impl Scene for Battle
+{
+    fn update(&mut self) -> Option<Transition>
+    {
+        self.tick()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas<Window>, resources: &mut HashMap<String, Texture>)
+    {
+        Battle::render(self, canvas, resources);
+    }
+
+    fn handle_input(&mut self, input: Input) -> Option<Transition>
+    {
+        // any input ends the demo
+        if self.demo && !matches!(input, Input::KeyUp(_))
+        {
+            return Battle::leave(Title::new());
+        }
+        match input
+        {
+            Input::Key(key) if self.is_typing() => self.keyevent(key),
+            Input::Key(Keycode::F5 | Keycode::F9) if !self.can_save() => self.notify("This game can't be saved."),
+            Input::Key(Keycode::F5) => match self.save(SAVE_PATH)
+            {
+                Ok(()) => self.notify("Game saved."),
+                Err(e) => self.notify(&format!("Saving failed: {}", e)),
+            },
+            Input::Key(Keycode::F9) => match Battle::load(SAVE_PATH)
+            {
+                Ok(loaded) => return Some(Transition::Replace(Box::new(loaded))),
+                Err(e) => self.notify(&format!("Loading failed: {}", e)),
+            },
+            Input::Key(key) => self.keyevent(key),
+            Input::Mouse(event) => self.mouseevent(event),
+            Input::KeyUp(_) => {},
+        }
+        None
+    }
+
+    fn on_enter(&mut self)
+    {
+        // back from a pause, the animations go on where they stopped
+        self.last_tick = Instant::now();
+    }
+
+    // the remote side doesn't wait for us
+    fn can_pause(&self) -> bool
+    {
+        !self.is_networked() && !self.demo
+    }
+
+    fn is_typing(&self) -> bool
+    {
+        Battle::is_typing(self)
+    }
+
+    fn music(&self) -> Option<Track>
+    {
+        Some(Track::Battle)
+    }
+
+    fn take_cues(&mut self) -> Vec<Cue>
+    {
+        Battle::take_cues(self)
+    }
+
+    // Keeps a running battle for later. Network games can't wait, they are
+    // resigned.
+    fn on_quit(&mut self)
+    {
+        if self.is_networked()
+        {
+            self.resign();
+        }
+        else if self.can_save() && !self.is_over()
+        {
+            if let Err(e) = self.save(SAVE_PATH)
+            {
+                // the battle is dropped after this, a notice would never be seen
+                eprintln!("Autosave failed: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ships_must_fit_on_the_board() {
+        let mut field = Playfield::new();
+        assert_eq!(Battle::ship_tiles(&field, 7, 3, true), Some(vec![7, 8, 9]));
+        assert_eq!(Battle::ship_tiles(&field, 8, 3, true), None);
+        assert_eq!(Battle::ship_tiles(&field, 70, 3, false), Some(vec![70, 80, 90]));
+        assert_eq!(Battle::ship_tiles(&field, 80, 3, false), None);
+
+        field.tiles[8] = TileState::HasShip;
+        assert_eq!(Battle::ship_tiles(&field, 7, 2, true), None);
+    }
+
+    #[test]
+    fn a_record_that_cant_be_written_is_reported_once() {
+        // a file where the record's directory should be
+        let blocker = std::env::temp_dir().join(format!("bns-record-test-{}", std::process::id()));
+        std::fs::write(&blocker, "").unwrap();
+        let mut b = Battle::with_controllers(3, [Controller::Human, Controller::Unicorn]);
+        b.record_path = Some(blocker.join("match.bnr"));
+        b.write_record();
+        b.write_record();
+        assert!(b.record_path.is_none());
+        assert_eq!(b.text.iter().filter(|t| t.starts_with("Could not write match record")).count(), 1);
+        let _ = std::fs::remove_file(&blocker);
+    }
+
+    #[test]
+    fn ais_play_each_other_in_full_view() {
+        let mut b = Battle::spectate(["hunt", "parity"], 3).unwrap();
+        assert!(b.is_spectated() && !b.can_save());
+        assert_eq!(b.record.players, ["hunt".to_string(), "parity".to_string()]);
+        b.record_path = None;
+        while !b.fleet_destroyed(1 - b.current)
+        {
+            assert!(b.computer_turn());
+            if !b.fleet_destroyed(1 - b.current)
+            {
+                b.current = 1 - b.current;
             }
         }
+        assert!(b.record.shots.len() <= 200);
+        assert!(Battle::spectate(["hunt", "psychic"], 3).is_err());
+    }
+
+    #[test]
+    fn hot_seat_players_place_their_fleets_in_turn() {
+        let mut b = Battle::with_controllers(3, [Controller::Human, Controller::Human]);
+        assert!(b.state == State::PassDevice);
+        assert!(b.fields[0].ships.is_empty() && b.fields[1].ships.is_empty());
+
+        for side in 0..2 {
+            assert_eq!(b.current, side);
+            b.has_click = true;
+            b.tick();
+            assert!(b.state == State::Placing);
+            assert_eq!(b.viewer(), side);
+            b.place_remaining_ships();
+            b.tick();
+            assert!(b.state == State::PassDevice);
+        }
+
+        // player 1 opens fire once both fleets are out
+        assert_eq!(b.current, 0);
+        assert_eq!(b.record.fleets[1].len(), b.rules.ship_sizes.len());
+        b.has_click = true;
+        b.tick();
+        assert!(b.state == State::Aiming);
     }
 
+    #[test]
+    fn network_shots_wait_for_their_result() {
+        use crate::net::Host;
+        use std::time::Duration;
+
+        let host = Host::bind(0).unwrap();
+        let guest = Connection::connect(&format!("127.0.0.1:{}", host.port())).unwrap();
+        let mut accepted = None;
+        for _ in 0..500 {
+            accepted = host.accept().unwrap();
+            if accepted.is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        let mut a = Battle::networked(accepted.unwrap(), 0, "guest");
+        let mut b = Battle::networked(guest, 1, "host");
+        a.record_path = None;
+        b.record_path = None;
+        a.place_remaining_ships();
+        b.place_remaining_ships();
+
+        let run = |a: &mut Battle, b: &mut Battle, done: &dyn Fn(&Battle, &Battle) -> bool| {
+            for _ in 0..500 {
+                a.tick();
+                b.tick();
+                if done(a, b) {
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(2));
+            }
+            panic!("the battle got stuck");
+        };
+        run(&mut a, &mut b, &|a, b| a.state == State::Aiming && b.state == State::Aiming);
+        assert_eq!((a.current, b.current), (0, 0));
+
+        // the host aims at a ship of the guest
+        let index = b.fields[1].ships[0].tiles[0];
+        a.cursor_pos = Vec2d::from_ints(aix + (index % 10) as i32 * 32 + 4, offy + (index / 10) as i32 * 32 + 4);
+        a.has_click = true;
+        a.tick();
+        assert_eq!(a.pending_shot, Some(index));
+        assert!(a.state == State::WaitingForProjectile);
+
+        run(&mut a, &mut b, &|a, _| a.pending_shot.is_none());
+        assert!(a.fields[1].tiles[index] == TileState::ShotAndHit);
+        assert!(b.fields[1].tiles[index] == TileState::ShotAndHit);
+        assert_eq!(a.record.shots, b.record.shots);
+    }
 }
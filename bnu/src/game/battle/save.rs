@@ -0,0 +1,325 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::Path;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use super::{Playfield, Battle, Controller, Ship, State, TileState, TurnEvents};
+use crate::game::record::{Record, RECORD_DIR};
+use crate::vecmath::Vec2d;
+
+pub const SAVE_PATH: &str = "savegame.bns";
+
+// Bump this whenever the layout below changes, older saves are rejected
+// with an error instead of being misread.
+const SAVE_VERSION: u32 = 3;
+const SAVE_HEADER: &str = "bns-save";
+
+fn state_name(state: &State) -> String {
+    match state {
+        State::Placing => "Placing".to_string(),
+        State::PassDevice => "PassDevice".to_string(),
+        State::WaitingForOpponent => "WaitingForOpponent".to_string(),
+        State::Aiming => "Aiming".to_string(),
+        State::WaitingForProjectile => "WaitingForProjectile".to_string(),
+        State::Won(side) => format!("Won {}", side + 1),
+        State::Replay => "Replay".to_string(),
+    }
+}
+
+fn parse_state(name: &str) -> Result<State, String> {
+    match name {
+        "Placing" => Ok(State::Placing),
+        "PassDevice" => Ok(State::PassDevice),
+        "WaitingForOpponent" => Ok(State::WaitingForOpponent),
+        "Aiming" => Ok(State::Aiming),
+        "WaitingForProjectile" => Ok(State::WaitingForProjectile),
+        "Won 1" => Ok(State::Won(0)),
+        "Won 2" => Ok(State::Won(1)),
+        "Replay" => Ok(State::Replay),
+        _ => Err(format!("Unknown battle state '{}'", name)),
+    }
+}
+
+fn parse_controller(name: &str) -> Result<Controller, String> {
+    match name {
+        "human" => Ok(Controller::Human),
+        "unicorn" => Ok(Controller::Unicorn),
+        _ => Err(format!("Unknown player '{}'", name)),
+    }
+}
+
+fn parse_last_hit(value: &str) -> Result<Option<Vec2d>, String> {
+    match value {
+        "none" => Ok(None),
+        v => {
+            let (x, y) = v
+                .split_once(' ')
+                .ok_or(format!("Invalid last hit '{}'", v))?;
+            Ok(Some(Vec2d::from_ints(
+                parse_number("last_hit", x)?,
+                parse_number("last_hit", y)?,
+            )))
+        }
+    }
+}
+
+fn event_name(event: TurnEvents) -> &'static str {
+    match event {
+        TurnEvents::NoEvent => "NoEvent",
+        TurnEvents::RandomTaunt => "RandomTaunt",
+        TurnEvents::UniWasHit => "UniWasHit",
+        TurnEvents::PlayerWasHit => "PlayerWasHit",
+        TurnEvents::UniLostShip => "UniLostShip",
+        TurnEvents::PlayerLostShip => "PlayerLostShip",
+    }
+}
+
+fn parse_event(name: &str) -> Result<TurnEvents, String> {
+    match name {
+        "NoEvent" => Ok(TurnEvents::NoEvent),
+        "RandomTaunt" => Ok(TurnEvents::RandomTaunt),
+        "UniWasHit" => Ok(TurnEvents::UniWasHit),
+        "PlayerWasHit" => Ok(TurnEvents::PlayerWasHit),
+        "UniLostShip" => Ok(TurnEvents::UniLostShip),
+        "PlayerLostShip" => Ok(TurnEvents::PlayerLostShip),
+        _ => Err(format!("Unknown turn event '{}'", name)),
+    }
+}
+
+fn tiles_to_string(field: &Playfield) -> String {
+    field
+        .tiles
+        .iter()
+        .map(|t| match t {
+            TileState::Empty => '.',
+            TileState::ShotAt => 'o',
+            TileState::ShotAndHit => 'x',
+            TileState::HasShip => '#',
+        })
+        .collect()
+}
+
+fn ships_to_string(field: &Playfield) -> String {
+    field
+        .ships
+        .iter()
+        .map(|s| {
+            s.tiles
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn parse_field(tiles: &str, ships: &str) -> Result<Playfield, String> {
+    let mut field = Playfield::new();
+    if tiles.chars().count() != field.tiles.len() {
+        return Err(format!(
+            "Expected {} tiles, found {}",
+            field.tiles.len(),
+            tiles.chars().count()
+        ));
+    }
+    for (idx, c) in tiles.chars().enumerate() {
+        field.tiles[idx] = match c {
+            '.' => TileState::Empty,
+            'o' => TileState::ShotAt,
+            'x' => TileState::ShotAndHit,
+            '#' => TileState::HasShip,
+            _ => return Err(format!("Unknown tile '{}'", c)),
+        };
+    }
+    for ship in ships.split_whitespace() {
+        let mut tiles = Vec::new();
+        for t in ship.split(',') {
+            let index: usize = t
+                .parse()
+                .map_err(|_| format!("Invalid ship tile '{}'", t))?;
+            if index >= field.tiles.len() {
+                return Err(format!("Ship tile {} is off the board", index));
+            }
+            tiles.push(index);
+        }
+        field.ships.push(Ship { tiles });
+    }
+    Ok(field)
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value '{}' for '{}'", value, key))
+}
+
+pub fn save_exists(path: &str) -> bool {
+    Path::new(path).exists()
+}
+
+pub fn delete_save(path: &str) {
+    let _ = fs::remove_file(path);
+}
+
+impl Battle {
+    pub fn to_save_string(&self) -> String {
+        let mut out = String::new();
+        out += &format!("{} {}\n", SAVE_HEADER, SAVE_VERSION);
+        out += &format!("seed {}\n", self.seed);
+        out += &format!("rng_pos {}\n", self.rng.get_word_pos());
+        out += &format!("state {}\n", state_name(&self.state));
+        out += &format!("turn_event {}\n", event_name(self.turn_event));
+        out += &format!(
+            "controllers {} {}\n",
+            self.controllers[0].name(),
+            self.controllers[1].name()
+        );
+        out += &format!("current {}\n", self.current);
+        for side in 0..2 {
+            match self.last_hit[side] {
+                Some(v) => out += &format!("last_hit{} {} {}\n", side + 1, v.x as i32, v.y as i32),
+                None => out += &format!("last_hit{} none\n", side + 1),
+            }
+            out += &format!("side{}_tiles {}\n", side + 1, tiles_to_string(&self.fields[side]));
+            out += &format!("side{}_ships {}\n", side + 1, ships_to_string(&self.fields[side]));
+        }
+        for t in self.text.iter() {
+            out += &format!("text {}\n", t);
+        }
+        // The match record so far, so that it continues after loading
+        for line in self.record.to_string().lines() {
+            out += &format!("record {}\n", line);
+        }
+        out
+    }
+
+    pub fn from_save_string(data: &str) -> Result<Battle, String> {
+        let mut lines = data.lines();
+        let header = lines.next().unwrap_or("");
+        let version = match header.split_once(' ') {
+            Some((SAVE_HEADER, v)) => parse_number::<u32>("version", v.trim())?,
+            _ => return Err("This is not a battleships save game".to_string()),
+        };
+        if version != SAVE_VERSION {
+            return Err(format!(
+                "Save game version {} is not supported, this build reads version {}",
+                version, SAVE_VERSION
+            ));
+        }
+
+        let mut values: HashMap<&str, &str> = HashMap::new();
+        let mut text = VecDeque::new();
+        let mut record = String::new();
+        for line in lines {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            if key == "text" {
+                text.push_back(value.to_string());
+            } else if key == "record" {
+                record += value;
+                record += "\n";
+            } else {
+                values.insert(key, value);
+            }
+        }
+        let get = |key: &str| -> Result<&str, String> {
+            values
+                .get(key)
+                .copied()
+                .ok_or(format!("Save game is missing '{}'", key))
+        };
+
+        let seed: u64 = parse_number("seed", get("seed")?)?;
+        let rng_pos: u128 = parse_number("rng_pos", get("rng_pos")?)?;
+
+        let mut b = Battle::with_seed(seed);
+        b.rng = ChaCha8Rng::seed_from_u64(seed);
+        b.rng.set_word_pos(rng_pos);
+        b.state = parse_state(get("state")?)?;
+        b.turn_event = parse_event(get("turn_event")?)?;
+        let (first, second) = get("controllers")?
+            .split_once(' ')
+            .ok_or("Save game needs two controllers".to_string())?;
+        b.controllers = [parse_controller(first)?, parse_controller(second)?];
+        b.current = parse_number("current", get("current")?)?;
+        if b.current > 1 {
+            return Err(format!("Unknown side {}", b.current));
+        }
+        for side in 0..2 {
+            let n = side + 1;
+            b.last_hit[side] = parse_last_hit(get(&format!("last_hit{}", n))?)?;
+            b.fields[side] = parse_field(
+                get(&format!("side{}_tiles", n))?,
+                get(&format!("side{}_ships", n))?,
+            )?;
+        }
+        b.text = text;
+        b.record = Record::parse(&record).map_err(|e| format!("Broken match record: {}", e))?;
+        b.rules = b.record.rules.clone();
+        b.record_path = Some(Path::new(RECORD_DIR).join(b.record.file_name()));
+        Ok(b)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_save_string()).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &str) -> Result<Battle, String> {
+        let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Battle::from_save_string(&data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn save_round_trip() {
+        let mut b = Battle::with_seed(42);
+        b.fields[0].tiles[3] = TileState::ShotAt;
+        b.last_hit[1] = Some(Vec2d::from_ints(3, 4));
+        b.text.push_back("You will pay for this!".to_string());
+        let _: u32 = b.rng.gen();
+
+        let saved = b.to_save_string();
+        let mut loaded = Battle::from_save_string(&saved).unwrap();
+        assert_eq!(saved, loaded.to_save_string());
+        assert_eq!(loaded.fields[0].ships.len(), 5);
+        assert!(loaded.last_hit[1].is_some());
+        assert_eq!(loaded.record, b.record);
+        // the random stream continues where it left off
+        assert_eq!(b.rng.gen::<u64>(), loaded.rng.gen::<u64>());
+    }
+
+    #[test]
+    fn hot_seat_round_trip() {
+        let mut b = Battle::with_controllers(9, [Controller::Human, Controller::Human]);
+        b.current = 1;
+        b.state = State::Placing;
+        let loaded = Battle::from_save_string(&b.to_save_string()).unwrap();
+        assert_eq!(loaded.controllers, [Controller::Human, Controller::Human]);
+        assert_eq!(loaded.current, 1);
+        assert!(loaded.state == State::Placing);
+    }
+
+    #[test]
+    fn older_version_is_rejected() {
+        let saved = Battle::with_seed(1).to_save_string();
+        let old = saved.replacen("bns-save 3", "bns-save 0", 1);
+        match Battle::from_save_string(&old) {
+            Err(e) => assert!(e.contains("version 0")),
+            Ok(_) => panic!("old save was accepted"),
+        }
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        assert!(Battle::from_save_string("").is_err());
+        assert!(Battle::from_save_string("bns-save 3\nseed 1\n").is_err());
+    }
+}
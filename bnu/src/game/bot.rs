@@ -0,0 +1,242 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::record::{coordinate, parse_coordinate, parse_ship, ShotOutcome};
+use super::rules::Rules;
+use crate::net::commit::check_layout;
+
+// An opponent living in another process, so bots can be written in any
+// language. The game talks to it line by line over stdin/stdout:
+//
+//   game -> bot                        bot -> game
+//   bns-bot 1                          name <any text>
+//   rules board=10 ships=2,3,3,4,5
+//   place                              fleet A1-B1 C3-C5 ...
+//   shoot                              shot E5
+//   result E5 miss|hit|sunk            (the bot's last shot)
+//   opponent B2 miss|hit|sunk          (the other side's last shot)
+//   end win|loss
+//
+// Coordinates are written like in match records: letters for columns, numbers
+// for rows. Every answer has to arrive within the timeout, an illegal fleet
+// or shot loses the game.
+
+pub const BOT_PROTOCOL_VERSION: u32 = 1;
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct Bot {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    name: String,
+    board_size: usize,
+    timeout: Duration,
+    // set while a shot was asked for but not answered yet
+    shot_deadline: Option<Instant>,
+}
+
+// A bot's fleet, checked against the rules
+pub fn parse_fleet(line: &str, rules: &Rules) -> Result<Vec<Vec<usize>>, String> {
+    let ships = line
+        .strip_prefix("fleet ")
+        .ok_or(format!("Expected a fleet, got '{}'", line))?;
+    let fleet = ships
+        .split_whitespace()
+        .map(|s| parse_ship(s, rules.board_size))
+        .collect::<Result<Vec<Vec<usize>>, String>>()?;
+    check_layout(&fleet, &rules.ship_sizes).map_err(|e| format!("Illegal fleet, {}", e))?;
+    Ok(fleet)
+}
+
+// A bot's shot, which must be on the board and at a tile not shot at before
+pub fn parse_shot(line: &str, board_size: usize, already_shot: &dyn Fn(usize) -> bool) -> Result<usize, String> {
+    let target = line
+        .strip_prefix("shot ")
+        .ok_or(format!("Expected a shot, got '{}'", line))?;
+    let index = parse_coordinate(target.trim(), board_size)?;
+    if already_shot(index) {
+        return Err(format!("{} was shot at before", target.trim()));
+    }
+    Ok(index)
+}
+
+impl Bot {
+    // Starts the bot and introduces the rules. The command is split at
+    // whitespace, the first word is the program.
+    pub fn launch(command: &str, rules: &Rules, timeout: Duration) -> Result<Bot, String> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or("No bot command given".to_string())?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| format!("Can't start bot '{}': {}", command, e))?;
+        let stdin = child.stdin.take().ok_or("Bot has no stdin".to_string())?;
+        let stdout = child.stdout.take().ok_or("Bot has no stdout".to_string())?;
+
+        // stdout is read on its own thread, so waiting for it can time out
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => {
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        let mut bot = Bot {
+            child,
+            stdin,
+            lines,
+            name: String::new(),
+            board_size: rules.board_size,
+            timeout,
+            shot_deadline: None,
+        };
+        let answer = bot.ask(&format!("bns-bot {}", BOT_PROTOCOL_VERSION))?;
+        bot.name = answer
+            .strip_prefix("name ")
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty())
+            .ok_or(format!("Expected a name, got '{}'", answer))?;
+        let sizes: Vec<String> = rules.ship_sizes.iter().map(|s| s.to_string()).collect();
+        bot.send(&format!("rules board={} ships={}", rules.board_size, sizes.join(",")))?;
+        Ok(bot)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", line)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| format!("Bot '{}' stopped listening: {}", self.name, e))
+    }
+
+    fn ask(&mut self, line: &str) -> Result<String, String> {
+        self.send(line)?;
+        match self.lines.recv_timeout(self.timeout) {
+            Ok(answer) => Ok(answer.trim().to_string()),
+            Err(RecvTimeoutError::Timeout) => Err(format!("Bot '{}' took too long to answer '{}'", self.name, line)),
+            Err(RecvTimeoutError::Disconnected) => Err(format!("Bot '{}' quit", self.name)),
+        }
+    }
+
+    pub fn place_fleet(&mut self, rules: &Rules) -> Result<Vec<Vec<usize>>, String> {
+        let answer = self.ask("place")?;
+        parse_fleet(&answer, rules)
+    }
+
+    // Asks for a shot without blocking the frame loop, None until the answer
+    // is there
+    pub fn poll_shot(&mut self, already_shot: &dyn Fn(usize) -> bool) -> Result<Option<usize>, String> {
+        let deadline = match self.shot_deadline {
+            Some(d) => d,
+            None => {
+                self.send("shoot")?;
+                let d = Instant::now() + self.timeout;
+                self.shot_deadline = Some(d);
+                d
+            }
+        };
+        match self.lines.try_recv() {
+            Ok(answer) => {
+                self.shot_deadline = None;
+                parse_shot(answer.trim(), self.board_size, already_shot).map(Some)
+            }
+            Err(TryRecvError::Empty) if Instant::now() < deadline => Ok(None),
+            Err(TryRecvError::Empty) => Err(format!("Bot '{}' took too long to shoot", self.name)),
+            Err(TryRecvError::Disconnected) => Err(format!("Bot '{}' quit", self.name)),
+        }
+    }
+
+    // `own_shot` tells whether the bot fired it or was shot at
+    pub fn tell_result(&mut self, own_shot: bool, index: usize, outcome: ShotOutcome) -> Result<(), String> {
+        let kind = if own_shot { "result" } else { "opponent" };
+        let line = format!("{} {} {}", kind, coordinate(index, self.board_size), outcome.name());
+        self.send(&line)
+    }
+
+    pub fn tell_end(&mut self, won: bool) {
+        let _ = self.send(if won { "end win" } else { "end loss" });
+    }
+}
+
+impl Drop for Bot {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fleets_are_validated() {
+        let rules = Rules::classic();
+        let fleet = parse_fleet("fleet A1-B1 A3-C3 A5-C5 A7-D7 A9-E9", &rules).unwrap();
+        assert_eq!(fleet[0], vec![0, 1]);
+        // too few ships, overlap, off the board
+        assert!(parse_fleet("fleet A1-B1", &rules).is_err());
+        assert!(parse_fleet("fleet A1-B1 A1-C1 A5-C5 A7-D7 A9-E9", &rules).is_err());
+        assert!(parse_fleet("fleet A1-B1 A3-C3 A5-C5 A7-D7 H9-L9", &rules).is_err());
+        assert!(parse_fleet("shot A1", &rules).is_err());
+    }
+
+    #[test]
+    fn shots_are_validated() {
+        let never = |_: usize| false;
+        assert_eq!(parse_shot("shot C2", 10, &never).unwrap(), 12);
+        assert!(parse_shot("shot K2", 10, &never).is_err());
+        assert!(parse_shot("shot C2", 10, &|i| i == 12).is_err());
+        assert!(parse_shot("fire C2", 10, &never).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn talks_to_a_bot_process() {
+        // the bot only goes on when it heard what it expects
+        let script = "read hello; [ \"$hello\" = \"bns-bot 1\" ] || exit; echo name shellbot; \
+                      read rules; [ \"$rules\" = \"rules board=10 ships=2,3,3,4,5\" ] || exit; \
+                      read place; echo fleet A1-B1 A3-C3 A5-C5 A7-D7 A9-E9; \
+                      read shoot; echo shot J10; \
+                      read result; read opponent; read shoot; \
+                      [ \"$result $opponent\" = \"result J10 miss opponent C2 hit\" ] && echo shot A1; \
+                      read shoot; sleep 2";
+        let rules = Rules::classic();
+        // commands are split at whitespace, so the script goes through a file
+        let path = std::env::temp_dir().join(format!("bns-bot-test-{}.sh", std::process::id()));
+        std::fs::write(&path, script).unwrap();
+        let mut bot = Bot::launch(&format!("sh {}", path.display()), &rules, Duration::from_millis(300)).unwrap();
+        assert_eq!(bot.name(), "shellbot");
+        assert_eq!(bot.place_fleet(&rules).unwrap().len(), 5);
+        // polled every frame, like the battle does
+        let shoot = |bot: &mut Bot| loop {
+            if let Some(index) = bot.poll_shot(&|_| false)? {
+                return Ok::<usize, String>(index);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(shoot(&mut bot).unwrap(), 99);
+        bot.tell_result(true, 99, ShotOutcome::Miss).unwrap();
+        bot.tell_result(false, 12, ShotOutcome::Hit).unwrap();
+        assert_eq!(shoot(&mut bot).unwrap(), 0);
+        // the third shot never comes
+        assert!(shoot(&mut bot).is_err());
+        bot.tell_end(false);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+use std::fs;
+
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+
+use super::battle::{Battle, Taunts};
+use super::rules::Rules;
+use super::MouseEvent;
+use crate::audio::Track;
+use crate::draw;
+use crate::scene::{Input, Scene, Transition};
+use crate::window_center;
+
+// A row of unicorn bosses to beat in order. Each one brings its own
+// portrait, lines, targeting strategy, fleet and rule tweaks. Beating a boss
+// unlocks the next one, the progress is kept in CAMPAIGN_PATH.
+
+pub const CAMPAIGN_PATH: &str = "campaign.bns";
+const CAMPAIGN_HEADER: &str = "bns-campaign";
+const CAMPAIGN_VERSION: u32 = 1;
+
+const LIST_LEFT: i32 = 60;
+const LIST_TOP: i32 = 120;
+const ENTRY_HEIGHT: i32 = 50;
+const ENTRY_WIDTH: i32 = 300;
+
+pub struct Boss {
+    pub name: &'static str,
+    // file in assets/
+    pub portrait: &'static str,
+    pub greeting: &'static str,
+    pub taunts: Taunts,
+    // one of engine::strategy::STRATEGY_NAMES
+    pub strategy: &'static str,
+    // the fleet both sides sail in this fight
+    pub ship_sizes: &'static [usize],
+    pub hit_again: bool,
+    // the boss fires the first shot
+    pub opens: bool,
+}
+
+impl Boss {
+    pub fn rules(&self) -> Rules {
+        Rules {
+            ship_sizes: self.ship_sizes.to_vec(),
+            hit_again: self.hit_again,
+            ..Rules::classic()
+        }
+    }
+
+    fn description(&self) -> Vec<String> {
+        let sizes: Vec<String> = self.ship_sizes.iter().map(|s| s.to_string()).collect();
+        let mut lines = vec![format!("Fleet: {} ships of size {}", self.ship_sizes.len(), sizes.join(", "))];
+        if self.hit_again {
+            lines.push("A hit earns another shot".to_string());
+        }
+        if self.opens {
+            lines.push(format!("{} fires first", self.name));
+        }
+        lines
+    }
+}
+
+pub const BOSSES: [Boss; 4] = [
+    Boss {
+        name: "Sparkles",
+        portrait: "boss_sparkles.png",
+        greeting: "Hi! Mom says I can have your ships when I win!",
+        taunts: Taunts {
+            random: &["Eeny, meeny, miny, moe", "Is it this one?", "I'm not even looking!"],
+            after_hit: &["I hit something! Yay!", "Boom! Did you see that?"],
+            after_being_hit: &["Hey, that's not fair!", "Ouch! I'm telling Mom!"],
+            after_sinking: &["Bye bye little boat!", "It sank! It really sank!"],
+            after_losing_ship: &["Waaah, my favourite ship!", "That one was broken anyway"],
+            victory: "I won! I won! Can we play again?",
+            defeat: "I'm telling my big sister about you.",
+        },
+        strategy: "random",
+        ship_sizes: &[2, 3, 3, 4, 5],
+        hit_again: false,
+        opens: false,
+    },
+    Boss {
+        name: "Bramble",
+        portrait: "boss_bramble.png",
+        greeting: "You're in my waters now. Mind the thorns.",
+        taunts: Taunts {
+            random: &["The hedge has eyes", "I'll find you, one leaf at a time", "Patience wins wars"],
+            after_hit: &["Caught in the brambles", "Got a thorn in your hull?"],
+            after_being_hit: &["A scratch, nothing more", "You'll regret pruning me"],
+            after_sinking: &["Overgrown and sunk", "Compost for the deep"],
+            after_losing_ship: &["It'll grow back", "You cut deep, sailor"],
+            victory: "Everything ends up in the hedge.",
+            defeat: "Fine. Go on. My sister waits in the dark.",
+        },
+        strategy: "hunt",
+        ship_sizes: &[2, 2, 3, 3, 4],
+        hit_again: false,
+        opens: true,
+    },
+    Boss {
+        name: "Nightmare",
+        portrait: "boss_nightmare.png",
+        greeting: "Every hit I land, I shoot again. Sleep well.",
+        taunts: Taunts {
+            random: &["Can't sleep?", "I'm under your bed", "Count the sheep, captain"],
+            after_hit: &["And again!", "Sweet dreams are made of this"],
+            after_being_hit: &["Just a bad dream", "You can't hurt a nightmare"],
+            after_sinking: &["Drowned in your sleep", "Wake up! Oh, too late"],
+            after_losing_ship: &["I have plenty more dreams", "That was only a daydream"],
+            victory: "Lights out, captain.",
+            defeat: "Morning comes... but the Queen never sleeps.",
+        },
+        strategy: "parity",
+        ship_sizes: &[2, 3, 3, 4, 5],
+        hit_again: true,
+        opens: false,
+    },
+    Boss {
+        name: "Queen Glimmerhorn",
+        portrait: "boss_glimmerhorn.png",
+        greeting: "So you sank my children. Kneel before the Queen.",
+        taunts: Taunts {
+            random: &[
+                "I will end you",
+                "Your puny fleet is no match for me",
+                "Mess with the best and die like the rest",
+            ],
+            after_hit: &["There's nothing like the smell of napalm in the morning", "You should've stayed at home"],
+            after_being_hit: &["Treason!", "Guards! Guards!"],
+            after_sinking: &["Glub glub glub. That was your ship.", "Say hello to Davy Jones for me"],
+            after_losing_ship: &["That one was a decoy anyway", "I have more ships than you have brain cells"],
+            victory: "Long live the Queen.",
+            defeat: "Impossible... the crown is yours, captain.",
+        },
+        strategy: "unicorn",
+        ship_sizes: &[2, 3, 3, 4, 4, 5],
+        hit_again: true,
+        opens: true,
+    },
+];
+
+// Every portrait file the bosses use, for loading them at startup
+pub fn portraits() -> Vec<&'static str> {
+    let mut files: Vec<&'static str> = BOSSES.iter().map(|b| b.portrait).collect();
+    files.sort_unstable();
+    files.dedup();
+    files
+}
+
+// How far the player got, bosses are beaten in order
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Progress {
+    pub beaten: usize,
+}
+
+impl Progress {
+    pub fn is_unlocked(&self, boss: usize) -> bool {
+        boss <= self.beaten && boss < BOSSES.len()
+    }
+
+    pub fn to_save_string(self) -> String {
+        format!("{} {}\nbeaten {}\n", CAMPAIGN_HEADER, CAMPAIGN_VERSION, self.beaten)
+    }
+
+    pub fn from_save_string(data: &str) -> Result<Progress, String> {
+        let mut lines = data.lines();
+        match lines.next().and_then(|l| l.split_once(' ')) {
+            Some((CAMPAIGN_HEADER, v)) if v.trim() == CAMPAIGN_VERSION.to_string() => {}
+            _ => return Err("This is not a campaign file this build can read".to_string()),
+        }
+        let mut progress = Progress::default();
+        for line in lines {
+            match line.split_once(' ') {
+                Some(("beaten", v)) => {
+                    let beaten: usize = v.trim().parse().map_err(|_| format!("Invalid value '{}' for 'beaten'", v))?;
+                    progress.beaten = beaten.min(BOSSES.len());
+                }
+                _ => return Err(format!("Can't read line '{}'", line)),
+            }
+        }
+        Ok(progress)
+    }
+
+    // A campaign that was never started has nothing beaten yet
+    pub fn load(path: &str) -> Result<Progress, String> {
+        match fs::read_to_string(path) {
+            Ok(data) => Progress::from_save_string(&data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Progress::default()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn save(self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_save_string()).map_err(|e| e.to_string())
+    }
+
+    // Beating a boss again doesn't lock the ones after it
+    pub fn record_win(&mut self, boss: usize) {
+        self.beaten = self.beaten.max(boss + 1).min(BOSSES.len());
+    }
+}
+
+// Called when the player beat a boss, returns what to tell them
+pub fn boss_beaten(boss: usize) -> String {
+    let mut progress = Progress::load(CAMPAIGN_PATH).unwrap_or_default();
+    progress.record_win(boss);
+    if let Err(e) = progress.save(CAMPAIGN_PATH) {
+        return format!("Could not save the campaign: {}", e);
+    }
+    match BOSSES.get(boss + 1) {
+        Some(next) => format!("{} awaits you.", next.name),
+        None => "You beat every unicorn. The seas are yours!".to_string(),
+    }
+}
+
+pub fn draw_portrait(canvas: &mut Canvas<Window>, resources: &mut HashMap<String, Texture>, boss: &Boss, rect: Rect) {
+    let key = if resources.contains_key(boss.portrait) { boss.portrait } else { "unicorn" };
+    if let Some(texture) = resources.get(key) {
+        let _ = canvas.copy(texture, None, rect);
+    }
+}
+
+// The list of bosses between fights
+pub struct CampaignMap {
+    progress: Progress,
+    selected: usize,
+    chosen: Option<usize>,
+    leave: bool,
+    error: Option<String>,
+}
+
+impl CampaignMap {
+    pub fn new() -> Self {
+        let (progress, error) = match Progress::load(CAMPAIGN_PATH) {
+            Ok(p) => (p, None),
+            Err(e) => (Progress::default(), Some(format!("Campaign progress lost: {}", e))),
+        };
+        CampaignMap {
+            progress,
+            // the first boss not beaten yet
+            selected: progress.beaten.min(BOSSES.len() - 1),
+            chosen: None,
+            leave: false,
+            error,
+        }
+    }
+
+    fn entry_rect(idx: usize) -> Rect {
+        Rect::new(LIST_LEFT, LIST_TOP + idx as i32 * ENTRY_HEIGHT, ENTRY_WIDTH as u32, (ENTRY_HEIGHT - 10) as u32)
+    }
+
+    fn entry_at(x: u32, y: u32) -> Option<usize> {
+        (0..BOSSES.len()).find(|idx| CampaignMap::entry_rect(*idx).contains_point(Point::new(x as i32, y as i32)))
+    }
+
+    fn choose(&mut self, idx: usize) {
+        if self.progress.is_unlocked(idx) {
+            self.chosen = Some(idx);
+        }
+    }
+
+    pub fn render(&self, canvas: &mut Canvas<Window>, resources: &mut HashMap<String, Texture>) {
+        let white = Color::RGB(255, 255, 255);
+        let grey = Color::RGB(110, 110, 110);
+        let _ = draw::draw_text_centered(canvas, "Campaign", 32, Point::new(window_center.x as i32, 50), white);
+
+        for (idx, boss) in BOSSES.iter().enumerate() {
+            let rect = CampaignMap::entry_rect(idx);
+            let unlocked = self.progress.is_unlocked(idx);
+            let color = match (idx == self.selected, unlocked) {
+                (true, _) => Color::RGB(255, 200, 0),
+                (false, true) => white,
+                (false, false) => grey,
+            };
+            let label = match (unlocked, idx < self.progress.beaten) {
+                (false, _) => "???".to_string(),
+                (true, true) => format!("{} - beaten", boss.name),
+                (true, false) => boss.name.to_string(),
+            };
+            canvas.set_draw_color(color);
+            let _ = canvas.draw_rect(rect);
+            let _ = draw::draw_text_centered(canvas, &label, 18, rect.center(), color);
+        }
+
+        let boss = &BOSSES[self.selected];
+        if self.progress.is_unlocked(self.selected) {
+            draw_portrait(canvas, resources, boss, Rect::new(480, 110, 200, 200));
+            let _ = draw::draw_text(canvas, &format!("\"{}\"", boss.greeting), 14, Point::new(LIST_LEFT, 340), white);
+            for (n, line) in boss.description().iter().enumerate() {
+                let _ = draw::draw_text(canvas, line, 14, Point::new(LIST_LEFT, 370 + n as i32 * 20), white);
+            }
+        } else {
+            let _ = draw::draw_text(canvas, "Beat the unicorns before to meet this one", 14, Point::new(LIST_LEFT, 340), grey);
+        }
+
+        let help = "Up/Down: choose   Return: fight   Backspace: back to the title";
+        let _ = draw::draw_text(canvas, help, 12, Point::new(LIST_LEFT, 560), white);
+        if let Some(error) = &self.error {
+            let _ = draw::draw_text(canvas, error, 14, Point::new(LIST_LEFT, 520), Color::RGB(255, 64, 64));
+        }
+    }
+
+    pub fn mouseevent(&mut self, event: MouseEvent) {
+        match event {
+            MouseEvent::Motion { x, y } => {
+                if let Some(idx) = CampaignMap::entry_at(x, y) {
+                    self.selected = idx;
+                }
+            }
+            MouseEvent::Click { x, y } => {
+                if let Some(idx) = CampaignMap::entry_at(x, y) {
+                    self.choose(idx);
+                }
+            }
+        }
+    }
+
+    pub fn keyevent(&mut self, key: Keycode) {
+        match key {
+            Keycode::Up => self.selected = self.selected.saturating_sub(1),
+            Keycode::Down => self.selected = (self.selected + 1).min(BOSSES.len() - 1),
+            Keycode::Return | Keycode::KpEnter => self.choose(self.selected),
+            Keycode::Backspace => self.leave = true,
+            _ => {}
+        }
+    }
+
+    pub fn tick(&mut self) -> Option<Transition> {
+        if self.leave {
+            return Some(Transition::Pop);
+        }
+        self.chosen
+            .take()
+            .map(|boss| Transition::Replace(Box::new(Battle::against_boss(boss))))
+    }
+}
+
+impl Scene for CampaignMap {
+    fn update(&mut self) -> Option<Transition> {
+        self.tick()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas<Window>, resources: &mut HashMap<String, Texture>) {
+        CampaignMap::render(self, canvas, resources);
+    }
+
+    fn handle_input(&mut self, input: Input) -> Option<Transition> {
+        match input {
+            Input::Key(key) => self.keyevent(key),
+            Input::Mouse(event) => self.mouseevent(event),
+            Input::KeyUp(_) => {}
+        }
+        None
+    }
+
+    fn music(&self) -> Option<Track> {
+        Some(Track::Title)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::strategy;
+
+    #[test]
+    fn bosses_unlock_in_order() {
+        let mut progress = Progress::default();
+        assert!(progress.is_unlocked(0));
+        assert!(!progress.is_unlocked(1));
+        progress.record_win(0);
+        assert!(progress.is_unlocked(1));
+        // replaying an earlier boss keeps what was unlocked
+        progress.record_win(1);
+        progress.record_win(0);
+        assert_eq!(progress.beaten, 2);
+        let loaded = Progress::from_save_string(&progress.to_save_string()).unwrap();
+        assert_eq!(loaded, progress);
+        assert!(Progress::from_save_string("bns-campaign 9\nbeaten 1\n").is_err());
+    }
+
+    #[test]
+    fn every_boss_can_be_fought() {
+        for boss in BOSSES.iter() {
+            assert!(strategy::by_name(boss.strategy).is_ok(), "{}", boss.name);
+            // the fleet has to fit the board, leaving room to place it
+            let tiles: usize = boss.ship_sizes.iter().sum();
+            assert!(tiles <= boss.rules().board_size * boss.rules().board_size / 3);
+            assert!(!boss.taunts.random.is_empty());
+            assert!(std::path::Path::new("assets").join(boss.portrait).exists(), "{}", boss.portrait);
+        }
+        // everyone has a face of their own
+        assert_eq!(portraits().len(), BOSSES.len());
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Point;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+
+use super::battle::Battle;
+use super::title::Title;
+use crate::audio::Track;
+use crate::draw;
+use crate::net::{Connection, Host, Message, PROTOCOL_VERSION};
+use crate::scene::{Input, Scene, Transition};
+use crate::window_center;
+
+// Sets up a network game: the host waits for someone to join, the other
+// side types the host's address. Both say hello, then the battle starts.
+pub struct Lobby {
+    // the host is side 0, the joining side 1
+    local: usize,
+    host: Option<Host>,
+    connection: Option<Connection>,
+    address: String,
+    editing: bool,
+    connect_requested: bool,
+    status: String,
+    leave: bool,
+}
+
+fn player_name() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "captain".to_string())
+}
+
+impl Lobby {
+    pub fn host(port: u16) -> Box<dyn Scene> {
+        match Host::bind(port) {
+            Ok(host) => Box::new(Lobby {
+                local: 0,
+                status: format!("Waiting for an opponent on port {}", host.port()),
+                host: Some(host),
+                connection: None,
+                address: String::new(),
+                editing: false,
+                connect_requested: false,
+                leave: false,
+            }),
+            Err(e) => Box::new(Title::with_error(e)),
+        }
+    }
+
+    // Without an address, the player types one in first
+    pub fn join(address: Option<String>) -> Box<dyn Scene> {
+        Box::new(Lobby {
+            local: 1,
+            host: None,
+            connection: None,
+            editing: address.is_none(),
+            connect_requested: address.is_some(),
+            address: address.unwrap_or_default(),
+            status: "Type the address of the host, e.g. 192.168.0.2:4711".to_string(),
+            leave: false,
+        })
+    }
+
+    pub fn is_typing(&self) -> bool {
+        self.editing
+    }
+
+    fn say_hello(&mut self, mut connection: Connection) {
+        let hello = Message::Hello {
+            version: PROTOCOL_VERSION,
+            name: player_name(),
+        };
+        match connection.send(&hello) {
+            Ok(()) => {
+                self.status = format!("Connected to {}, waiting for hello", connection.peer());
+                self.connection = Some(connection);
+            }
+            Err(e) => self.status = e,
+        }
+    }
+
+    // Back to the title, with what went wrong
+    fn fail(error: String) -> Option<Transition> {
+        Some(Transition::Reset(Box::new(Title::with_error(error))))
+    }
+
+    pub fn tick(&mut self) -> Option<Transition> {
+        if self.leave {
+            return Some(Transition::Reset(Box::new(Title::new())));
+        }
+
+        if self.connection.is_none() {
+            if let Some(host) = self.host.as_ref() {
+                match host.accept() {
+                    Ok(Some(connection)) => self.say_hello(connection),
+                    Ok(None) => {}
+                    Err(e) => return Lobby::fail(e),
+                }
+            } else if self.connect_requested {
+                self.connect_requested = false;
+                match Connection::connect(self.address.trim()) {
+                    Ok(connection) => {
+                        self.editing = false;
+                        self.say_hello(connection);
+                    }
+                    Err(e) => {
+                        self.editing = true;
+                        self.status = e;
+                    }
+                }
+            }
+        }
+
+        if let Some(connection) = self.connection.as_mut() {
+            let messages = match connection.poll() {
+                Ok(m) => m,
+                Err(e) => return Lobby::fail(e),
+            };
+            let mut messages = messages.into_iter();
+            match messages.next() {
+                Some(Message::Hello { version, name }) => {
+                    if version != PROTOCOL_VERSION {
+                        return Lobby::fail(format!(
+                            "The opponent speaks protocol version {}, this build speaks version {}",
+                            version, PROTOCOL_VERSION
+                        ));
+                    }
+                    let connection = self.connection.take()?;
+                    let mut battle = Battle::networked(connection, self.local, &name);
+                    // whatever came right after the hello belongs to the battle
+                    for m in messages {
+                        battle.receive(m);
+                    }
+                    return Some(Transition::Replace(Box::new(battle)));
+                }
+                Some(other) => {
+                    return Lobby::fail(format!("Expected a hello, got '{}'", other.to_line()))
+                }
+                None => {}
+            }
+        }
+        None
+    }
+
+    pub fn keyevent(&mut self, key: Keycode) {
+        if !self.editing {
+            if key == Keycode::Backspace {
+                self.leave = true;
+            }
+            return;
+        }
+        match key {
+            Keycode::Return | Keycode::KpEnter => self.connect_requested = true,
+            Keycode::Backspace => {
+                if self.address.pop().is_none() {
+                    self.leave = true;
+                }
+            }
+            _ => {
+                if let Some(c) = super::typed_char(key) {
+                    self.address.push(c);
+                }
+            }
+        }
+    }
+
+    pub fn render(&self, canvas: &mut Canvas<Window>, _resources: &HashMap<String, Texture>) {
+        let white = Color::RGB(255, 255, 255);
+        let center = window_center.x as i32;
+        let title = if self.local == 0 { "Hosting a network game" } else { "Joining a network game" };
+        let _ = draw::draw_text_centered(canvas, title, 32, Point::new(center, 120), white);
+        if self.editing {
+            let _ = draw::draw_text_centered(
+                canvas,
+                &format!("Address: {}_", self.address),
+                20,
+                Point::new(center, 260),
+                Color::RGB(255, 200, 0),
+            );
+        }
+        let _ = draw::draw_text_centered(canvas, &self.status, 16, Point::new(center, 320), white);
+        let hint = if self.editing {
+            "Return connects, Backspace on an empty address goes back"
+        } else {
+            "Backspace goes back"
+        };
+        let _ = draw::draw_text_centered(canvas, hint, 14, Point::new(center, 540), white);
+    }
+}
+
+impl Scene for Lobby {
+    fn update(&mut self) -> Option<Transition> {
+        self.tick()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas<Window>, resources: &mut HashMap<String, Texture>) {
+        Lobby::render(self, canvas, resources);
+    }
+
+    fn handle_input(&mut self, input: Input) -> Option<Transition> {
+        if let Input::Key(key) = input {
+            self.keyevent(key);
+        }
+        None
+    }
+
+    fn is_typing(&self) -> bool {
+        Lobby::is_typing(self)
+    }
+
+    fn music(&self) -> Option<Track> {
+        Some(Track::Title)
+    }
+}
@@ -1,61 +1,155 @@
 use std::collections::HashMap;
 
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Point;
 use sdl2::render::Texture;
 
+use crate::audio::Audio;
+use crate::draw;
+use crate::window_height;
+use crate::scene::{Input, Scene, SceneStack, Transition};
+
 mod battle;
+mod bot;
+mod campaign;
+mod lobby;
+mod outtro;
+mod overworld;
+mod pause;
+mod record;
+mod replay;
+mod title;
 
-#[derive(Clone)]
-pub enum GameState {
-    Intro,
-    Tile,    
-    Battle(battle::Battle),
-    Outtro{playerLost: bool},
-}
+use crate::engine::rules;
 
-pub enum MouseEvent
-{
-    Motion{x: u32, y: u32},
-    Click{x: u32, y: u32}
-}
+pub use campaign::portraits;
+pub use crate::scene::MouseEvent;
 
 pub struct Game {
-    g: GameState,
+    scenes: SceneStack,
 }
 
 impl Game {
-    pub fn new(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) -> Self {
+    pub fn new() -> Self {
         Self {
-            g: GameState::Battle(battle::Battle::new()),
+            scenes: SceneStack::new(Box::new(title::Title::new())),
         }
     }
 
-    pub fn render(&mut self, canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, resources: &HashMap<String, Texture>) {
-        match self.g {
-            GameState::Intro => todo!(),
-            GameState::Tile => todo!(),
-            GameState::Battle(ref b) => b.render(canvas, resources),
-            GameState::Outtro{playerLost}  => todo!(),
+    fn start(&mut self, scene: Box<dyn Scene>) {
+        self.scenes.reset(scene);
+    }
+
+    pub fn start_bot(&mut self, command: &str) {
+        self.start(match battle::Battle::against_bot(command) {
+            Ok(b) => Box::new(b),
+            Err(e) => Box::new(title::Title::with_error(e)),
+        });
+    }
+
+    // An AI-vs-AI game, each player is a strategy or "bot:<command>"
+    pub fn start_watch(&mut self, players: [&str; 2]) {
+        self.start(match battle::Battle::spectate(players, rand::random()) {
+            Ok(b) => Box::new(b),
+            Err(e) => Box::new(title::Title::with_error(e)),
+        });
+    }
+
+    pub fn start_host(&mut self, port: u16) {
+        self.start(lobby::Lobby::host(port));
+    }
+
+    pub fn start_join(&mut self, address: &str) {
+        self.start(lobby::Lobby::join(Some(address.to_string())));
+    }
+
+    // The lander over the title, Backspace goes back to the menu
+    pub fn start_lander(&mut self) {
+        self.start(Box::new(title::Title::new()));
+        self.scenes.apply(Transition::Push(Box::new(crate::alt::Lander::new())));
+    }
+
+    // Opens a match record directly, e.g. from the command line
+    pub fn start_replay(&mut self, path: &str) {
+        self.start(match record::Record::read(std::path::Path::new(path)) {
+            Ok(r) => Box::new(replay::Replay::new(r)),
+            Err(e) => Box::new(title::Title::with_error(format!("Could not open {}: {}", path, e))),
+        });
+    }
+
+    pub fn render(&mut self, canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, resources: &mut HashMap<String, Texture>, audio: &Audio) {
+        self.scenes.render(canvas, resources);
+        // M is easily hit by accident, a silent game says why
+        let status = if !audio.is_available() {
+            Some("No sound")
+        } else if audio.settings().muted {
+            Some("Muted, M for sound")
+        } else {
+            None
+        };
+        if let Some(status) = status {
+            let at = Point::new(6, window_height as i32 - 18);
+            let _ = draw::draw_text(canvas, status, 12, at, Color::RGB(160, 160, 160));
         }
     }
 
-    pub fn tick(&mut self) {
-        let nextGameState;
-        match self.g {
-            GameState::Intro => nextGameState = GameState::Intro,
-            GameState::Tile => nextGameState = GameState::Tile,
-            GameState::Battle(ref mut b) => nextGameState = b.tick(),
-            GameState::Outtro{playerLost} => {nextGameState = GameState::Outtro { playerLost }},
+    pub fn tick(&mut self, audio: &mut Audio) {
+        self.scenes.update();
+        for cue in self.scenes.take_cues() {
+            audio.cue(cue);
         }
-        self.g = nextGameState;
+        audio.play_music(self.scenes.music());
     }
 
     pub fn mouseeveent(&mut self, event: MouseEvent)
     {
-        match self.g {
-            GameState::Intro => todo!(),
-            GameState::Tile => todo!(),
-            GameState::Battle(ref mut b) => b.mouseevent(event),
-            GameState::Outtro{playerLost}  => {},
-        }        
+        self.scenes.handle_input(Input::Mouse(event));
+    }
+
+    pub fn keyevent(&mut self, key: Keycode)
+    {
+        if key == Keycode::P && !self.scenes.is_typing() && self.scenes.can_pause() {
+            self.scenes.apply(Transition::Push(Box::new(pause::Pause::new())));
+            return;
+        }
+        self.scenes.handle_input(Input::Key(key));
+    }
+
+    pub fn keyup(&mut self, key: Keycode)
+    {
+        self.scenes.handle_input(Input::KeyUp(key));
+    }
+
+    pub fn is_typing(&self) -> bool
+    {
+        self.scenes.is_typing()
+    }
+
+    // Called when the window closes, every scene keeps what it needs
+    pub fn on_quit(&mut self)
+    {
+        self.scenes.on_quit();
+    }
+}
+
+// The character a key types into a text line, shift is not supported
+fn typed_char(key: Keycode) -> Option<char> {
+    match key {
+        Keycode::Space => Some(' '),
+        Keycode::Period | Keycode::KpPeriod => Some('.'),
+        Keycode::Comma => Some(','),
+        Keycode::Semicolon | Keycode::Colon => Some(':'),
+        Keycode::Minus | Keycode::KpMinus => Some('-'),
+        Keycode::Slash => Some('/'),
+        Keycode::Quote => Some('\''),
+        _ => {
+            let name = key.name();
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii_alphanumeric() => Some(c.to_ascii_lowercase()),
+                _ => None,
+            }
+        }
     }
 }
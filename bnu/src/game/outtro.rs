@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use sdl2::pixels::Color;
+use sdl2::rect::Point;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+
+use super::title::Title;
+use super::MouseEvent;
+use crate::audio::Track;
+use crate::draw;
+use crate::scene::{Input, Scene, Transition};
+use crate::window_center;
+
+// The end screen of a game, a click goes back to the title
+pub struct Outtro {
+    player_lost: bool,
+    headline: String,
+}
+
+impl Outtro {
+    pub fn new(player_lost: bool, headline: String) -> Self {
+        Outtro { player_lost, headline }
+    }
+}
+
+impl Scene for Outtro {
+    fn update(&mut self) -> Option<Transition> {
+        None
+    }
+
+    fn render(&mut self, canvas: &mut Canvas<Window>, _resources: &mut HashMap<String, Texture>) {
+        let _ = draw::draw_text_centered(
+            canvas,
+            &self.headline,
+            48,
+            Point::new(window_center.x as i32, window_center.y as i32 - 40),
+            Color::RGB(255, 255, 255),
+        );
+        let _ = draw::draw_text_centered(
+            canvas,
+            "Click to return to the title screen",
+            16,
+            Point::new(window_center.x as i32, window_center.y as i32 + 40),
+            Color::RGB(255, 255, 255),
+        );
+    }
+
+    fn handle_input(&mut self, input: Input) -> Option<Transition> {
+        match input {
+            Input::Mouse(MouseEvent::Click { .. }) => Some(Transition::Reset(Box::new(Title::new()))),
+            _ => None,
+        }
+    }
+
+    fn music(&self) -> Option<Track> {
+        Some(if self.player_lost { Track::Defeat } else { Track::Victory })
+    }
+}
@@ -0,0 +1,521 @@
+use std::collections::{HashMap, VecDeque};
+
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+
+use super::battle::Battle;
+use super::outtro::Outtro;
+use super::MouseEvent;
+use crate::audio::Track;
+use crate::draw;
+use crate::scene::{Input, Scene, Transition};
+
+// The strategic layer: a grid of sea sectors the flagship sails across.
+// Unicorn squadrons hide in the fog, meeting one starts a battle. Whatever
+// the fleet suffers there stays with it until a harbour repairs it.
+
+pub const COLS: usize = 16;
+pub const ROWS: usize = 10;
+const SECTOR_SIZE: i32 = 48;
+const MAP_LEFT: i32 = 16;
+const MAP_TOP: i32 = 50;
+
+// sectors around the flagship that are in view
+const SIGHT: usize = 2;
+const ISLAND_CHANCE: f64 = 0.12;
+const HARBOURS: usize = 2;
+// squadrons out of sight move this often
+const PATROL_CHANCE: f64 = 0.3;
+
+const START_FLEET: [usize; 5] = [2, 3, 3, 4, 5];
+
+// name, fleet and strategy of the squadrons, from weak to strong
+const SQUADRON_KINDS: [(&str, &[usize], &str); 4] = [
+    ("Unicorn patrol", &[2, 3], "random"),
+    ("Unicorn escort", &[2, 3, 3], "hunt"),
+    ("Unicorn raiders", &[2, 2, 3, 4], "hunt"),
+    ("Unicorn battle group", &[2, 3, 3, 4, 5], "parity"),
+];
+const SQUADRONS: [usize; 6] = [0, 0, 1, 1, 2, 3];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Sector {
+    Sea,
+    Island,
+    // an island the fleet can sail into, it repairs every damaged ship
+    Harbour,
+}
+
+// One ship of the player's fleet and the hits it has taken so far
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Vessel {
+    pub size: usize,
+    pub hits: usize,
+}
+
+impl Vessel {
+    pub fn is_sunk(&self) -> bool {
+        self.hits >= self.size
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Squadron {
+    pub sector: usize,
+    pub name: &'static str,
+    pub ship_sizes: &'static [usize],
+    // one of engine::strategy::STRATEGY_NAMES
+    pub strategy: &'static str,
+}
+
+#[derive(Clone)]
+pub struct Overworld {
+    sectors: Vec<Sector>,
+    // sectors the fleet has seen at least once, the rest is fog
+    seen: Vec<bool>,
+    flagship: usize,
+    fleet: Vec<Vessel>,
+    squadrons: Vec<Squadron>,
+    // the squadron the flagship ran into, fought on the next tick
+    engaged: Option<usize>,
+    rng: ChaCha8Rng,
+    message: Option<String>,
+    leave: bool,
+}
+
+fn position(sector: usize) -> (usize, usize) {
+    (sector % COLS, sector / COLS)
+}
+
+// Sectors diagonal to each other count as one apart
+fn distance(a: usize, b: usize) -> usize {
+    let ((ax, ay), (bx, by)) = (position(a), position(b));
+    ax.abs_diff(bx).max(ay.abs_diff(by))
+}
+
+// The sectors left, right, above and below
+fn neighbours(sector: usize) -> Vec<usize> {
+    let (x, y) = position(sector);
+    let mut result = Vec::new();
+    if x > 0 {
+        result.push(sector - 1);
+    }
+    if x + 1 < COLS {
+        result.push(sector + 1);
+    }
+    if y > 0 {
+        result.push(sector - COLS);
+    }
+    if y + 1 < ROWS {
+        result.push(sector + COLS);
+    }
+    result
+}
+
+impl Overworld {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let start = (ROWS / 2) * COLS;
+        loop {
+            let mut sectors: Vec<Sector> = (0..COLS * ROWS)
+                .map(|_| if rng.gen_bool(ISLAND_CHANCE) { Sector::Island } else { Sector::Sea })
+                .collect();
+            sectors[start] = Sector::Sea;
+            let islands: Vec<usize> = (0..sectors.len()).filter(|s| sectors[*s] == Sector::Island).collect();
+            for harbour in islands.choose_multiple(&mut rng, HARBOURS) {
+                sectors[*harbour] = Sector::Harbour;
+            }
+
+            // squadrons start out of sight
+            let mut open: Vec<usize> = (0..sectors.len())
+                .filter(|s| sectors[*s] == Sector::Sea && distance(*s, start) > SIGHT + 1)
+                .collect();
+            open.shuffle(&mut rng);
+            if open.len() < SQUADRONS.len() {
+                continue;
+            }
+            let squadrons: Vec<Squadron> = SQUADRONS
+                .iter()
+                .zip(open)
+                .map(|(kind, sector)| {
+                    let (name, ship_sizes, strategy) = SQUADRON_KINDS[*kind];
+                    Squadron {
+                        sector,
+                        name,
+                        ship_sizes,
+                        strategy,
+                    }
+                })
+                .collect();
+
+            let mut world = Overworld {
+                sectors,
+                seen: vec![false; COLS * ROWS],
+                flagship: start,
+                fleet: START_FLEET.iter().map(|size| Vessel { size: *size, hits: 0 }).collect(),
+                squadrons,
+                engaged: None,
+                rng: rng.clone(),
+                message: Some("Find and sink every unicorn squadron. Harbours repair your ships.".to_string()),
+                leave: false,
+            };
+            // islands must not cut off a squadron or a harbour
+            let reachable = world.reachable();
+            let cut_off = world.squadrons.iter().map(|s| s.sector).chain(world.harbours()).any(|s| !reachable[s]);
+            if !cut_off {
+                world.reveal();
+                return world;
+            }
+        }
+    }
+
+    pub fn fleet(&self) -> &[Vessel] {
+        &self.fleet
+    }
+
+    // The squadron about to be fought
+    pub fn enemy(&self) -> Option<&Squadron> {
+        self.engaged.map(|idx| &self.squadrons[idx])
+    }
+
+    fn harbours(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.sectors.len()).filter(|s| self.sectors[*s] == Sector::Harbour)
+    }
+
+    fn is_navigable(&self, sector: usize) -> bool {
+        self.sectors[sector] != Sector::Island
+    }
+
+    // Every sector the flagship can sail to from where it is
+    fn reachable(&self) -> Vec<bool> {
+        let mut reached = vec![false; self.sectors.len()];
+        let mut queue = VecDeque::from([self.flagship]);
+        reached[self.flagship] = true;
+        while let Some(sector) = queue.pop_front() {
+            for next in neighbours(sector) {
+                if !reached[next] && self.is_navigable(next) {
+                    reached[next] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+        reached
+    }
+
+    fn in_sight(&self, sector: usize) -> bool {
+        distance(sector, self.flagship) <= SIGHT
+    }
+
+    fn reveal(&mut self) {
+        for sector in 0..self.sectors.len() {
+            if self.in_sight(sector) {
+                self.seen[sector] = true;
+            }
+        }
+    }
+
+    fn squadron_at(&self, sector: usize) -> Option<usize> {
+        self.squadrons.iter().position(|s| s.sector == sector)
+    }
+
+    fn sail(&mut self, dx: i32, dy: i32) {
+        let (x, y) = position(self.flagship);
+        let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+        if nx < 0 || ny < 0 || nx >= COLS as i32 || ny >= ROWS as i32 {
+            return;
+        }
+        self.sail_to(nx as usize + ny as usize * COLS);
+    }
+
+    fn sail_to(&mut self, sector: usize) {
+        if self.engaged.is_some() || !neighbours(self.flagship).contains(&sector) {
+            return;
+        }
+        if !self.is_navigable(sector) {
+            self.message = Some("Land ahoy! The fleet can't sail there.".to_string());
+            return;
+        }
+        self.flagship = sector;
+        self.message = None;
+        self.reveal();
+        if self.sectors[sector] == Sector::Harbour && self.fleet.iter().any(|v| v.hits > 0) {
+            for vessel in self.fleet.iter_mut() {
+                vessel.hits = 0;
+            }
+            self.message = Some("The harbour patched up your fleet.".to_string());
+        }
+        self.engaged = self.squadron_at(sector);
+        if self.engaged.is_none() {
+            self.move_squadrons();
+        }
+    }
+
+    // Squadrons that see the flagship close in, the others patrol
+    fn move_squadrons(&mut self) {
+        for idx in 0..self.squadrons.len() {
+            let from = self.squadrons[idx].sector;
+            let mut options: Vec<usize> = neighbours(from)
+                .into_iter()
+                .filter(|s| self.sectors[*s] == Sector::Sea && self.squadron_at(*s).is_none())
+                .collect();
+            let target = if self.in_sight(from) {
+                options.retain(|s| distance(*s, self.flagship) < distance(from, self.flagship));
+                options.choose(&mut self.rng).copied()
+            } else if self.rng.gen_bool(PATROL_CHANCE) {
+                options.choose(&mut self.rng).copied()
+            } else {
+                None
+            };
+            if let Some(sector) = target {
+                self.squadrons[idx].sector = sector;
+                if sector == self.flagship {
+                    self.engaged = Some(idx);
+                    return;
+                }
+            }
+        }
+    }
+
+    // Back from a battle against the engaged squadron, `fleet` is the
+    // player's fleet as it left the battle
+    pub fn after_battle(mut self, won: bool, fleet: Vec<Vessel>) -> Transition {
+        match self.settle(won, fleet) {
+            Some(end) => Transition::Replace(Box::new(end)),
+            None => Transition::Replace(Box::new(self)),
+        }
+    }
+
+    // Takes the fleet back, returns the end screen if the expedition is over
+    fn settle(&mut self, won: bool, fleet: Vec<Vessel>) -> Option<Outtro> {
+        let lost = fleet.iter().filter(|v| v.is_sunk()).count();
+        self.fleet = fleet.into_iter().filter(|v| !v.is_sunk()).collect();
+        if !won || self.fleet.is_empty() {
+            return Some(Outtro::new(true, "Your fleet was lost at sea".to_string()));
+        }
+        if let Some(idx) = self.engaged.take() {
+            self.squadrons.remove(idx);
+        }
+        if self.squadrons.is_empty() {
+            return Some(Outtro::new(false, "The seas are clear".to_string()));
+        }
+        let losses = match lost {
+            0 => "without losses".to_string(),
+            1 => "losing one ship".to_string(),
+            n => format!("losing {} ships", n),
+        };
+        self.message = Some(format!("Squadron sunk, {}. {} left.", losses, self.squadrons.len()));
+        None
+    }
+
+    fn sector_rect(sector: usize) -> Rect {
+        let (x, y) = position(sector);
+        Rect::new(
+            MAP_LEFT + x as i32 * SECTOR_SIZE,
+            MAP_TOP + y as i32 * SECTOR_SIZE,
+            SECTOR_SIZE as u32,
+            SECTOR_SIZE as u32,
+        )
+    }
+
+    fn sector_at(x: u32, y: u32) -> Option<usize> {
+        (0..COLS * ROWS).find(|s| Overworld::sector_rect(*s).contains_point(Point::new(x as i32, y as i32)))
+    }
+
+    pub fn render(&self, canvas: &mut Canvas<Window>, resources: &mut HashMap<String, Texture>) {
+        let white = Color::RGB(255, 255, 255);
+        let _ = draw::draw_text(canvas, "Sea Expedition", 16, Point::new(MAP_LEFT, 10), white);
+
+        for (sector, kind) in self.sectors.iter().enumerate() {
+            let rect = Overworld::sector_rect(sector);
+            if !self.seen[sector] {
+                canvas.set_draw_color(Color::RGB(40, 44, 52));
+                let _ = canvas.fill_rect(rect);
+                continue;
+            }
+            if let Some(water) = resources.get("water") {
+                let _ = canvas.copy(water, None, rect);
+            }
+            if *kind != Sector::Sea {
+                let island = Rect::new(rect.x() + 8, rect.y() + 8, rect.width() - 16, rect.height() - 16);
+                canvas.set_draw_color(Color::RGB(194, 178, 128));
+                let _ = canvas.fill_rect(island);
+                if *kind == Sector::Harbour {
+                    let _ = draw::draw_text_centered(canvas, "H", 16, rect.center(), Color::RGB(0, 0, 0));
+                }
+            }
+            // what was seen once but is out of view now stays dimmed
+            if !self.in_sight(sector) {
+                canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
+                canvas.set_draw_color(Color::RGBA(0, 0, 0, 110));
+                let _ = canvas.fill_rect(rect);
+                canvas.set_blend_mode(sdl2::render::BlendMode::None);
+            }
+        }
+
+        for squadron in self.squadrons.iter().filter(|s| self.in_sight(s.sector)) {
+            let rect = Overworld::sector_rect(squadron.sector);
+            canvas.set_draw_color(Color::RGB(220, 40, 40));
+            let _ = canvas.fill_rect(Rect::from_center(rect.center(), 20, 20));
+        }
+        let flagship = Overworld::sector_rect(self.flagship);
+        canvas.set_draw_color(Color::RGB(255, 200, 0));
+        let _ = canvas.fill_rect(Rect::from_center(flagship.center(), 24, 14));
+
+        let afloat: Vec<String> = self
+            .fleet
+            .iter()
+            .map(|v| format!("{}{}", "#".repeat(v.size - v.hits), "x".repeat(v.hits)))
+            .collect();
+        let status = format!("Fleet: {}   Squadrons left: {}", afloat.join(" "), self.squadrons.len());
+        let bottom = MAP_TOP + ROWS as i32 * SECTOR_SIZE;
+        let _ = draw::draw_text(canvas, &status, 14, Point::new(MAP_LEFT, bottom + 10), white);
+        if let Some(message) = &self.message {
+            let _ = draw::draw_text(canvas, message, 14, Point::new(MAP_LEFT, bottom + 32), white);
+        }
+        let help = "Arrows or click: sail   Backspace: back to the title";
+        let _ = draw::draw_text(canvas, help, 12, Point::new(MAP_LEFT, bottom + 56), white);
+    }
+
+    pub fn mouseevent(&mut self, event: MouseEvent) {
+        if let MouseEvent::Click { x, y } = event {
+            if let Some(sector) = Overworld::sector_at(x, y) {
+                self.sail_to(sector);
+            }
+        }
+    }
+
+    pub fn keyevent(&mut self, key: Keycode) {
+        match key {
+            Keycode::Up => self.sail(0, -1),
+            Keycode::Down => self.sail(0, 1),
+            Keycode::Left => self.sail(-1, 0),
+            Keycode::Right => self.sail(1, 0),
+            Keycode::Backspace => self.leave = true,
+            _ => {}
+        }
+    }
+
+    pub fn tick(&mut self) -> Option<Transition> {
+        if self.leave {
+            return Some(Transition::Pop);
+        }
+        // the battle takes the expedition along and brings it back
+        if let Some(enemy) = self.enemy().cloned() {
+            return Some(Transition::Replace(Box::new(Battle::at_sea(self.clone(), enemy))));
+        }
+        None
+    }
+}
+
+impl Scene for Overworld {
+    fn update(&mut self) -> Option<Transition> {
+        self.tick()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas<Window>, resources: &mut HashMap<String, Texture>) {
+        Overworld::render(self, canvas, resources);
+    }
+
+    fn handle_input(&mut self, input: Input) -> Option<Transition> {
+        match input {
+            Input::Key(key) => self.keyevent(key),
+            Input::Mouse(event) => self.mouseevent(event),
+            Input::KeyUp(_) => {}
+        }
+        None
+    }
+
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    fn music(&self) -> Option<Track> {
+        Some(Track::Title)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_sea() -> Overworld {
+        let mut world = Overworld::new(1);
+        world.sectors = vec![Sector::Sea; COLS * ROWS];
+        world.squadrons.clear();
+        world
+    }
+
+    #[test]
+    fn every_squadron_can_be_reached() {
+        for seed in 0..20 {
+            let world = Overworld::new(seed);
+            let reachable = world.reachable();
+            assert_eq!(world.squadrons.len(), SQUADRONS.len());
+            assert!(world.squadrons.iter().all(|s| reachable[s.sector] && !world.in_sight(s.sector)));
+            assert_eq!(world.harbours().count(), HARBOURS);
+        }
+        assert_eq!(Overworld::new(4).sectors, Overworld::new(4).sectors);
+    }
+
+    #[test]
+    fn islands_block_the_way_and_fog_lifts_while_sailing() {
+        let mut world = open_sea();
+        let start = world.flagship;
+        world.sectors[start + 1] = Sector::Island;
+        world.sail(1, 0);
+        assert_eq!(world.flagship, start);
+        // the left edge of the map
+        world.sail(-1, 0);
+        assert_eq!(world.flagship, start);
+
+        assert!(!world.seen[start + SIGHT + 1]);
+        world.sail(0, 1);
+        world.sail(1, 0);
+        assert!(world.seen[start + SIGHT + 1]);
+    }
+
+    #[test]
+    fn meeting_a_squadron_starts_a_battle() {
+        let mut world = open_sea();
+        let start = world.flagship;
+        world.squadrons.push(Squadron {
+            sector: start + 1,
+            name: "Unicorn patrol",
+            ship_sizes: &[2, 3],
+            strategy: "random",
+        });
+        world.sail(1, 0);
+        assert_eq!(world.enemy().map(|s| s.name), Some("Unicorn patrol"));
+        assert!(matches!(world.tick(), Some(Transition::Replace(_))));
+    }
+
+    #[test]
+    fn damage_stays_until_a_harbour_repairs_it() {
+        let mut world = open_sea();
+        for idx in 0..2 {
+            world.squadrons.push(Squadron {
+                sector: idx,
+                name: "Unicorn patrol",
+                ship_sizes: &[2, 3],
+                strategy: "random",
+            });
+        }
+        world.engaged = Some(0);
+        let fleet = vec![Vessel { size: 2, hits: 2 }, Vessel { size: 3, hits: 1 }, Vessel { size: 4, hits: 0 }];
+        assert!(world.settle(true, fleet.clone()).is_none());
+        assert_eq!(world.fleet, vec![Vessel { size: 3, hits: 1 }, Vessel { size: 4, hits: 0 }]);
+        assert_eq!(world.squadrons.len(), 1);
+        assert!(world.engaged.is_none());
+
+        world.sectors[world.flagship + 1] = Sector::Harbour;
+        world.sail(1, 0);
+        assert!(world.fleet.iter().all(|v| v.hits == 0));
+
+        assert!(world.settle(false, fleet).is_some());
+    }
+}
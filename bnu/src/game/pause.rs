@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Point;
+use sdl2::render::{BlendMode, Canvas, Texture};
+use sdl2::video::Window;
+
+use super::title::Title;
+use crate::draw;
+use crate::scene::{Input, Scene, Transition};
+use crate::window_center;
+
+// Drawn over a paused game, which doesn't move until this is popped
+pub struct Pause;
+
+impl Pause {
+    pub fn new() -> Self {
+        Pause
+    }
+}
+
+impl Scene for Pause {
+    fn update(&mut self) -> Option<Transition> {
+        None
+    }
+
+    fn render(&mut self, canvas: &mut Canvas<Window>, _resources: &mut HashMap<String, Texture>) {
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+        let _ = canvas.fill_rect(None);
+        canvas.set_blend_mode(BlendMode::None);
+
+        let white = Color::RGB(255, 255, 255);
+        let center = Point::new(window_center.x as i32, window_center.y as i32);
+        let _ = draw::draw_text_centered(canvas, "Paused", 48, center.offset(0, -40), white);
+        let help = "P or Return: continue   Backspace: back to the title";
+        let _ = draw::draw_text_centered(canvas, help, 16, center.offset(0, 30), white);
+    }
+
+    fn handle_input(&mut self, input: Input) -> Option<Transition> {
+        match input {
+            Input::Key(Keycode::P | Keycode::Return | Keycode::KpEnter) => Some(Transition::Pop),
+            // the game below is kept for later, see SceneStack::apply
+            Input::Key(Keycode::Backspace) => Some(Transition::Reset(Box::new(Title::new()))),
+            _ => None,
+        }
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,343 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use super::rules::Rules;
+
+// A match record is a plain text file, one statement per line:
+//
+//   bns-record 1
+//   rules board=10 ships=2,3,3,4,5 [again=yes]
+//   seed 1234
+//   player 1 human
+//   player 2 unicorn
+//   fleet 1 A1-B1 C3-C5 ...
+//   fleet 2 ...
+//   shot 1 E5 miss
+//   shot 2 B2 hit
+//   shot 2 B3 sunk
+//   winner 2
+//
+// Columns are letters starting at A, rows are numbers starting at 1. Ships
+// are written bow-stern, shots in the order they were fired. Lines starting
+// with '#' are comments.
+
+pub const RECORD_DIR: &str = "records";
+const RECORD_VERSION: u32 = 1;
+const RECORD_HEADER: &str = "bns-record";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ShotOutcome {
+    Miss,
+    Hit,
+    Sunk,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RecordedShot {
+    // 0 for the first player, 1 for the second
+    pub side: usize,
+    pub index: usize,
+    pub outcome: ShotOutcome,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Record {
+    pub rules: Rules,
+    pub seed: u64,
+    pub players: [String; 2],
+    // tile indices of each ship, per side
+    pub fleets: [Vec<Vec<usize>>; 2],
+    pub shots: Vec<RecordedShot>,
+    pub winner: Option<usize>,
+}
+
+pub fn coordinate(index: usize, board_size: usize) -> String {
+    let column = (b'A' + (index % board_size) as u8) as char;
+    format!("{}{}", column, index / board_size + 1)
+}
+
+pub fn parse_coordinate(text: &str, board_size: usize) -> Result<usize, String> {
+    let mut chars = text.chars();
+    let column = chars
+        .next()
+        .ok_or("Empty coordinate".to_string())?
+        .to_ascii_uppercase();
+    let row: usize = chars
+        .as_str()
+        .parse()
+        .map_err(|_| format!("Invalid coordinate '{}'", text))?;
+    let x = (column as usize).wrapping_sub('A' as usize);
+    if x >= board_size || row == 0 || row > board_size {
+        return Err(format!("Coordinate '{}' is off the board", text));
+    }
+    Ok(x + (row - 1) * board_size)
+}
+
+impl ShotOutcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShotOutcome::Miss => "miss",
+            ShotOutcome::Hit => "hit",
+            ShotOutcome::Sunk => "sunk",
+        }
+    }
+
+    fn parse(text: &str) -> Result<ShotOutcome, String> {
+        match text {
+            "miss" => Ok(ShotOutcome::Miss),
+            "hit" => Ok(ShotOutcome::Hit),
+            "sunk" => Ok(ShotOutcome::Sunk),
+            _ => Err(format!("Unknown shot result '{}'", text)),
+        }
+    }
+}
+
+fn parse_side(text: &str) -> Result<usize, String> {
+    match text {
+        "1" => Ok(0),
+        "2" => Ok(1),
+        _ => Err(format!("Unknown player '{}', expected 1 or 2", text)),
+    }
+}
+
+pub fn parse_ship(text: &str, board_size: usize) -> Result<Vec<usize>, String> {
+    let (bow, stern) = text
+        .split_once('-')
+        .ok_or(format!("Invalid ship '{}'", text))?;
+    let bow = parse_coordinate(bow, board_size)?;
+    let stern = parse_coordinate(stern, board_size)?;
+    let (bx, by) = (bow % board_size, bow / board_size);
+    let (sx, sy) = (stern % board_size, stern / board_size);
+    if by == sy {
+        let step: isize = if sx >= bx { 1 } else { -1 };
+        let len = bx.abs_diff(sx) + 1;
+        Ok((0..len)
+            .map(|i| (bow as isize + step * i as isize) as usize)
+            .collect())
+    } else if bx == sx {
+        let step: isize = if sy >= by { 1 } else { -1 };
+        let len = by.abs_diff(sy) + 1;
+        Ok((0..len)
+            .map(|i| (bow as isize + step * (i * board_size) as isize) as usize)
+            .collect())
+    } else {
+        Err(format!("Ship '{}' is not a straight line", text))
+    }
+}
+
+impl Record {
+    pub fn new(rules: Rules, seed: u64, players: [String; 2]) -> Self {
+        Record {
+            rules,
+            seed,
+            players,
+            fleets: [Vec::new(), Vec::new()],
+            shots: Vec::new(),
+            winner: None,
+        }
+    }
+
+    pub fn file_name(&self) -> String {
+        format!("match-{}.bnr", self.seed)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        fs::write(path, self.to_string()).map_err(|e| e.to_string())
+    }
+
+    pub fn read(path: &Path) -> Result<Record, String> {
+        let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Record::parse(&data)
+    }
+
+    // The most recently written record in RECORD_DIR, if any
+    pub fn latest() -> Option<std::path::PathBuf> {
+        let entries = fs::read_dir(RECORD_DIR).ok()?;
+        entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "bnr"))
+            .max_by_key(|e| e.metadata().and_then(|m| m.modified()).ok())
+            .map(|e| e.path())
+    }
+
+    pub fn parse(data: &str) -> Result<Record, String> {
+        let mut lines = data
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'));
+
+        let header = lines.next().unwrap_or("");
+        match header.split_once(' ') {
+            Some((RECORD_HEADER, v)) if v.trim() == RECORD_VERSION.to_string() => {}
+            Some((RECORD_HEADER, v)) => {
+                return Err(format!(
+                    "Record version {} is not supported, expected {}",
+                    v.trim(),
+                    RECORD_VERSION
+                ))
+            }
+            _ => return Err("This is not a match record".to_string()),
+        }
+
+        let mut record = Record::new(Rules::classic(), 0, [String::new(), String::new()]);
+        for line in lines {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[0] {
+                "rules" => {
+                    for setting in words[1..].iter() {
+                        match setting.split_once('=') {
+                            Some(("board", v)) => {
+                                record.rules.board_size = v
+                                    .parse()
+                                    .map_err(|_| format!("Invalid board size '{}'", v))?
+                            }
+                            Some(("ships", v)) => {
+                                record.rules.ship_sizes = v
+                                    .split(',')
+                                    .map(|s| s.parse().map_err(|_| format!("Invalid ship size '{}'", s)))
+                                    .collect::<Result<Vec<usize>, String>>()?
+                            }
+                            Some(("again", "yes")) => record.rules.hit_again = true,
+                            Some(("again", "no")) => record.rules.hit_again = false,
+                            _ => return Err(format!("Unknown rule '{}'", setting)),
+                        }
+                    }
+                    // fleets and shots are read against these rules
+                    record.rules.validate()?;
+                }
+                "seed" if words.len() == 2 => {
+                    record.seed = words[1]
+                        .parse()
+                        .map_err(|_| format!("Invalid seed '{}'", words[1]))?
+                }
+                "player" if words.len() >= 3 => {
+                    record.players[parse_side(words[1])?] = words[2..].join(" ")
+                }
+                "fleet" if words.len() >= 2 => {
+                    let side = parse_side(words[1])?;
+                    record.fleets[side] = words[2..]
+                        .iter()
+                        .map(|s| parse_ship(s, record.rules.board_size))
+                        .collect::<Result<Vec<Vec<usize>>, String>>()?;
+                }
+                "shot" if words.len() == 4 => record.shots.push(RecordedShot {
+                    side: parse_side(words[1])?,
+                    index: parse_coordinate(words[2], record.rules.board_size)?,
+                    outcome: ShotOutcome::parse(words[3])?,
+                }),
+                "winner" if words.len() == 2 => record.winner = Some(parse_side(words[1])?),
+                _ => return Err(format!("Can't read line '{}'", line)),
+            }
+        }
+        Ok(record)
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let size = self.rules.board_size;
+        writeln!(f, "{} {}", RECORD_HEADER, RECORD_VERSION)?;
+        let ships: Vec<String> = self.rules.ship_sizes.iter().map(|s| s.to_string()).collect();
+        write!(f, "rules board={} ships={}", size, ships.join(","))?;
+        // left out when off, so that classic records stay as they were
+        if self.rules.hit_again {
+            write!(f, " again=yes")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "seed {}", self.seed)?;
+        for (side, name) in self.players.iter().enumerate() {
+            writeln!(f, "player {} {}", side + 1, name)?;
+        }
+        for (side, fleet) in self.fleets.iter().enumerate() {
+            write!(f, "fleet {}", side + 1)?;
+            for ship in fleet.iter() {
+                if let (Some(bow), Some(stern)) = (ship.first(), ship.last()) {
+                    write!(f, " {}-{}", coordinate(*bow, size), coordinate(*stern, size))?;
+                }
+            }
+            writeln!(f)?;
+        }
+        for shot in self.shots.iter() {
+            writeln!(
+                f,
+                "shot {} {} {}",
+                shot.side + 1,
+                coordinate(shot.index, size),
+                shot.outcome.name()
+            )?;
+        }
+        if let Some(winner) = self.winner {
+            writeln!(f, "winner {}", winner + 1)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Record {
+        let mut r = Record::new(
+            Rules::classic(),
+            1234,
+            ["human".to_string(), "unicorn".to_string()],
+        );
+        r.fleets[0] = vec![vec![0, 1], vec![22, 32, 42]];
+        r.fleets[1] = vec![vec![99, 98, 97]];
+        r.shots.push(RecordedShot {
+            side: 0,
+            index: 97,
+            outcome: ShotOutcome::Hit,
+        });
+        r.shots.push(RecordedShot {
+            side: 1,
+            index: 5,
+            outcome: ShotOutcome::Miss,
+        });
+        r.winner = Some(0);
+        r
+    }
+
+    #[test]
+    fn coordinates_use_letters_and_numbers() {
+        assert_eq!(coordinate(0, 10), "A1");
+        assert_eq!(coordinate(99, 10), "J10");
+        assert_eq!(parse_coordinate("c4", 10).unwrap(), 32);
+        assert!(parse_coordinate("K1", 10).is_err());
+        assert!(parse_coordinate("A11", 10).is_err());
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let r = sample();
+        let text = r.to_string();
+        assert!(text.contains("fleet 1 A1-B1 C3-C5"));
+        assert!(text.contains("fleet 2 J10-H10"));
+        assert_eq!(Record::parse(&text).unwrap(), r);
+    }
+
+    #[test]
+    fn rule_variants_are_recorded() {
+        let mut r = sample();
+        assert!(!r.to_string().contains("again"));
+        r.rules.hit_again = true;
+        r.rules.ship_sizes = vec![2, 2, 3];
+        assert_eq!(Record::parse(&r.to_string()).unwrap().rules, r.rules);
+    }
+
+    #[test]
+    fn broken_records_are_rejected() {
+        assert!(Record::parse("").is_err());
+        assert!(Record::parse("bns-record 7\n").is_err());
+        assert!(Record::parse("bns-record 1\nshot 3 A1 hit\n").is_err());
+        assert!(Record::parse("bns-record 1\nfleet 1 A1-B2\n").is_err());
+        // the board can't be bigger than the playfield
+        let big = sample().to_string().replace("board=10", "board=12");
+        assert!(Record::parse(&big).unwrap_err().contains("Board size 12"));
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Point;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+
+use super::battle::Battle;
+use super::record::{coordinate, Record, ShotOutcome};
+use super::title::Title;
+use crate::audio::{Cue, Track};
+use crate::draw;
+use crate::scene::{Input, Scene, Transition};
+
+// shots per second
+const SPEEDS: [f32; 4] = [0.5, 1.0, 2.0, 4.0];
+
+// Plays a match record back on a battle that has no players of its own
+pub struct Replay {
+    record: Record,
+    battle: Battle,
+    // number of shots already shown
+    position: usize,
+    playing: bool,
+    speed: usize,
+    last_step: Instant,
+    leave: bool,
+}
+
+impl Replay {
+    pub fn new(record: Record) -> Self {
+        let battle = Battle::from_record(&record);
+        Replay {
+            record,
+            battle,
+            position: 0,
+            playing: true,
+            speed: 1,
+            last_step: Instant::now(),
+            leave: false,
+        }
+    }
+
+    fn describe(&self, position: usize) -> String {
+        let shot = &self.record.shots[position];
+        let outcome = match shot.outcome {
+            ShotOutcome::Miss => "miss",
+            ShotOutcome::Hit => "hit",
+            ShotOutcome::Sunk => "sunk",
+        };
+        format!(
+            "Shot {}: {} fires at {} - {}",
+            position + 1,
+            self.record.players[shot.side],
+            coordinate(shot.index, self.record.rules.board_size),
+            outcome
+        )
+    }
+
+    fn step_forward(&mut self) {
+        if self.position >= self.record.shots.len() {
+            return;
+        }
+        let shot = self.record.shots[self.position];
+        self.battle.replay_shot(&shot, true);
+        self.battle.notify(&self.describe(self.position));
+        self.position += 1;
+        self.last_step = Instant::now();
+    }
+
+    // Going back rebuilds the board from the start, without animations
+    fn step_back(&mut self) {
+        let target = self.position.saturating_sub(1);
+        self.battle = Battle::from_record(&self.record);
+        for (idx, shot) in self.record.shots[..target].iter().enumerate() {
+            self.battle.replay_shot(shot, false);
+            if idx + 1 == target {
+                self.battle.notify(&self.describe(idx));
+            }
+        }
+        self.position = target;
+        self.playing = false;
+    }
+
+    pub fn keyevent(&mut self, key: Keycode) {
+        match key {
+            Keycode::Space => self.playing = !self.playing,
+            Keycode::Right => {
+                self.playing = false;
+                self.step_forward();
+            }
+            Keycode::Left => self.step_back(),
+            Keycode::Up => self.speed = (self.speed + 1).min(SPEEDS.len() - 1),
+            Keycode::Down => self.speed = self.speed.saturating_sub(1),
+            Keycode::Backspace => self.leave = true,
+            _ => {}
+        }
+    }
+
+    pub fn take_cues(&mut self) -> Vec<Cue> {
+        self.battle.take_cues()
+    }
+
+    pub fn tick(&mut self) -> Option<Transition> {
+        if self.leave {
+            return Some(Transition::Reset(Box::new(Title::new())));
+        }
+        self.battle.tick_effects();
+        let interval = 1.0 / SPEEDS[self.speed];
+        if self.playing
+            && !self.battle.is_animating()
+            && self.last_step.elapsed().as_secs_f32() >= interval
+        {
+            self.step_forward();
+            if self.position >= self.record.shots.len() {
+                self.playing = false;
+            }
+        }
+        None
+    }
+
+    pub fn render(&self, canvas: &mut Canvas<Window>, resources: &mut HashMap<String, Texture>) {
+        self.battle.render(canvas, resources);
+        let winner = match self.record.winner {
+            Some(side) if self.position >= self.record.shots.len() => {
+                format!(" - {} won", self.record.players[side])
+            }
+            _ => String::new(),
+        };
+        let status = format!(
+            "Replay {}/{}{}  {}  {}x   Space: play/pause  Left/Right: step  Up/Down: speed  Backspace: leave",
+            self.position,
+            self.record.shots.len(),
+            winner,
+            if self.playing { "playing" } else { "paused" },
+            SPEEDS[self.speed]
+        );
+        let _ = draw::draw_text(canvas, &status, 12, Point::new(10, 570), Color::RGB(255, 255, 255));
+    }
+}
+
+impl Scene for Replay {
+    fn update(&mut self) -> Option<Transition> {
+        self.tick()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas<Window>, resources: &mut HashMap<String, Texture>) {
+        Replay::render(self, canvas, resources);
+    }
+
+    fn handle_input(&mut self, input: Input) -> Option<Transition> {
+        if let Input::Key(key) = input {
+            self.keyevent(key);
+        }
+        None
+    }
+
+    fn music(&self) -> Option<Track> {
+        Some(Track::Battle)
+    }
+
+    fn take_cues(&mut self) -> Vec<Cue> {
+        Replay::take_cues(self)
+    }
+}
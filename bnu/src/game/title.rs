@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+
+use super::battle::{self, Battle};
+use super::campaign::CampaignMap;
+use super::lobby::Lobby;
+use super::overworld::Overworld;
+use super::record::Record;
+use super::replay::Replay;
+use super::MouseEvent;
+use crate::alt;
+use crate::audio::Track;
+use crate::draw;
+use crate::engine::strategy::STRATEGY_NAMES;
+use crate::net;
+use crate::scene::{Input, Scene, Transition};
+use crate::window_center;
+
+const MENU_TOP: i32 = 260;
+const ENTRY_HEIGHT: i32 = 30;
+const ENTRY_WIDTH: i32 = 240;
+// idle time before the demo battle starts
+const ATTRACT_AFTER_SECS: u64 = 10;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Choice {
+    NewGame,
+    Campaign,
+    Expedition,
+    Lander,
+    TwoPlayers,
+    HostGame,
+    JoinGame,
+    Continue,
+    WatchReplay,
+    WatchAis,
+}
+
+impl Choice {
+    fn label(&self) -> &'static str {
+        match self {
+            Choice::NewGame => "New Game",
+            Choice::Campaign => "Campaign",
+            Choice::Expedition => "Sea Expedition",
+            Choice::Lander => "Lunar Lander",
+            Choice::TwoPlayers => "Two Players",
+            Choice::HostGame => "Host Network Game",
+            Choice::JoinGame => "Join Network Game",
+            Choice::Continue => "Continue",
+            Choice::WatchReplay => "Replay Last Match",
+            Choice::WatchAis => "Watch AIs",
+        }
+    }
+}
+
+pub struct Title {
+    entries: Vec<Choice>,
+    selected: usize,
+    chosen: Option<Choice>,
+    error: Option<String>,
+    // the strategies of the AI-vs-AI game, as indices into STRATEGY_NAMES
+    watch: [usize; 2],
+    last_input: Instant,
+}
+
+impl Title {
+    pub fn new() -> Self {
+        Title {
+            entries: Title::menu(),
+            selected: 0,
+            chosen: None,
+            error: None,
+            watch: [0, STRATEGY_NAMES.len() - 1],
+            last_input: Instant::now(),
+        }
+    }
+
+    // Continue and the replay only show up when there is something to see
+    fn menu() -> Vec<Choice> {
+        let mut entries = Vec::new();
+        if battle::save_exists(battle::SAVE_PATH) {
+            entries.push(Choice::Continue);
+        }
+        entries.push(Choice::NewGame);
+        entries.push(Choice::Campaign);
+        entries.push(Choice::Expedition);
+        entries.push(Choice::Lander);
+        entries.push(Choice::TwoPlayers);
+        entries.push(Choice::HostGame);
+        entries.push(Choice::JoinGame);
+        if Record::latest().is_some() {
+            entries.push(Choice::WatchReplay);
+        }
+        entries.push(Choice::WatchAis);
+        entries
+    }
+
+    pub fn with_error(error: String) -> Self {
+        let mut t = Title::new();
+        t.error = Some(error);
+        t
+    }
+
+    fn entry_rect(idx: usize) -> Rect {
+        Rect::new(
+            window_center.x as i32 - ENTRY_WIDTH / 2,
+            MENU_TOP + idx as i32 * ENTRY_HEIGHT,
+            ENTRY_WIDTH as u32,
+            (ENTRY_HEIGHT - 8) as u32,
+        )
+    }
+
+    fn label(&self, entry: Choice) -> String {
+        match entry {
+            Choice::WatchAis => format!(
+                "{}: {} vs {}",
+                entry.label(),
+                STRATEGY_NAMES[self.watch[0]],
+                STRATEGY_NAMES[self.watch[1]]
+            ),
+            _ => entry.label().to_string(),
+        }
+    }
+
+    fn entry_at(&self, x: u32, y: u32) -> Option<usize> {
+        (0..self.entries.len()).find(|idx| Title::entry_rect(*idx).contains_point(Point::new(x as i32, y as i32)))
+    }
+
+    pub fn render(&self, canvas: &mut Canvas<Window>, resources: &HashMap<String, Texture>) {
+        let white = Color::RGB(255, 255, 255);
+        if let Some(uni) = resources.get("unicorn") {
+            let _ = canvas.copy(uni, None, Rect::new(window_center.x as i32 - 75, 100, 150, 150));
+        }
+        let _ = draw::draw_text_centered(
+            canvas,
+            "Battleships... against a unicorn",
+            32,
+            Point::new(window_center.x as i32, 60),
+            white,
+        );
+
+        for (idx, entry) in self.entries.iter().enumerate() {
+            let rect = Title::entry_rect(idx);
+            let color = if idx == self.selected {
+                Color::RGB(255, 200, 0)
+            } else {
+                white
+            };
+            canvas.set_draw_color(color);
+            let _ = canvas.draw_rect(rect);
+            let _ = draw::draw_text_centered(canvas, &self.label(*entry), 20, rect.center(), color);
+        }
+        if self.entries[self.selected] == Choice::WatchAis {
+            let below = Title::entry_rect(self.entries.len()).center();
+            let _ = draw::draw_text_centered(canvas, "Left/Right change the AIs", 14, below, white);
+        }
+
+        if let Some(error) = &self.error {
+            let _ = draw::draw_text_centered(
+                canvas,
+                error,
+                14,
+                Point::new(window_center.x as i32, 588),
+                Color::RGB(255, 64, 64),
+            );
+        }
+    }
+
+    pub fn mouseevent(&mut self, event: MouseEvent) {
+        self.last_input = Instant::now();
+        match event {
+            MouseEvent::Motion { x, y } => {
+                if let Some(idx) = self.entry_at(x, y) {
+                    self.selected = idx;
+                }
+            }
+            MouseEvent::Click { x, y } => {
+                if let Some(idx) = self.entry_at(x, y) {
+                    self.chosen = Some(self.entries[idx]);
+                }
+            }
+        }
+    }
+
+    pub fn keyevent(&mut self, key: Keycode) {
+        self.last_input = Instant::now();
+        let watching = self.entries[self.selected] == Choice::WatchAis;
+        match key {
+            Keycode::Left if watching => self.watch[0] = (self.watch[0] + 1) % STRATEGY_NAMES.len(),
+            Keycode::Right if watching => self.watch[1] = (self.watch[1] + 1) % STRATEGY_NAMES.len(),
+            Keycode::Up => self.selected = self.selected.saturating_sub(1),
+            Keycode::Down => self.selected = (self.selected + 1).min(self.entries.len() - 1),
+            Keycode::Return | Keycode::KpEnter => self.chosen = Some(self.entries[self.selected]),
+            _ => {}
+        }
+    }
+
+    pub fn tick(&mut self) -> Option<Transition> {
+        let next: Box<dyn Scene> = match self.chosen.take() {
+            Some(Choice::NewGame) => Box::new(Battle::new()),
+            // menus of their own, Backspace comes back here
+            Some(Choice::Campaign) => return Some(Transition::Push(Box::new(CampaignMap::new()))),
+            Some(Choice::Expedition) => return Some(Transition::Push(Box::new(Overworld::new(rand::random())))),
+            Some(Choice::Lander) => return Some(Transition::Push(Box::new(alt::Lander::new()))),
+            Some(Choice::TwoPlayers) => Box::new(Battle::hot_seat()),
+            Some(Choice::HostGame) => Lobby::host(net::DEFAULT_PORT),
+            Some(Choice::JoinGame) => Lobby::join(None),
+            Some(Choice::Continue) => match Battle::load(battle::SAVE_PATH) {
+                Ok(b) => Box::new(b),
+                Err(e) => return self.fail(format!("Could not continue: {}", e)),
+            },
+            Some(Choice::WatchReplay) => match Record::latest().ok_or("No match recorded".to_string()).and_then(|p| Record::read(&p)) {
+                Ok(r) => Box::new(Replay::new(r)),
+                Err(e) => return self.fail(format!("Could not open the replay: {}", e)),
+            },
+            Some(Choice::WatchAis) => {
+                let players = [STRATEGY_NAMES[self.watch[0]], STRATEGY_NAMES[self.watch[1]]];
+                match Battle::spectate(players, rand::random()) {
+                    Ok(b) => Box::new(b),
+                    Err(e) => return self.fail(e),
+                }
+            }
+            None if self.last_input.elapsed().as_secs() >= ATTRACT_AFTER_SECS => Box::new(Battle::attract()),
+            None => return None,
+        };
+        Some(Transition::Replace(next))
+    }
+
+    fn fail(&mut self, error: String) -> Option<Transition> {
+        self.error = Some(error);
+        None
+    }
+}
+
+impl Scene for Title {
+    fn update(&mut self) -> Option<Transition> {
+        self.tick()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas<Window>, resources: &mut HashMap<String, Texture>) {
+        Title::render(self, canvas, resources);
+    }
+
+    fn handle_input(&mut self, input: Input) -> Option<Transition> {
+        match input {
+            Input::Key(key) => self.keyevent(key),
+            Input::Mouse(event) => self.mouseevent(event),
+            Input::KeyUp(_) => {}
+        }
+        None
+    }
+
+    fn on_enter(&mut self) {
+        // back from a menu, the demo waits again
+        self.last_input = Instant::now();
+        // a game left behind may just have been saved
+        self.entries = Title::menu();
+        self.selected = self.selected.min(self.entries.len() - 1);
+    }
+
+    fn music(&self) -> Option<Track> {
+        Some(Track::Title)
+    }
+}
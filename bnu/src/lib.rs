@@ -0,0 +1,3 @@
+// The parts of the game that don't need SDL, shared by the game itself and
+// the headless tools in src/bin
+pub mod engine;
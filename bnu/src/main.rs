@@ -12,8 +12,16 @@ use sdl2::rect::Rect;
 use sdl2::render::{Texture, TextureCreator};
 use sdl2::video::WindowContext;
 
+use bns::engine;
+
+mod alt;
+mod anim;
+mod audio;
 mod draw;
 mod game;
+mod net;
+mod particles;
+mod scene;
 mod vecmath;
 
 pub const window_width: u32 = 800;
@@ -41,6 +49,7 @@ pub fn main() -> Result<(), String> {
     canvas.clear();
     canvas.present();
     let mut event_pump = sdl_context.event_pump()?;
+    let mut audio = audio::Audio::new(&sdl_context);
 
     let texture_c = canvas.texture_creator();
     let uni = texture_c
@@ -49,24 +58,76 @@ pub fn main() -> Result<(), String> {
     let ocean = texture_c
         .load_texture("./assets/water.png")
         .unwrap();
+    let puff = texture_c
+        .load_texture("./assets/puff.png")
+        .unwrap();
 
 
     let mut texture_dict: HashMap<String, Texture> = HashMap::new();
     texture_dict.insert("unicorn".to_string(), uni);
     texture_dict.insert("water".to_string(), ocean);
+    texture_dict.insert("puff".to_string(), puff);
+    // campaign bosses fall back to the unicorn if their picture is missing
+    for file in game::portraits() {
+        if let Ok(portrait) = texture_c.load_texture(format!("./assets/{}", file)) {
+            texture_dict.insert(file.to_string(), portrait);
+        }
+    }
 
 
-    let mut g = game::Game::new(&mut canvas);
+    let mut g = game::Game::new();
+    // bns --replay records/match-123.bnr
+    // bns --host [port]
+    // bns --join host[:port]
+    // bns --bot "python3 mybot.py"
+    // bns --watch parity "bot:python3 mybot.py"
+    // bns --lander
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(pos) = args.iter().position(|a| a == "--replay") {
+        match args.get(pos + 1) {
+            Some(path) => g.start_replay(path),
+            None => println!("--replay needs a match record file"),
+        }
+    }
+    if let Some(pos) = args.iter().position(|a| a == "--bot") {
+        match args.get(pos + 1) {
+            Some(command) => g.start_bot(command),
+            None => println!("--bot needs the command that starts the bot"),
+        }
+    }
+    if let Some(pos) = args.iter().position(|a| a == "--watch") {
+        match (args.get(pos + 1), args.get(pos + 2)) {
+            (Some(first), Some(second)) => g.start_watch([first, second]),
+            _ => println!("--watch needs two AIs, strategies or bot:<command>"),
+        }
+    }
+    if args.iter().any(|a| a == "--lander") {
+        g.start_lander();
+    }
+    if let Some(pos) = args.iter().position(|a| a == "--host") {
+        match args.get(pos + 1).map(|p| p.parse::<u16>()) {
+            Some(Ok(port)) => g.start_host(port),
+            Some(Err(_)) => println!("--host needs a port number"),
+            None => g.start_host(net::DEFAULT_PORT),
+        }
+    }
+    if let Some(pos) = args.iter().position(|a| a == "--join") {
+        match args.get(pos + 1) {
+            Some(address) => g.start_join(address),
+            None => println!("--join needs the address of the host"),
+        }
+    }
 
     loop {
-        if !(do_events(&mut g, &mut event_pump)) {
+        if !(do_events(&mut g, &mut event_pump, &mut audio)) {
+            g.on_quit();
             break;
         }
-        g.tick();
+        g.tick(&mut audio);
 
         canvas.set_draw_color(Color::RGB(0, 0, 0));
         canvas.clear();
-        g.render(&mut canvas, &texture_dict);
+        g.render(&mut canvas, &mut texture_dict, &audio);
         canvas.present();
         ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 30));
     }
@@ -74,7 +135,7 @@ pub fn main() -> Result<(), String> {
     Ok(())
 }
 
-fn do_events(g: &mut game::Game, event_pump: &mut sdl2::EventPump) -> bool {
+fn do_events(g: &mut game::Game, event_pump: &mut sdl2::EventPump, audio: &mut audio::Audio) -> bool {
     for event in event_pump.poll_iter() {
         match event {
             Event::MouseMotion { timestamp, window_id, which, mousestate, x, y, xrel, yrel } =>
@@ -93,34 +154,33 @@ fn do_events(g: &mut game::Game, event_pump: &mut sdl2::EventPump) -> bool {
                 keycode: Some(Keycode::Escape),
                 ..
             } => return false,
+            // while typing, every key goes to the text line
             Event::KeyDown {
-                keycode: Some(Keycode::Space),
+                keycode: Some(key),
                 ..
-            } => {}
-            Event::KeyUp {
-                keycode: Some(Keycode::Space),
+            } if g.is_typing() => g.keyevent(key),
+            Event::KeyDown {
+                keycode: Some(Keycode::M),
                 ..
-            } => {}
-
-            Event::KeyUp {
-                keycode: Some(Keycode::Left),
+            } => audio.toggle_mute(),
+            Event::KeyDown {
+                keycode: Some(Keycode::Plus | Keycode::Equals | Keycode::KpPlus),
                 ..
-            } => {}
-
+            } => audio.change_volume(16),
             Event::KeyDown {
-                keycode: Some(Keycode::Left),
+                keycode: Some(Keycode::Minus | Keycode::KpMinus),
                 ..
-            } => {}
-
+            } => audio.change_volume(-16),
             Event::KeyUp {
-                keycode: Some(Keycode::Right),
+                keycode: Some(key),
                 ..
-            } => {}
+            } => g.keyup(key),
+
 
             Event::KeyDown {
-                keycode: Some(Keycode::Right),
+                keycode: Some(key),
                 ..
-            } => {}
+            } => g.keyevent(key),
             _ => {}
         }
     }
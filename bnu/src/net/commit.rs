@@ -0,0 +1,223 @@
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use super::Outcome;
+
+// Commit-reveal for fleets. Before the first shot each side sends the hash
+// of its salted layout. Once the game is over both sides reveal salt and
+// layout, and every result the other side reported is replayed against it.
+// Nothing in here touches the network, so all of it can be tested directly.
+
+const BOARD_SIZE: usize = 10;
+const DOMAIN: &str = "bns-fleet-v1";
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Verdict {
+    Honest,
+    // carries the reason, shown to the player
+    Disputed(String),
+}
+
+// 16 random bytes as hex, from the OS backed generator so it can't be guessed
+pub fn new_salt() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    to_hex(&bytes)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Ships separated by ';', tiles of a ship by ',', e.g. "0,1;22,32,42"
+pub fn layout_to_string(fleet: &[Vec<usize>]) -> String {
+    fleet
+        .iter()
+        .map(|ship| {
+            ship.iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        })
+        .collect::<Vec<String>>()
+        .join(";")
+}
+
+pub fn layout_from_string(text: &str) -> Result<Vec<Vec<usize>>, String> {
+    text.split(';')
+        .filter(|s| !s.is_empty())
+        .map(|ship| {
+            ship.split(',')
+                .map(|t| t.parse().map_err(|_| format!("Invalid tile '{}'", t)))
+                .collect()
+        })
+        .collect()
+}
+
+pub fn commitment(fleet: &[Vec<usize>], salt: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(DOMAIN.as_bytes());
+    hasher.update(b"|");
+    hasher.update(salt.as_bytes());
+    hasher.update(b"|");
+    hasher.update(layout_to_string(fleet).as_bytes());
+    to_hex(&hasher.finalize())
+}
+
+// Ships must be straight, on the board, not overlapping and match the sizes
+// the rules ask for
+pub fn check_layout(fleet: &[Vec<usize>], ship_sizes: &[usize]) -> Result<(), String> {
+    let mut sizes: Vec<usize> = fleet.iter().map(|s| s.len()).collect();
+    let mut expected = ship_sizes.to_vec();
+    sizes.sort_unstable();
+    expected.sort_unstable();
+    if sizes != expected {
+        return Err(format!("the fleet has ships of size {:?}", sizes));
+    }
+
+    let mut taken = [false; BOARD_SIZE * BOARD_SIZE];
+    for ship in fleet.iter() {
+        for t in ship.iter() {
+            if *t >= taken.len() {
+                return Err(format!("tile {} is off the board", t));
+            }
+            if taken[*t] {
+                return Err(format!("two ships share tile {}", t));
+            }
+            taken[*t] = true;
+        }
+        let mut tiles = ship.clone();
+        tiles.sort_unstable();
+        let horizontal = tiles
+            .windows(2)
+            .all(|w| w[1] == w[0] + 1 && w[1] % BOARD_SIZE != 0);
+        let vertical = tiles.windows(2).all(|w| w[1] == w[0] + BOARD_SIZE);
+        if !horizontal && !vertical {
+            return Err(format!("ship {:?} is not a straight line", ship));
+        }
+    }
+    Ok(())
+}
+
+// Checks a revealed fleet against the commitment sent at the start and
+// replays every result its owner reported, in order. `reported` holds the
+// shots fired at this fleet together with the answer that came back.
+pub fn verify(
+    committed: &str,
+    salt: &str,
+    fleet: &[Vec<usize>],
+    ship_sizes: &[usize],
+    reported: &[(usize, Outcome)],
+) -> Verdict {
+    if commitment(fleet, salt) != committed {
+        return Verdict::Disputed("the revealed fleet doesn't match its commitment".to_string());
+    }
+    if let Err(e) = check_layout(fleet, ship_sizes) {
+        return Verdict::Disputed(format!("illegal fleet, {}", e));
+    }
+    let mut hit = [false; BOARD_SIZE * BOARD_SIZE];
+    for (shot, (index, outcome)) in reported.iter().enumerate() {
+        let ship = fleet.iter().find(|s| s.contains(index));
+        let truth = match ship {
+            // shooting at a tile that was already hit counts as a miss
+            Some(ship) if !hit[*index] => {
+                hit[*index] = true;
+                if ship.iter().all(|t| hit[*t]) {
+                    Outcome::Sunk(ship.clone())
+                } else {
+                    Outcome::Hit
+                }
+            }
+            _ => Outcome::Miss,
+        };
+        if truth != *outcome {
+            return Verdict::Disputed(format!(
+                "shot {} at tile {} was reported as {:?} but was {:?}",
+                shot + 1,
+                index,
+                outcome,
+                truth
+            ));
+        }
+    }
+    Verdict::Honest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZES: [usize; 2] = [2, 3];
+
+    fn fleet() -> Vec<Vec<usize>> {
+        vec![vec![0, 1], vec![22, 32, 42]]
+    }
+
+    fn honest_results() -> Vec<(usize, Outcome)> {
+        vec![
+            (5, Outcome::Miss),
+            (0, Outcome::Hit),
+            (0, Outcome::Miss),
+            (1, Outcome::Sunk(vec![0, 1])),
+            (32, Outcome::Hit),
+        ]
+    }
+
+    #[test]
+    fn honest_game_verifies() {
+        let salt = new_salt();
+        let c = commitment(&fleet(), &salt);
+        assert_eq!(verify(&c, &salt, &fleet(), &SIZES, &honest_results()), Verdict::Honest);
+    }
+
+    #[test]
+    fn layout_string_round_trip() {
+        let text = layout_to_string(&fleet());
+        assert_eq!(text, "0,1;22,32,42");
+        assert_eq!(layout_from_string(&text).unwrap(), fleet());
+    }
+
+    #[test]
+    fn changed_fleet_or_salt_is_disputed() {
+        let c = commitment(&fleet(), "salt");
+        let moved = vec![vec![0, 1], vec![23, 33, 43]];
+        assert!(matches!(verify(&c, "salt", &moved, &SIZES, &[]), Verdict::Disputed(_)));
+        assert!(matches!(verify(&c, "pepper", &fleet(), &SIZES, &[]), Verdict::Disputed(_)));
+    }
+
+    #[test]
+    fn lies_about_results_are_disputed() {
+        let c = commitment(&fleet(), "salt");
+        let lies = [
+            // a miss that was a hit
+            vec![(0, Outcome::Miss)],
+            // a hit on open water
+            vec![(5, Outcome::Hit)],
+            // sunk too early
+            vec![(0, Outcome::Sunk(vec![0, 1]))],
+            // sunk but not admitted
+            vec![(0, Outcome::Hit), (1, Outcome::Hit)],
+        ];
+        for lie in lies.iter() {
+            assert!(matches!(verify(&c, "salt", &fleet(), &SIZES, lie), Verdict::Disputed(_)));
+        }
+    }
+
+    #[test]
+    fn illegal_layouts_are_disputed() {
+        let layouts = [
+            // overlapping
+            vec![vec![0, 1], vec![1, 11, 21]],
+            // wrapping around the edge
+            vec![vec![9, 10], vec![22, 32, 42]],
+            // diagonal
+            vec![vec![0, 11], vec![22, 32, 42]],
+            // wrong sizes
+            vec![vec![0, 1], vec![22, 32]],
+        ];
+        for layout in layouts.iter() {
+            let c = commitment(layout, "salt");
+            assert!(matches!(verify(&c, "salt", layout, &SIZES, &[]), Verdict::Disputed(_)));
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+pub mod commit;
+
+// Both sides talk a line based text protocol over TCP:
+//
+//   hello <version> <name>      first message of both sides
+//   commit <sha256 hex>         commitment to the sender's fleet, see commit.rs
+//   ready                       the sender's fleet is placed
+//   fire <index>                the sender shoots at tile <index>
+//   result <index> miss|hit     answer to a fire
+//   result <index> sunk <t,t,t> answer to a fire that sank the ship on <t,t,t>
+//   chat <text>
+//   resign
+//   reveal <salt> <layout>      the sender's fleet once the game is over
+//
+// Tiles are indices into the 10x10 board, row by row. A peer announcing a
+// different version in its hello is turned away.
+
+pub const PROTOCOL_VERSION: u32 = 2;
+pub const DEFAULT_PORT: u16 = 4711;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// more than this without a newline is not our protocol
+const MAX_LINE: usize = 4096;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Outcome {
+    Miss,
+    Hit,
+    // carries all tiles of the ship that went down
+    Sunk(Vec<usize>),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Message {
+    Hello { version: u32, name: String },
+    Commit(String),
+    Ready,
+    Fire { index: usize },
+    Result { index: usize, outcome: Outcome },
+    Chat(String),
+    Resign,
+    Reveal { salt: String, fleet: Vec<Vec<usize>> },
+}
+
+fn parse_index(text: &str) -> Result<usize, String> {
+    match text.parse::<usize>() {
+        Ok(i) if i < 100 => Ok(i),
+        _ => Err(format!("Invalid tile '{}'", text)),
+    }
+}
+
+impl Message {
+    pub fn to_line(&self) -> String {
+        match self {
+            Message::Hello { version, name } => format!("hello {} {}", version, name),
+            Message::Commit(hash) => format!("commit {}", hash),
+            Message::Ready => "ready".to_string(),
+            Message::Fire { index } => format!("fire {}", index),
+            Message::Result { index, outcome } => match outcome {
+                Outcome::Miss => format!("result {} miss", index),
+                Outcome::Hit => format!("result {} hit", index),
+                Outcome::Sunk(tiles) => {
+                    let tiles: Vec<String> = tiles.iter().map(|t| t.to_string()).collect();
+                    format!("result {} sunk {}", index, tiles.join(","))
+                }
+            },
+            // chat must stay on one line
+            Message::Chat(text) => format!("chat {}", text.replace(['\r', '\n'], " ")),
+            Message::Resign => "resign".to_string(),
+            Message::Reveal { salt, fleet } => {
+                format!("reveal {} {}", salt, commit::layout_to_string(fleet))
+            }
+        }
+    }
+
+    pub fn parse(line: &str) -> Result<Message, String> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let words: Vec<&str> = rest.split_whitespace().collect();
+        match (command, words.as_slice()) {
+            ("hello", [version, name @ ..]) => Ok(Message::Hello {
+                version: version
+                    .parse()
+                    .map_err(|_| format!("Invalid version '{}'", version))?,
+                name: name.join(" "),
+            }),
+            ("commit", [hash]) if hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()) => {
+                Ok(Message::Commit(hash.to_string()))
+            }
+            ("ready", []) => Ok(Message::Ready),
+            ("fire", [index]) => Ok(Message::Fire {
+                index: parse_index(index)?,
+            }),
+            ("result", [index, "miss"]) => Ok(Message::Result {
+                index: parse_index(index)?,
+                outcome: Outcome::Miss,
+            }),
+            ("result", [index, "hit"]) => Ok(Message::Result {
+                index: parse_index(index)?,
+                outcome: Outcome::Hit,
+            }),
+            ("result", [index, "sunk", tiles]) => Ok(Message::Result {
+                index: parse_index(index)?,
+                outcome: Outcome::Sunk(
+                    tiles
+                        .split(',')
+                        .map(parse_index)
+                        .collect::<Result<Vec<usize>, String>>()?,
+                ),
+            }),
+            ("chat", _) => Ok(Message::Chat(rest.to_string())),
+            ("resign", []) => Ok(Message::Resign),
+            ("reveal", [salt, layout]) => Ok(Message::Reveal {
+                salt: salt.to_string(),
+                fleet: commit::layout_from_string(layout)?,
+            }),
+            _ => Err(format!("Unknown message '{}'", line)),
+        }
+    }
+}
+
+// One end of a game connection. Reading never blocks, so it can be polled
+// once per frame; a slow peer just means no messages yet.
+pub struct Connection {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    peer: String,
+}
+
+impl Connection {
+    fn from_stream(stream: TcpStream) -> Result<Connection, String> {
+        stream.set_nonblocking(true).map_err(|e| e.to_string())?;
+        let _ = stream.set_nodelay(true);
+        let peer = stream
+            .peer_addr()
+            .map(|a| a.to_string())
+            .unwrap_or_else(|_| "unknown".to_string());
+        Ok(Connection {
+            stream,
+            buffer: Vec::new(),
+            peer,
+        })
+    }
+
+    // Joins a host given as "host:port", the default port is used if none is given
+    pub fn connect(address: &str) -> Result<Connection, String> {
+        let address = if address.contains(':') {
+            address.to_string()
+        } else {
+            format!("{}:{}", address, DEFAULT_PORT)
+        };
+        let addrs: Vec<SocketAddr> = address
+            .to_socket_addrs()
+            .map_err(|e| format!("Can't resolve {}: {}", address, e))?
+            .collect();
+        let mut last_error = format!("No address found for {}", address);
+        for addr in addrs {
+            match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                Ok(stream) => return Connection::from_stream(stream),
+                Err(e) => last_error = format!("Can't connect to {}: {}", addr, e),
+            }
+        }
+        Err(last_error)
+    }
+
+    pub fn peer(&self) -> &str {
+        &self.peer
+    }
+
+    pub fn send(&mut self, message: &Message) -> Result<(), String> {
+        let line = message.to_line() + "\n";
+        // the socket is non-blocking, but a short line always fits the send
+        // buffer unless the peer stopped reading entirely
+        let mut data = line.as_bytes();
+        while !data.is_empty() {
+            match self.stream.write(data) {
+                Ok(0) => return Err("Connection closed".to_string()),
+                Ok(n) => data = &data[n..],
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    std::thread::sleep(Duration::from_millis(1))
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(format!("Connection lost: {}", e)),
+            }
+        }
+        Ok(())
+    }
+
+    // All complete messages received since the last call. An error means the
+    // connection is gone or the peer speaks something else.
+    pub fn poll(&mut self) -> Result<Vec<Message>, String> {
+        let mut chunk = [0u8; 1024];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err("The opponent closed the connection".to_string()),
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(format!("Connection lost: {}", e)),
+            }
+        }
+
+        let mut messages = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            if line.trim().is_empty() {
+                continue;
+            }
+            messages.push(Message::parse(&line)?);
+        }
+        if self.buffer.len() > MAX_LINE {
+            return Err("The opponent sent garbage".to_string());
+        }
+        Ok(messages)
+    }
+}
+
+// Waits for the other side to join, without blocking the frame loop
+pub struct Host {
+    listener: TcpListener,
+}
+
+impl Host {
+    pub fn bind(port: u16) -> Result<Host, String> {
+        let listener = TcpListener::bind(("0.0.0.0", port))
+            .map_err(|e| format!("Can't listen on port {}: {}", port, e))?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        Ok(Host { listener })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().map(|a| a.port()).unwrap_or(0)
+    }
+
+    pub fn accept(&self) -> Result<Option<Connection>, String> {
+        match self.listener.accept() {
+            Ok((stream, _)) => Connection::from_stream(stream).map(Some),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poll_until(c: &mut Connection, count: usize) -> Vec<Message> {
+        let mut received = Vec::new();
+        for _ in 0..500 {
+            received.extend(c.poll().unwrap());
+            if received.len() >= count {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        received
+    }
+
+    fn pair() -> (Connection, Connection) {
+        let host = Host::bind(0).unwrap();
+        let guest = Connection::connect(&format!("127.0.0.1:{}", host.port())).unwrap();
+        for _ in 0..500 {
+            if let Some(c) = host.accept().unwrap() {
+                return (c, guest);
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        panic!("nobody joined");
+    }
+
+    #[test]
+    fn messages_survive_a_round_trip() {
+        let messages = [
+            Message::Hello {
+                version: PROTOCOL_VERSION,
+                name: "Captain Blue".to_string(),
+            },
+            Message::Ready,
+            Message::Fire { index: 42 },
+            Message::Result {
+                index: 42,
+                outcome: Outcome::Miss,
+            },
+            Message::Result {
+                index: 7,
+                outcome: Outcome::Sunk(vec![6, 7, 8]),
+            },
+            Message::Chat("nice shot".to_string()),
+            Message::Resign,
+            Message::Commit(commit::commitment(&[vec![1, 2]], "salt")),
+            Message::Reveal {
+                salt: "abc123".to_string(),
+                fleet: vec![vec![1, 2], vec![30, 40, 50]],
+            },
+        ];
+        for m in messages.iter() {
+            assert_eq!(&Message::parse(&m.to_line()).unwrap(), m);
+        }
+        assert!(Message::parse("fire 100").is_err());
+        assert!(Message::parse("launch 4").is_err());
+        assert!(Message::parse("commit 1234").is_err());
+        assert!(Message::parse("result 4 sunk 4,x").is_err());
+    }
+
+    #[test]
+    fn localhost_exchange() {
+        let (mut host, mut guest) = pair();
+        guest.send(&Message::Fire { index: 3 }).unwrap();
+        guest.send(&Message::Chat("hi".to_string())).unwrap();
+        let received = poll_until(&mut host, 2);
+        assert_eq!(
+            received,
+            vec![Message::Fire { index: 3 }, Message::Chat("hi".to_string())]
+        );
+    }
+
+    #[test]
+    fn disconnect_is_reported() {
+        let (mut host, guest) = pair();
+        drop(guest);
+        let mut result = Ok(Vec::new());
+        for _ in 0..500 {
+            result = host.poll();
+            if result.is_err() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use rand::prelude::*;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas, Texture};
+use sdl2::video::Window;
+
+use crate::vecmath::{TransformationMatrix, Vec2d};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Look {
+    // A filled square of the particle's current size
+    Point,
+    // A texture from the resource dictionary, tinted with the particle's color
+    Texture(&'static str),
+}
+
+#[derive(Clone)]
+pub struct EmitterConfig {
+    // particles per second while the emitter is active
+    pub spawn_rate: f32,
+    // particles spawned at once, when the emitter is created
+    pub burst: usize,
+    // how long the emitter keeps spawning, None means until stopped
+    pub active_for: Option<f32>,
+    // min/max lifetime of a single particle in seconds
+    pub lifetime: (f32, f32),
+    // min/max speed in pixels per second
+    pub speed: (f32, f32),
+    // mean direction of emission in radians, and the total angle around it
+    pub direction: f32,
+    pub spread: f32,
+    // pixels per second per second
+    pub gravity: Vec2d,
+    pub start_color: Color,
+    pub end_color: Color,
+    pub start_size: f32,
+    pub end_size: f32,
+    pub look: Look,
+    pub blend: BlendMode,
+}
+
+impl EmitterConfig {
+    pub fn splash() -> Self {
+        EmitterConfig {
+            spawn_rate: 0.0,
+            burst: 30,
+            active_for: Some(0.0),
+            lifetime: (0.4, 0.8),
+            speed: (40.0, 110.0),
+            direction: -PI / 2.0,
+            spread: PI / 2.0,
+            gravity: Vec2d::new(0.0, 300.0),
+            start_color: Color::RGBA(220, 240, 255, 255),
+            end_color: Color::RGBA(120, 170, 255, 0),
+            start_size: 3.0,
+            end_size: 1.0,
+            look: Look::Point,
+            blend: BlendMode::Blend,
+        }
+    }
+
+    pub fn explosion() -> Self {
+        EmitterConfig {
+            spawn_rate: 0.0,
+            burst: 40,
+            active_for: Some(0.0),
+            lifetime: (0.3, 0.7),
+            speed: (30.0, 140.0),
+            direction: 0.0,
+            spread: 2.0 * PI,
+            gravity: Vec2d::new(0.0, 80.0),
+            start_color: Color::RGBA(255, 240, 160, 255),
+            end_color: Color::RGBA(200, 40, 0, 0),
+            start_size: 4.0,
+            end_size: 1.0,
+            look: Look::Point,
+            blend: BlendMode::Add,
+        }
+    }
+
+    pub fn smoke() -> Self {
+        EmitterConfig {
+            spawn_rate: 12.0,
+            burst: 4,
+            active_for: Some(2.5),
+            lifetime: (1.0, 2.0),
+            speed: (5.0, 20.0),
+            direction: -PI / 2.0,
+            spread: PI / 3.0,
+            gravity: Vec2d::new(4.0, -10.0),
+            start_color: Color::RGBA(90, 90, 90, 180),
+            end_color: Color::RGBA(50, 50, 50, 0),
+            start_size: 6.0,
+            end_size: 20.0,
+            look: Look::Texture("puff"),
+            blend: BlendMode::Blend,
+        }
+    }
+
+    // Points along +y of the emitter direction, rotate via `direction`
+    pub fn exhaust() -> Self {
+        EmitterConfig {
+            spawn_rate: 120.0,
+            burst: 0,
+            active_for: None,
+            lifetime: (0.15, 0.4),
+            speed: (60.0, 120.0),
+            direction: PI / 2.0,
+            spread: PI / 8.0,
+            gravity: Vec2d::default(),
+            start_color: Color::RGBA(255, 230, 150, 255),
+            end_color: Color::RGBA(255, 60, 0, 0),
+            start_size: 3.0,
+            end_size: 1.0,
+            look: Look::Point,
+            blend: BlendMode::Add,
+        }
+    }
+
+    pub fn debris() -> Self {
+        EmitterConfig {
+            spawn_rate: 0.0,
+            burst: 60,
+            active_for: Some(0.0),
+            lifetime: (1.0, 3.0),
+            speed: (20.0, 90.0),
+            direction: -PI / 2.0,
+            spread: PI * 1.5,
+            gravity: Vec2d::new(0.0, 9.81 / 24.0 * 60.0),
+            start_color: Color::RGBA(255, 255, 255, 255),
+            end_color: Color::RGBA(120, 120, 120, 0),
+            start_size: 2.0,
+            end_size: 2.0,
+            look: Look::Point,
+            blend: BlendMode::Blend,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Particle {
+    position: Vec2d,
+    velocity: Vec2d,
+    age: f32,
+    lifetime: f32,
+}
+
+impl Particle {
+    fn life_fraction(&self) -> f32 {
+        (self.age / self.lifetime).min(1.0)
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn lerp_color(a: Color, b: Color, t: f32) -> Color {
+    Color::RGBA(
+        lerp(a.r as f32, b.r as f32, t) as u8,
+        lerp(a.g as f32, b.g as f32, t) as u8,
+        lerp(a.b as f32, b.b as f32, t) as u8,
+        lerp(a.a as f32, b.a as f32, t) as u8,
+    )
+}
+
+fn sample(range: (f32, f32)) -> f32 {
+    if range.1 <= range.0 {
+        return range.0;
+    }
+    rand::thread_rng().gen_range(range.0..range.1)
+}
+
+#[derive(Clone)]
+pub struct Emitter {
+    config: EmitterConfig,
+    pub position: Vec2d,
+    particles: Vec<Particle>,
+    // fractional particles that were not spawned yet
+    spawn_debt: f32,
+    age: f32,
+    stopped: bool,
+}
+
+impl Emitter {
+    pub fn new(config: EmitterConfig, position: Vec2d) -> Self {
+        let mut e = Emitter {
+            config,
+            position,
+            particles: Vec::new(),
+            spawn_debt: 0.0,
+            age: 0.0,
+            stopped: false,
+        };
+        for _ in 0..e.config.burst {
+            e.spawn();
+        }
+        e
+    }
+
+    pub fn set_direction(&mut self, direction: f32) {
+        self.config.direction = direction;
+    }
+
+    pub fn stop(&mut self) {
+        self.stopped = true;
+    }
+
+    pub fn restart(&mut self) {
+        self.stopped = false;
+        self.age = 0.0;
+    }
+
+    pub fn is_emitting(&self) -> bool {
+        if self.stopped {
+            return false;
+        }
+        match self.config.active_for {
+            Some(duration) => self.age < duration,
+            None => true,
+        }
+    }
+
+    // An emitter is done once it stopped spawning and all particles died
+    pub fn is_finished(&self) -> bool {
+        !self.is_emitting() && self.particles.is_empty()
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    fn spawn(&mut self) {
+        let angle = self.config.direction + (sample((0.0, 1.0)) - 0.5) * self.config.spread;
+        let speed = sample(self.config.speed);
+        self.particles.push(Particle {
+            position: self.position,
+            velocity: Vec2d::from_angle(angle) * speed,
+            age: 0.0,
+            lifetime: sample(self.config.lifetime).max(0.001),
+        });
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for p in self.particles.iter_mut() {
+            p.velocity = p.velocity + self.config.gravity * dt;
+            p.position = p.position + p.velocity * dt;
+            p.age += dt;
+        }
+        self.particles.retain(|p| p.age < p.lifetime);
+
+        if self.is_emitting() {
+            self.spawn_debt += self.config.spawn_rate * dt;
+            while self.spawn_debt >= 1.0 {
+                self.spawn();
+                self.spawn_debt -= 1.0;
+            }
+        }
+        self.age += dt;
+    }
+
+    pub fn render(
+        &self,
+        canvas: &mut Canvas<Window>,
+        resources: Option<&mut HashMap<String, Texture>>,
+    ) {
+        self.render_through(canvas, resources, &TransformationMatrix::unit());
+    }
+
+    // Particles live in the coordinates `view` maps onto the screen
+    pub fn render_through(
+        &self,
+        canvas: &mut Canvas<Window>,
+        resources: Option<&mut HashMap<String, Texture>>,
+        view: &TransformationMatrix,
+    ) {
+        canvas.set_blend_mode(self.config.blend);
+        let mut texture = match (self.config.look, resources) {
+            (Look::Texture(name), Some(res)) => res.get_mut(name),
+            _ => None,
+        };
+        if let Some(t) = texture.as_mut() {
+            t.set_blend_mode(self.config.blend);
+        }
+
+        for p in self.particles.iter() {
+            let t = p.life_fraction();
+            let color = lerp_color(self.config.start_color, self.config.end_color, t);
+            let size = (lerp(self.config.start_size, self.config.end_size, t) * view.scale_factor()).max(1.0);
+            let position = view.transform(&p.position);
+            let rect = Rect::new(
+                (position.x - size / 2.0) as i32,
+                (position.y - size / 2.0) as i32,
+                size as u32,
+                size as u32,
+            );
+            match texture.as_mut() {
+                Some(tex) => {
+                    tex.set_color_mod(color.r, color.g, color.b);
+                    tex.set_alpha_mod(color.a);
+                    let _ = canvas.copy(tex, None, rect);
+                }
+                None => {
+                    canvas.set_draw_color(color);
+                    let _ = canvas.fill_rect(rect);
+                }
+            }
+        }
+
+        if let Some(t) = texture {
+            t.set_color_mod(255, 255, 255);
+            t.set_alpha_mod(255);
+        }
+    }
+}
+
+// more than enough for a few explosions at once
+const MAX_PARTICLES: usize = 2000;
+
+#[derive(Clone, Default)]
+pub struct ParticleSystem {
+    emitters: Vec<Emitter>,
+}
+
+impl ParticleSystem {
+    pub fn new() -> Self {
+        ParticleSystem {
+            emitters: Vec::new(),
+        }
+    }
+
+    // A burst is dropped while the screen is full of particles already
+    pub fn spawn(&mut self, config: EmitterConfig, position: Vec2d) {
+        if self.particle_count() + config.burst > MAX_PARTICLES {
+            return;
+        }
+        self.emitters.push(Emitter::new(config, position));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.emitters.is_empty()
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.emitters.iter().map(|e| e.particle_count()).sum()
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for e in self.emitters.iter_mut() {
+            e.update(dt);
+        }
+        self.emitters.retain(|e| !e.is_finished());
+    }
+
+    pub fn render(
+        &self,
+        canvas: &mut Canvas<Window>,
+        mut resources: Option<&mut HashMap<String, Texture>>,
+    ) {
+        for e in self.emitters.iter() {
+            e.render(canvas, resources.as_deref_mut());
+        }
+    }
+
+    pub fn render_through(
+        &self,
+        canvas: &mut Canvas<Window>,
+        mut resources: Option<&mut HashMap<String, Texture>>,
+        view: &TransformationMatrix,
+    ) {
+        for e in self.emitters.iter() {
+            e.render_through(canvas, resources.as_deref_mut(), view);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_spawns_immediately() {
+        let e = Emitter::new(EmitterConfig::splash(), Vec2d::default());
+        assert_eq!(e.particle_count(), EmitterConfig::splash().burst);
+    }
+
+    #[test]
+    fn spawn_rate_is_honoured() {
+        let mut config = EmitterConfig::exhaust();
+        config.spawn_rate = 10.0;
+        config.lifetime = (10.0, 10.0);
+        let mut e = Emitter::new(config, Vec2d::default());
+        for _ in 0..10 {
+            e.update(0.1);
+        }
+        assert_eq!(e.particle_count(), 10);
+    }
+
+    #[test]
+    fn gravity_pulls_particles() {
+        let mut config = EmitterConfig::splash();
+        config.burst = 1;
+        config.speed = (0.0, 0.0);
+        config.lifetime = (5.0, 5.0);
+        config.gravity = Vec2d::new(0.0, 10.0);
+        let mut e = Emitter::new(config, Vec2d::default());
+        e.update(1.0);
+        assert_eq!(e.particles[0].velocity.y, 10.0);
+        assert_eq!(e.particles[0].position.y, 10.0);
+    }
+
+    #[test]
+    fn finished_emitters_are_removed() {
+        let mut s = ParticleSystem::new();
+        s.spawn(EmitterConfig::explosion(), Vec2d::default());
+        assert!(!s.is_empty());
+        for _ in 0..10 {
+            s.update(0.1);
+        }
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    fn a_full_screen_drops_new_bursts() {
+        let mut s = ParticleSystem::new();
+        for _ in 0..100 {
+            s.spawn(EmitterConfig::explosion(), Vec2d::default());
+        }
+        assert_eq!(s.particle_count(), MAX_PARTICLES);
+    }
+
+    #[test]
+    fn stopped_emitter_only_waits_for_its_particles() {
+        let mut e = Emitter::new(EmitterConfig::exhaust(), Vec2d::default());
+        e.update(0.1);
+        assert!(e.particle_count() > 0);
+        e.stop();
+        e.update(1.0);
+        assert!(e.is_finished());
+    }
+}
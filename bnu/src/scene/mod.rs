@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas, Texture};
+use sdl2::video::Window;
+
+use crate::audio::{Cue, Track};
+use crate::{window_height, window_width};
+
+// Everything on screen is a scene: menus, battles, the lander, pause and
+// end screens. Scenes sit on a stack, only the top one is updated and gets
+// the input. A scene asks for another one by returning a Transition.
+
+pub enum MouseEvent {
+    Motion { x: u32, y: u32 },
+    Click { x: u32, y: u32 },
+}
+
+pub enum Input {
+    Key(Keycode),
+    KeyUp(Keycode),
+    Mouse(MouseEvent),
+}
+
+pub enum Transition {
+    // the new scene covers the current one, which waits below
+    Push(Box<dyn Scene>),
+    // back to the scene below
+    Pop,
+    Replace(Box<dyn Scene>),
+    // drops every scene, e.g. back to the title from a pause menu
+    Reset(Box<dyn Scene>),
+}
+
+pub trait Scene {
+    fn update(&mut self) -> Option<Transition>;
+    fn render(&mut self, canvas: &mut Canvas<Window>, resources: &mut HashMap<String, Texture>);
+    fn handle_input(&mut self, input: Input) -> Option<Transition>;
+    // the scene became the top one, or is the top one again
+    fn on_enter(&mut self) {}
+    // the scene is covered, replaced or popped
+    fn on_exit(&mut self) {}
+
+    // Drawn over the scene below instead of hiding it
+    fn is_overlay(&self) -> bool {
+        false
+    }
+
+    fn can_pause(&self) -> bool {
+        false
+    }
+
+    // Text entry wants every key, including the ones main handles itself
+    fn is_typing(&self) -> bool {
+        false
+    }
+
+    // None leaves the music to the scenes below
+    fn music(&self) -> Option<Track> {
+        None
+    }
+
+    // sounds to be played by the main loop
+    fn take_cues(&mut self) -> Vec<Cue> {
+        Vec::new()
+    }
+
+    // The window closes, the scene may keep what it needs for later
+    fn on_quit(&mut self) {}
+}
+
+const EFFECT_SECS: f32 = 0.4;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Effect {
+    Fade,
+    // pushing slides forward, popping back
+    Slide { forward: bool },
+}
+
+struct Running {
+    effect: Effect,
+    started: Instant,
+    // the scene that went away, drawn until the effect is over
+    leaving: Option<Box<dyn Scene>>,
+}
+
+impl Running {
+    fn progress(&self) -> f32 {
+        (self.started.elapsed().as_secs_f32() / EFFECT_SECS).min(1.0)
+    }
+}
+
+pub struct SceneStack {
+    scenes: Vec<Box<dyn Scene>>,
+    running: Option<Running>,
+}
+
+impl SceneStack {
+    pub fn new(mut first: Box<dyn Scene>) -> Self {
+        first.on_enter();
+        SceneStack {
+            scenes: vec![first],
+            running: None,
+        }
+    }
+
+    fn top(&self) -> &dyn Scene {
+        self.scenes.last().expect("the scene stack is never empty").as_ref()
+    }
+
+    fn top_mut(&mut self) -> &mut Box<dyn Scene> {
+        self.scenes.last_mut().expect("the scene stack is never empty")
+    }
+
+    // Nothing moves while the screen changes
+    fn is_changing(&self) -> bool {
+        self.running.as_ref().is_some_and(|r| r.progress() < 1.0)
+    }
+
+    pub fn update(&mut self) {
+        if self.is_changing() {
+            return;
+        }
+        self.running = None;
+        if let Some(transition) = self.top_mut().update() {
+            self.apply(transition);
+        }
+    }
+
+    pub fn handle_input(&mut self, input: Input) {
+        if self.is_changing() {
+            return;
+        }
+        if let Some(transition) = self.top_mut().handle_input(input) {
+            self.apply(transition);
+        }
+    }
+
+    pub fn apply(&mut self, transition: Transition) {
+        let (effect, leaving) = match transition {
+            Transition::Push(mut scene) => {
+                self.top_mut().on_exit();
+                scene.on_enter();
+                let effect = if scene.is_overlay() { None } else { Some(Effect::Slide { forward: true }) };
+                self.scenes.push(scene);
+                (effect, None)
+            }
+            // the last scene stays, there is nothing to go back to
+            Transition::Pop if self.scenes.len() == 1 => return,
+            Transition::Pop => {
+                let mut leaving = self.scenes.pop().expect("the scene stack is never empty");
+                leaving.on_exit();
+                self.top_mut().on_enter();
+                let effect = if leaving.is_overlay() { None } else { Some(Effect::Slide { forward: false }) };
+                (effect, Some(leaving))
+            }
+            Transition::Replace(mut scene) => {
+                let mut leaving = self.scenes.pop().expect("the scene stack is never empty");
+                leaving.on_exit();
+                scene.on_enter();
+                self.scenes.push(scene);
+                (Some(Effect::Fade), Some(leaving))
+            }
+            Transition::Reset(mut scene) => {
+                // every scene goes, the top one fades out
+                let leaving = self.drop_all();
+                scene.on_enter();
+                self.scenes.push(scene);
+                (Some(Effect::Fade), Some(leaving))
+            }
+        };
+        self.running = effect.map(|effect| Running {
+            effect,
+            started: Instant::now(),
+            leaving,
+        });
+    }
+
+    // Drops every scene for `scene` at once, without an effect
+    pub fn reset(&mut self, mut scene: Box<dyn Scene>) {
+        self.drop_all();
+        scene.on_enter();
+        self.scenes = vec![scene];
+        self.running = None;
+    }
+
+    // Empties the stack and returns the top scene. The scenes below were
+    // left when something was pushed over them, only the top one exits now.
+    // They didn't ask to go, e.g. a battle under the pause menu, so they keep
+    // what they need, as if the game was closed.
+    fn drop_all(&mut self) -> Box<dyn Scene> {
+        let mut top = self.scenes.pop().expect("the scene stack is never empty");
+        top.on_exit();
+        for s in self.scenes.iter_mut() {
+            s.on_quit();
+        }
+        self.scenes.clear();
+        top
+    }
+
+    pub fn is_typing(&self) -> bool {
+        self.top().is_typing()
+    }
+
+    pub fn can_pause(&self) -> bool {
+        !self.is_changing() && self.top().can_pause()
+    }
+
+    pub fn music(&self) -> Option<Track> {
+        self.scenes.iter().rev().find_map(|s| s.music())
+    }
+
+    // A scene that was just covered or left may still have a sound to stop
+    pub fn take_cues(&mut self) -> Vec<Cue> {
+        let mut cues: Vec<Cue> = self.scenes.iter_mut().flat_map(|s| s.take_cues()).collect();
+        if let Some(leaving) = self.running.as_mut().and_then(|r| r.leaving.as_mut()) {
+            cues.extend(leaving.take_cues());
+        }
+        cues
+    }
+
+    pub fn on_quit(&mut self) {
+        for scene in self.scenes.iter_mut() {
+            scene.on_quit();
+        }
+    }
+
+    pub fn render(&mut self, canvas: &mut Canvas<Window>, resources: &mut HashMap<String, Texture>) {
+        let Some(mut running) = self.running.take() else {
+            SceneStack::render_scenes(&mut self.scenes, canvas, resources);
+            return;
+        };
+        let progress = running.progress();
+        let width = window_width as i32;
+        match (running.effect, running.leaving.as_mut()) {
+            (Effect::Fade, leaving) => {
+                // out with the old scene in the first half, in with the new one
+                let darkness = if progress < 0.5 {
+                    match leaving {
+                        Some(scene) => scene.render(canvas, resources),
+                        None => SceneStack::render_scenes(&mut self.scenes, canvas, resources),
+                    }
+                    progress * 2.0
+                } else {
+                    SceneStack::render_scenes(&mut self.scenes, canvas, resources);
+                    (1.0 - progress) * 2.0
+                };
+                canvas.set_blend_mode(BlendMode::Blend);
+                canvas.set_draw_color(Color::RGBA(0, 0, 0, (darkness * 255.0) as u8));
+                let _ = canvas.fill_rect(None);
+                canvas.set_blend_mode(BlendMode::None);
+            }
+            (Effect::Slide { forward: true }, _) => {
+                let offset = (progress * width as f32) as i32;
+                let below = self.scenes.len() - 1;
+                SceneStack::with_offset(canvas, -offset, |c| SceneStack::render_scenes(&mut self.scenes[..below], c, resources));
+                SceneStack::with_offset(canvas, width - offset, |c| SceneStack::render_scenes(&mut self.scenes, c, resources));
+            }
+            (Effect::Slide { forward: false }, leaving) => {
+                let offset = (progress * width as f32) as i32;
+                SceneStack::with_offset(canvas, offset - width, |c| SceneStack::render_scenes(&mut self.scenes, c, resources));
+                if let Some(scene) = leaving {
+                    SceneStack::with_offset(canvas, offset, |c| scene.render(c, resources));
+                }
+            }
+        }
+        self.running = Some(running);
+    }
+
+    fn with_offset(canvas: &mut Canvas<Window>, x: i32, draw: impl FnOnce(&mut Canvas<Window>)) {
+        canvas.set_viewport(Rect::new(x, 0, window_width, window_height));
+        draw(canvas);
+        canvas.set_viewport(None);
+    }
+
+    // The top scene and every overlay below it, down to the first full scene
+    fn render_scenes(scenes: &mut [Box<dyn Scene>], canvas: &mut Canvas<Window>, resources: &mut HashMap<String, Texture>) {
+        let first = scenes.iter().rposition(|s| !s.is_overlay()).unwrap_or(0);
+        for scene in scenes.iter_mut().skip(first) {
+            scene.render(canvas, resources);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    // Writes what happens to it into a shared log
+    struct Probe {
+        name: &'static str,
+        log: Rc<RefCell<Vec<String>>>,
+        next: Option<Transition>,
+    }
+
+    impl Probe {
+        fn new(name: &'static str, log: &Rc<RefCell<Vec<String>>>) -> Box<Probe> {
+            Box::new(Probe {
+                name,
+                log: log.clone(),
+                next: None,
+            })
+        }
+    }
+
+    impl Scene for Probe {
+        fn update(&mut self) -> Option<Transition> {
+            self.log.borrow_mut().push(format!("update {}", self.name));
+            self.next.take()
+        }
+
+        fn render(&mut self, _canvas: &mut Canvas<Window>, _resources: &mut HashMap<String, Texture>) {}
+
+        fn handle_input(&mut self, _input: Input) -> Option<Transition> {
+            self.log.borrow_mut().push(format!("input {}", self.name));
+            None
+        }
+
+        fn on_enter(&mut self) {
+            self.log.borrow_mut().push(format!("enter {}", self.name));
+        }
+
+        fn on_exit(&mut self) {
+            self.log.borrow_mut().push(format!("exit {}", self.name));
+        }
+
+        fn on_quit(&mut self) {
+            self.log.borrow_mut().push(format!("quit {}", self.name));
+        }
+
+        fn music(&self) -> Option<Track> {
+            if self.name == "title" {
+                Some(Track::Title)
+            } else {
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn scenes_are_pushed_popped_and_replaced() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut stack = SceneStack::new(Probe::new("title", &log));
+        stack.apply(Transition::Push(Probe::new("menu", &log)));
+        assert_eq!(stack.scenes.len(), 2);
+        // the title's music keeps playing under the menu
+        assert_eq!(stack.music(), Some(Track::Title));
+
+        stack.apply(Transition::Replace(Probe::new("battle", &log)));
+        stack.apply(Transition::Pop);
+        stack.apply(Transition::Pop);
+        assert_eq!(stack.scenes.len(), 1);
+        stack.apply(Transition::Reset(Probe::new("end", &log)));
+        assert_eq!(stack.scenes.len(), 1);
+        assert_eq!(
+            *log.borrow(),
+            [
+                "enter title",
+                "exit title",
+                "enter menu",
+                "exit menu",
+                "enter battle",
+                "exit battle",
+                "enter title",
+                "exit title",
+                "enter end"
+            ]
+        );
+    }
+
+    #[test]
+    fn a_reset_exits_every_scene_once() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut stack = SceneStack::new(Probe::new("title", &log));
+        stack.apply(Transition::Push(Probe::new("battle", &log)));
+        stack.apply(Transition::Reset(Probe::new("end", &log)));
+        assert_eq!(
+            *log.borrow(),
+            ["enter title", "exit title", "enter battle", "exit battle", "quit title", "enter end"]
+        );
+    }
+
+    #[test]
+    fn a_reset_from_the_pause_menu_keeps_the_game_below() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut stack = SceneStack::new(Probe::new("title", &log));
+        stack.apply(Transition::Push(Probe::new("battle", &log)));
+        stack.apply(Transition::Push(Probe::new("pause", &log)));
+        stack.apply(Transition::Reset(Probe::new("title", &log)));
+        assert!(log.borrow().contains(&"quit battle".to_string()));
+        assert!(!log.borrow().contains(&"quit pause".to_string()));
+    }
+
+    #[test]
+    fn nothing_moves_while_the_screen_changes() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut stack = SceneStack::new(Probe::new("title", &log));
+        stack.apply(Transition::Replace(Probe::new("battle", &log)));
+        log.borrow_mut().clear();
+        stack.update();
+        stack.handle_input(Input::Key(Keycode::Return));
+        assert!(log.borrow().is_empty());
+
+        stack.running = None;
+        stack.update();
+        stack.handle_input(Input::Key(Keycode::Return));
+        assert_eq!(*log.borrow(), ["update battle", "input battle"]);
+    }
+}
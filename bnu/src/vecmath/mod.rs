@@ -132,10 +132,6 @@ impl TransformationMatrix {
         Self::translation(v.x, v.y)
     }
 
-    pub fn rotation_v(v: Vec2d) -> TransformationMatrix {
-        Self::rotate(v.angle())
-    }
-
     pub fn rotate(angle: f32) -> Self {
         TransformationMatrix {
             m: [
@@ -152,6 +148,11 @@ impl TransformationMatrix {
         }
     }
 
+    // How much longer a line gets, for matrices that scale evenly
+    pub fn scale_factor(&self) -> f32 {
+        (self.m[0][0] * self.m[0][0] + self.m[1][0] * self.m[1][0]).sqrt()
+    }
+
     pub fn transform(&self, v: &Vec2d) -> Vec2d {
         let x = self.m[0][0] * v.x + self.m[0][1] * v.y + self.m[0][2] * 1.0;
         let y = self.m[1][0] * v.x + self.m[1][1] * v.y + self.m[1][2] * 1.0;